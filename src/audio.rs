@@ -16,6 +16,8 @@ pub mod sfx_ids {
 pub struct SfxPlayer {
     sources: Vec<Sfx>,
     stream: Option<(OutputStream, OutputStreamHandle)>,
+    //Volume that sounds are played at, from 0 to 1
+    volume: f32,
 }
 
 fn sfx_from_file(path: &str) -> Result<Sfx, String> {
@@ -56,21 +58,34 @@ impl SfxPlayer {
             Ok((stream, stream_handle)) => Self {
                 sources: load_sounds(),
                 stream: Some((stream, stream_handle)),
+                volume: 1.0,
             },
             Err(msg) => {
                 eprintln!("{msg}");
-                Self {
-                    sources: vec![],
-                    stream: None,
-                }
+                Self::silent()
             }
         }
     }
 
+    //Creates a sound effect player that doesn't output any sound,
+    //this is used when simulating the game without a window
+    pub fn silent() -> Self {
+        Self {
+            sources: vec![],
+            stream: None,
+            volume: 1.0,
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
     pub fn play(&self, index: usize) {
         if let Some((_, stream_handle)) = &self.stream {
             if index < self.sources.len() {
-                let res = stream_handle.play_raw(self.sources[index].clone().convert_samples());
+                let sound = self.sources[index].clone().amplify(self.volume);
+                let res = stream_handle.play_raw(sound.convert_samples());
                 if let Err(msg) = res {
                     eprintln!("{msg}");
                 }
@@ -1,19 +1,35 @@
 use crate::{
     audio::{sfx_ids, SfxPlayer},
+    level::{difficulty::Difficulty, generate_level::TowerSize},
     sprite::{enemy::Enemy, particle::Particle},
     Level, Sprite,
 };
-use cgmath::{Deg, Matrix4, Vector2};
+use cgmath::{Matrix4, Vector2};
 
-use input_config::InputConfig;
+use gamepad::PadInput;
+use hiscore::{HighScoreSort, RunEnd};
+use input_config::{Action, InputConfig, MENU_ACTIONS};
+use profile::Profile;
+use replay::{Replay, ReplayPlayer};
+use settings::{Settings, SETTINGS};
+use stats::Statistics;
 
 pub mod display;
+pub mod gamepad;
 pub mod hiscore;
 pub mod input_config;
 pub mod player;
+pub mod profile;
+pub mod replay;
+pub mod save;
+pub mod settings;
+pub mod stats;
 pub mod update_game;
 
 //Constants
+//Length of a single simulation step in seconds, the game is always
+//simulated in steps of this size so that replays play back the same
+pub const SIM_TIMESTEP: f32 = 1.0 / 120.0;
 //Force of gravity on all sprites
 pub const GRAVITY: f32 = 16.0;
 pub const DEFAULT_PLAYER_HEALTH: i32 = 4;
@@ -22,6 +38,11 @@ pub const ATTACK_COOLDOWN: f32 = 0.5;
 pub const ATTACK_TIMER: f32 = 0.2;
 pub const PLAYER_WIDTH: f32 = 0.625;
 pub const PLAYER_HEIGHT: f32 = 0.8125;
+//Maximum number of digits that can be typed in for the seed
+pub const MAX_SEED_DIGITS: usize = 20;
+pub const DEFAULT_PLAYER_NAME: &str = "player";
+//Maximum number of characters in a player name
+pub const MAX_NAME_LEN: usize = 20;
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub enum GameScreen {
@@ -32,6 +53,11 @@ pub enum GameScreen {
     WinScreen,
     HighScores,
     AboutScreen,
+    StatisticsScreen,
+    AchievementsScreen,
+    ControlsScreen,
+    Settings,
+    NewProfile,
 }
 
 #[derive(Eq, PartialEq)]
@@ -52,6 +78,10 @@ pub struct Player {
     attack_timer: f32,
     pub arrows: u32,
     pub weapon: Weapon,
+    //Highest floor the player has reached, the bottom floor is 0
+    pub highest_floor: u32,
+    //What last hurt the player, used to record the cause of death
+    pub last_hit: RunEnd,
 }
 
 impl Player {
@@ -73,14 +103,17 @@ impl Player {
             attack_timer: 0.0,
             arrows: 3,
             weapon: Weapon::Sword,
+            highest_floor: 0,
+            last_hit: RunEnd::Unknown,
         }
     }
 
     //Returns true if we applied damage to the player, false otherwise
-    pub fn apply_damage(&mut self, amount: i32) -> bool {
+    pub fn apply_damage(&mut self, amount: i32, source: RunEnd) -> bool {
         if self.damage_cooldown <= 0.0 && amount > 0 && self.player_health > 0 {
             self.player_health -= amount;
             self.damage_cooldown = DAMAGE_COOLDOWN;
+            self.last_hit = source;
             return true;
         }
 
@@ -170,12 +203,55 @@ pub struct State {
     pub projectiles: Vec<(Projectile, Sprite)>,
     pub particles: Vec<Particle>,
     pub input: InputConfig,
+    //Seed used to generate the current tower
+    pub seed: u64,
+    //Seed typed in on the main menu, if this is empty then
+    //a random seed is chosen when the game starts
+    pub seed_input: String,
+    //Name of a new profile that is being typed in and
+    //the reason the last profile could not be created
+    pub profile_name_input: String,
+    pub profile_message: String,
+    //Size of the towers that are generated
+    pub tower_size: TowerSize,
+    //Difficulty of the current tower
+    pub difficulty: Difficulty,
+    //Number of simulation steps that have passed in the current run
+    pub tick: u64,
+    //Actions the player has done in the current run
+    pub replay: Replay,
+    //If this is set then the actions come from a replay
+    //instead of the keyboard
+    pub playback: Option<ReplayPlayer>,
+    //Profile that settings, high scores and saved runs are stored in
+    pub profile: Profile,
+    //Name that is saved with new high scores
+    pub player_name: String,
+    //Field the high score table is sorted by
+    pub highscore_sort: HighScoreSort,
+    //Statistics of every run played with the current profile
+    pub stats: Statistics,
+    //Names of the achievements unlocked at the end of the last run
+    pub new_achievements: Vec<&'static str>,
+    //Index in `input_config::ACTIONS` of the action that the next key
+    //pressed on the controls screen is bound to
+    pub rebinding: Option<usize>,
+    //Shown on the controls screen after the bindings are changed
+    pub controls_message: String,
+    //If this is set then the controls screen shows and
+    //rebinds gamepad inputs instead of keys
+    pub controls_gamepad: bool,
+    //Options picked on the settings screen, these are
+    //applied to the window and audio by the main loop
+    pub settings: Settings,
 }
 
 impl State {
     pub fn starting_state() -> Self {
+        let settings = Settings::default();
+        let (win_w, win_h) = settings.resolution;
         Self {
-            perspective: cgmath::perspective(Deg(75.0), 800.0 / 600.0, 0.1, 1000.0),
+            perspective: settings.perspective(win_w as f32 / win_h as f32),
             player: Player::start_state(),
             game_screen: GameScreen::MainMenu,
             level: Level::new(1, 1),
@@ -184,7 +260,118 @@ impl State {
             enemies: vec![],
             projectiles: vec![],
             particles: vec![],
-            input: InputConfig::new("input_settings"),
+            input: InputConfig::new(&Profile::working_dir().path(profile::INPUT_SETTINGS_FILE)),
+            seed: 0,
+            seed_input: String::new(),
+            profile_name_input: String::new(),
+            profile_message: String::new(),
+            tower_size: TowerSize::STANDARD,
+            difficulty: Difficulty::Normal,
+            tick: 0,
+            replay: Replay::new(0, TowerSize::STANDARD, Difficulty::Normal),
+            playback: None,
+            profile: Profile::working_dir(),
+            player_name: DEFAULT_PLAYER_NAME.to_string(),
+            highscore_sort: HighScoreSort::Score,
+            stats: Statistics::default(),
+            new_achievements: vec![],
+            rebinding: None,
+            controls_message: String::new(),
+            controls_gamepad: false,
+            settings,
+        }
+    }
+
+    //Returns the seed typed in by the player or a random seed
+    //if the player did not enter one
+    pub fn choose_seed(&self) -> u64 {
+        self.seed_input.parse().unwrap_or_else(|_| rand::random())
+    }
+
+    //Handle text typed on the main menu, this is used to enter the seed,
+    //and on the new profile screen to enter the name of the profile
+    pub fn handle_text_input(&mut self, ch: char) {
+        if self.game_screen == GameScreen::NewProfile {
+            if (ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+                && self.profile_name_input.len() < profile::MAX_PROFILE_NAME_LEN
+            {
+                self.profile_name_input.push(ch);
+            }
+            return;
+        }
+
+        if self.game_screen != GameScreen::MainMenu
+            || !ch.is_ascii_digit()
+            || self.seed_input.len() >= MAX_SEED_DIGITS
+        {
+            return;
+        }
+
+        //Only accept the digit if the seed still fits in a u64
+        let mut new_input = self.seed_input.clone();
+        new_input.push(ch);
+        if new_input.parse::<u64>().is_ok() {
+            self.seed_input = new_input;
+        }
+    }
+
+    pub fn handle_backspace(&mut self) {
+        if self.game_screen == GameScreen::MainMenu {
+            self.seed_input.pop();
+        } else if self.game_screen == GameScreen::NewProfile {
+            self.profile_name_input.pop();
+        }
+    }
+
+    //Switches to another profile and loads its key bindings,
+    //the name of the profile is saved with high scores
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.input = InputConfig::new(&profile.path(profile::INPUT_SETTINGS_FILE));
+        self.stats = Statistics::load(&profile.path(profile::STATS_FILE));
+        self.settings = Settings::load(&profile.path(profile::SETTINGS_FILE));
+        self.set_player_name(&profile.name);
+        self.profile = profile;
+    }
+
+    //Sets the name saved with high scores, only printable ascii
+    //characters are kept since those are the only ones we can draw
+    pub fn set_player_name(&mut self, name: &str) {
+        let name: String = name
+            .chars()
+            .filter(|ch| ch.is_ascii_graphic() || *ch == ' ')
+            .take(MAX_NAME_LEN)
+            .collect();
+        self.player_name = if name.trim().is_empty() {
+            DEFAULT_PLAYER_NAME.to_string()
+        } else {
+            name.trim().to_string()
+        };
+    }
+
+    //Statistics are only kept for runs the player is playing,
+    //not for replays that are being played back
+    pub fn tracking_stats(&self) -> bool {
+        self.playback.is_none()
+    }
+
+    //Saves the statistics of the current profile
+    pub fn save_stats(&self) {
+        if let Err(msg) = self.stats.save(&self.profile.path(profile::STATS_FILE)) {
+            eprintln!("failed to save statistics: {msg}");
+        }
+    }
+
+    //Changes the setting at this index in `settings::SETTINGS`
+    //and saves the settings of the current profile
+    pub fn change_setting(&mut self, setting_index: usize, direction: i32) {
+        if let Some(setting) = SETTINGS.get(setting_index) {
+            self.settings.change(*setting, direction);
+        }
+        if let Err(msg) = self
+            .settings
+            .save(&self.profile.path(profile::SETTINGS_FILE))
+        {
+            eprintln!("failed to save settings: {msg}");
         }
     }
 
@@ -210,6 +397,9 @@ impl State {
             Weapon::Bow => {
                 let spr = self.player.shoot();
                 if let Some(arrow) = spr {
+                    if self.tracking_stats() {
+                        self.stats.arrows_fired += 1;
+                    }
                     self.projectiles.push((Projectile::Arrow, arrow));
                 }
             }
@@ -231,57 +421,193 @@ impl State {
         }
     }
 
-    pub fn handle_key_press(&mut self, scancode: input_config::KeyId, sfx_player: &SfxPlayer) {
-        let action = self.input.get_action(scancode).unwrap_or("".to_string());
-        if action == "Escape" {
-            match self.game_screen {
-                GameScreen::Game => self.game_screen = GameScreen::Paused,
-                GameScreen::Paused => self.game_screen = GameScreen::Game,
-                _ => self.game_screen = GameScreen::MainMenu,
+    fn handle_action_press(&mut self, action: Action, sfx_player: &SfxPlayer) {
+        match action {
+            Action::Up => self.handle_up_key(sfx_player),
+            Action::Down => self.handle_down_key(),
+            Action::Left => self.set_player_velocity_x(-player::PLAYER_SPEED),
+            Action::Right => self.set_player_velocity_x(player::PLAYER_SPEED),
+            Action::Attack => self.player_attack(),
+            Action::Sword => self.player.weapon = Weapon::Sword,
+            Action::Bow => self.player.weapon = Weapon::Bow,
+            Action::Escape => {}
+        }
+    }
+
+    fn handle_action_release(&mut self, action: Action) {
+        match action {
+            Action::Up | Action::Down if self.player.climbing() => {
+                self.set_player_velocity_y(0.0);
             }
+            Action::Left if self.player_velocity().x < 0.0 => self.set_player_velocity_x(0.0),
+            Action::Right if self.player_velocity().x > 0.0 => self.set_player_velocity_x(0.0),
+            _ => {}
         }
+    }
 
-        //Ignore key presses if we aren't in the actual game
-        if self.game_screen != GameScreen::Game {
-            return;
+    pub fn apply_action(&mut self, action: Action, pressed: bool, sfx_player: &SfxPlayer) {
+        if pressed {
+            self.handle_action_press(action, sfx_player);
+        } else {
+            self.handle_action_release(action);
+        }
+    }
+
+    //Applies an action and saves it in the replay on the current tick
+    pub fn record_action(&mut self, action: Action, pressed: bool, sfx_player: &SfxPlayer) {
+        self.replay.record(self.tick, action, pressed);
+        self.apply_action(action, pressed, sfx_player);
+    }
+
+    //Saves the key bindings to the input settings file of the profile
+    pub fn save_input(&self) {
+        let path = self.profile.path(profile::INPUT_SETTINGS_FILE);
+        if let Err(msg) = self.input.save(&path) {
+            eprintln!("failed to save input settings: {msg}");
         }
+    }
 
-        if action == "Up" {
-            self.handle_up_key(sfx_player);
-        } else if action == "Down" {
-            self.handle_down_key();
-        } else if action == "Left" {
-            self.set_player_velocity_x(-player::PLAYER_SPEED);
-        } else if action == "Right" {
-            self.set_player_velocity_x(player::PLAYER_SPEED);
-        } else if action == "Attack" {
-            self.player_attack();
+    //Binds a key to an action on the controls screen and
+    //reports if the key was taken from another action
+    pub fn bind_key(&mut self, key_id: input_config::KeyId, action_index: usize) {
+        let action = input_config::ACTIONS[action_index];
+        let name = input_config::key_name(key_id);
+        match self.input.last_menu_key(key_id) {
+            Some(previous) if previous != action => self.report_last_input(&name, previous),
+            _ => {
+                let previous = self.input.bind(key_id, action);
+                self.report_binding(&name, previous, action);
+            }
         }
+    }
 
-        if action == "Sword" {
-            self.player.weapon = Weapon::Sword;
-        } else if action == "Bow" {
-            self.player.weapon = Weapon::Bow;
+    pub fn bind_pad_input(&mut self, input: PadInput, action_index: usize) {
+        let action = input_config::ACTIONS[action_index];
+        match self.input.last_menu_pad_input(input) {
+            Some(previous) if previous != action => self.report_last_input(&input.name(), previous),
+            _ => {
+                let previous = self.input.bind_pad(input, action);
+                self.report_binding(&input.name(), previous, action);
+            }
         }
     }
 
-    pub fn handle_key_release(&mut self, scancode: input_config::KeyId) {
-        //Ignore key presses if we aren't in the actual game
-        if self.game_screen != GameScreen::Game {
+    fn report_last_input(&mut self, input_name: &str, action: Action) {
+        self.controls_message = format!(
+            "{input_name} can not be moved, it is the only input for {}",
+            action.name()
+        );
+    }
+
+    fn report_binding(&mut self, input_name: &str, previous: Option<Action>, action: Action) {
+        self.controls_message = match previous {
+            Some(previous) => format!(
+                "{input_name} was moved from {} to {}",
+                previous.name(),
+                action.name()
+            ),
+            None => format!("{input_name} is bound to {}", action.name()),
+        };
+        self.save_input();
+    }
+
+    //Clears the keys or gamepad inputs of an action, depending on which
+    //of them the controls screen is showing. The actions that are used
+    //in the menus can not be cleared.
+    pub fn clear_binding(&mut self, action_index: usize) {
+        let action = input_config::ACTIONS[action_index];
+        if MENU_ACTIONS.contains(&action) {
+            self.controls_message = format!("{} is used in the menus", action.name());
             return;
         }
 
-        let action = self.input.get_action(scancode).unwrap_or("".to_string());
-        if action == "Up" || action == "Down" {
-            if self.player.climbing() {
-                self.set_player_velocity_y(0.0);
+        if self.controls_gamepad {
+            self.input.unbind_pad(action);
+        } else {
+            self.input.unbind(action);
+        }
+        self.controls_message = format!("{} was cleared", action.name());
+        self.save_input();
+    }
+
+    pub fn reset_controls(&mut self) {
+        self.input = InputConfig::default();
+        self.controls_message = "controls were reset".to_string();
+        self.save_input();
+    }
+
+    //Handles a key being pressed, the action it is bound
+    //to is returned so that it can be used in menus
+    pub fn handle_key_press(
+        &mut self,
+        scancode: input_config::KeyId,
+        sfx_player: &SfxPlayer,
+    ) -> Option<Action> {
+        //The controls screen captures the next key that is pressed,
+        //Escape stops waiting for a key without binding anything
+        if self.game_screen == GameScreen::ControlsScreen && self.rebinding.is_some() {
+            if scancode == glfw::Key::Escape as input_config::KeyId {
+                self.rebinding = None;
+                self.controls_message = "rebinding was cancelled".to_string();
+                return None;
+            }
+
+            if !self.controls_gamepad {
+                if let Some(action_index) = self.rebinding.take() {
+                    self.bind_key(scancode, action_index);
+                }
+                return None;
             }
-        } else if action == "Left" {
-            if self.player_velocity().x < 0.0 {
-                self.set_player_velocity_x(0.0);
+        }
+
+        let action = self.input.get_action(scancode)?;
+        self.handle_action_input(action, true, sfx_player);
+        Some(action)
+    }
+
+    pub fn handle_key_release(&mut self, scancode: input_config::KeyId, sfx_player: &SfxPlayer) {
+        if let Some(action) = self.input.get_action(scancode) {
+            self.handle_action_input(action, false, sfx_player);
+        }
+    }
+
+    //Handles a gamepad button or axis being pressed or released, the
+    //action it is bound to is returned so that it can be used in menus
+    pub fn handle_pad_input(
+        &mut self,
+        input: PadInput,
+        pressed: bool,
+        sfx_player: &SfxPlayer,
+    ) -> Option<Action> {
+        //The controls screen captures the next gamepad input that is pressed
+        if pressed && self.game_screen == GameScreen::ControlsScreen && self.controls_gamepad {
+            if let Some(action_index) = self.rebinding.take() {
+                self.bind_pad_input(input, action_index);
+                return None;
             }
-        } else if action == "Right" && self.player_velocity().x > 0.0 {
-            self.set_player_velocity_x(0.0);
         }
+
+        let action = self.input.get_pad_action(input)?;
+        self.handle_action_input(action, pressed, sfx_player);
+        Some(action)
+    }
+
+    //Handles an action from the keyboard or a gamepad
+    fn handle_action_input(&mut self, action: Action, pressed: bool, sfx_player: &SfxPlayer) {
+        if pressed && action == Action::Escape {
+            match self.game_screen {
+                GameScreen::Game => self.game_screen = GameScreen::Paused,
+                GameScreen::Paused => self.game_screen = GameScreen::Game,
+                _ => self.game_screen = GameScreen::MainMenu,
+            }
+        }
+
+        //Ignore key presses if we aren't in the actual game or
+        //if a replay is being played back
+        if self.game_screen != GameScreen::Game || self.playback.is_some() {
+            return;
+        }
+
+        self.record_action(action, pressed, sfx_player);
     }
 }
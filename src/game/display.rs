@@ -1,16 +1,30 @@
 use super::{Player, Projectile, State, Weapon, ATTACK_TIMER, PLAYER_HEIGHT};
 use crate::{
-    gfx::VertexArrayObject, level::display_level::SPRITE_RENDER_DISTANCE, shader::ShaderProgram, ui,
+    gfx::VertexArrayObject,
+    level::{display_level::SPRITE_RENDER_DISTANCE, floor_at},
+    shader::ShaderProgram,
+    ui,
 };
 use cgmath::{Matrix4, Rad};
 
 impl Player {
-    pub fn display_player(&self, rect_vao: &VertexArrayObject, sprite_shader: &ShaderProgram) {
+    //alpha is how far we are between the previous simulation
+    //step and the current one and is used to interpolate positions
+    pub fn display_player(
+        &self,
+        rect_vao: &VertexArrayObject,
+        sprite_shader: &ShaderProgram,
+        alpha: f32,
+    ) {
+        let position = self.player_spr.interpolated_position(alpha);
+        //Offset of the drawn position from the simulated position
+        let offset = position - self.player_spr.position;
+
         //Display the player sprite
         sprite_shader.uniform_bool("uFlipped", self.player_spr.flipped);
         let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-            self.player_spr.position.x,
-            self.player_spr.position.y + (1.0 - PLAYER_HEIGHT) / 2.0,
+            position.x,
+            position.y + (1.0 - PLAYER_HEIGHT) / 2.0,
             0.0,
         )) * Matrix4::from_scale(0.5);
         sprite_shader.uniform_matrix4f("uTransform", &transform_matrix);
@@ -25,6 +39,7 @@ impl Player {
         let attack_hitbox = self.attack_hitbox();
         sprite_shader.uniform_bool("uFlipped", false);
         if let Some(hitbox) = attack_hitbox {
+            let hitbox_pos = hitbox.position + offset;
             let flip_matrix = if self.player_spr.flipped {
                 Matrix4::from_angle_y(Rad(std::f32::consts::PI))
             } else {
@@ -32,7 +47,7 @@ impl Player {
             };
 
             let transform_matrix =
-                Matrix4::from_translation(cgmath::vec3(hitbox.position.x, hitbox.position.y, 0.0))
+                Matrix4::from_translation(cgmath::vec3(hitbox_pos.x, hitbox_pos.y, 0.0))
                     * Matrix4::from_scale(0.35)
                     * flip_matrix
                     * Matrix4::from_translation(cgmath::vec3(-1.0, -1.0, 0.0))
@@ -55,15 +70,15 @@ impl Player {
                 gl::Enable(gl::CULL_FACE);
             }
         } else if self.weapon == Weapon::Bow {
-            let offset = if self.player_spr.flipped {
+            let bow_offset = if self.player_spr.flipped {
                 0.1f32
             } else {
                 -0.1
             };
             sprite_shader.uniform_bool("uFlipped", self.player_spr.flipped);
             let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                self.player_spr.position.x + offset,
-                self.player_spr.position.y - 0.1,
+                position.x + bow_offset,
+                position.y - 0.1,
                 0.0,
             )) * Matrix4::from_scale(0.3);
             sprite_shader.uniform_matrix4f("uTransform", &transform_matrix);
@@ -116,18 +131,64 @@ impl Player {
 }
 
 impl State {
-    pub fn display_enemies(&self, rect_vao: &VertexArrayObject, shader_program: &ShaderProgram) {
+    pub fn display_enemies(
+        &self,
+        rect_vao: &VertexArrayObject,
+        shader_program: &ShaderProgram,
+        alpha: f32,
+    ) {
         for enemy in &self.enemies {
             if (enemy.sprite.position.y - self.player_position().y).abs() > SPRITE_RENDER_DISTANCE {
                 continue;
             }
 
-            enemy.display(rect_vao, shader_program);
+            enemy.display(rect_vao, shader_program, alpha);
+        }
+    }
+
+    //Displays the name and health of the boss on the
+    //floor the player is on at the top of the screen
+    pub fn display_boss_health(
+        &self,
+        rect_vao: &VertexArrayObject,
+        text_shader: &ShaderProgram,
+        window: &glfw::Window,
+    ) {
+        let floor = floor_at(self.player_position().y);
+        let boss = self
+            .enemies
+            .iter()
+            .find(|enemy| enemy.is_boss() && floor_at(enemy.sprite.position.y) == floor);
+
+        if let Some(boss) = boss {
+            let (_, win_h) = window.get_size();
+            let max_health = boss.enemy_type.def().health;
+            ui::display_ascii_text_centered(
+                rect_vao,
+                text_shader,
+                boss.enemy_type.name().as_bytes(),
+                0.0,
+                win_h as f32 / 2.0 - 48.0,
+                8.0,
+            );
+            ui::display_health_bar(
+                rect_vao,
+                text_shader,
+                boss.health,
+                max_health,
+                -(max_health - 1) as f32 * 12.0,
+                win_h as f32 / 2.0 - 80.0,
+            );
         }
     }
 
     //Display particles
-    pub fn display_particles(&self, rect_vao: &VertexArrayObject, shader_program: &ShaderProgram) {
+    pub fn display_particles(
+        &self,
+        rect_vao: &VertexArrayObject,
+        shader_program: &ShaderProgram,
+        alpha: f32,
+    ) {
         shader_program.uniform_bool("uFlipped", false);
 
         for particle in &self.particles {
@@ -137,7 +198,7 @@ impl State {
                 continue;
             }
 
-            particle.display(rect_vao, shader_program);
+            particle.display(rect_vao, shader_program, alpha);
         }
     }
 
@@ -146,8 +207,10 @@ impl State {
         &self,
         rect_vao: &VertexArrayObject,
         shader_program: &ShaderProgram,
+        alpha: f32,
     ) {
         for (projectile_type, spr) in &self.projectiles {
+            let position = spr.interpolated_position(alpha);
             if (spr.position.y - self.player_position().y).abs() > SPRITE_RENDER_DISTANCE {
                 continue;
             }
@@ -156,11 +219,9 @@ impl State {
             //Apply texture
             match *projectile_type {
                 Projectile::Fireball => {
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        spr.position.x,
-                        spr.position.y,
-                        0.0,
-                    )) * Matrix4::from_scale(0.5 * 0.3);
+                    let transform_matrix =
+                        Matrix4::from_translation(cgmath::vec3(position.x, position.y, 0.0))
+                            * Matrix4::from_scale(0.5 * 0.3);
                     shader_program.uniform_matrix4f("uTransform", &transform_matrix);
                     shader_program.uniform_vec2f("uTexOffset", 3.0 / 8.0, 3.0 / 8.0);
                 }
@@ -170,12 +231,10 @@ impl State {
                     } else {
                         -std::f32::consts::PI / 4.0
                     };
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        spr.position.x,
-                        spr.position.y,
-                        0.0,
-                    )) * Matrix4::from_angle_z(Rad(angle))
-                        * Matrix4::from_scale(0.5 * 0.5);
+                    let transform_matrix =
+                        Matrix4::from_translation(cgmath::vec3(position.x, position.y, 0.0))
+                            * Matrix4::from_angle_z(Rad(angle))
+                            * Matrix4::from_scale(0.5 * 0.5);
                     shader_program.uniform_matrix4f("uTransform", &transform_matrix);
                     shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 3.0 / 8.0);
                 }
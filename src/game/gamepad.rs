@@ -0,0 +1,248 @@
+use glfw::{GamepadAxis, GamepadButton, JoystickId};
+
+//Sticks and triggers count as pressed once they are pushed this far
+const AXIS_THRESHOLD: f32 = 0.5;
+const BUTTON_COUNT: usize = 15;
+const AXIS_COUNT: usize = 6;
+const JOYSTICK_COUNT: i32 = 16;
+
+//A button or one direction of an axis on a gamepad,
+//these are bound to actions in the same way as keys
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum PadInput {
+    Button(GamepadButton),
+    AxisPositive(GamepadAxis),
+    AxisNegative(GamepadAxis),
+}
+
+impl PadInput {
+    //Every input of a gamepad, buttons first and then axes
+    pub fn all() -> Vec<Self> {
+        let buttons = (0..BUTTON_COUNT as i32)
+            .filter_map(GamepadButton::from_i32)
+            .map(PadInput::Button);
+        let axes = (0..AXIS_COUNT as i32)
+            .filter_map(GamepadAxis::from_i32)
+            .flat_map(|axis| [PadInput::AxisNegative(axis), PadInput::AxisPositive(axis)]);
+        buttons.chain(axes).collect()
+    }
+
+    //Buttons are named after their variant in `glfw::GamepadButton` and
+    //axes after their variant in `glfw::GamepadAxis` followed by + or -
+    pub fn name(&self) -> String {
+        match self {
+            PadInput::Button(button) => format!("{button:?}"),
+            PadInput::AxisPositive(axis) => format!("{axis:?}+"),
+            PadInput::AxisNegative(axis) => format!("{axis:?}-"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all()
+            .into_iter()
+            .find(|input| input.name().eq_ignore_ascii_case(name))
+    }
+
+    fn is_held(&self, snapshot: &GamepadSnapshot) -> bool {
+        match self {
+            PadInput::Button(button) => snapshot.buttons[*button as usize],
+            PadInput::AxisPositive(axis) => snapshot.axes[*axis as usize] >= AXIS_THRESHOLD,
+            PadInput::AxisNegative(axis) => snapshot.axes[*axis as usize] <= -AXIS_THRESHOLD,
+        }
+    }
+}
+
+//The buttons and axes of a gamepad at one point in time
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct GamepadSnapshot {
+    pub buttons: [bool; BUTTON_COUNT],
+    pub axes: [f32; AXIS_COUNT],
+}
+
+//Where the state of the gamepad comes from, this is glfw when
+//playing the game but it can be replaced to test without a gamepad
+pub trait GamepadSource {
+    //Returns the current state of the gamepad, or None
+    //if no gamepad is connected
+    fn poll(&mut self) -> Option<GamepadSnapshot>;
+}
+
+//Reads the first connected joystick that glfw has a gamepad mapping for
+pub struct GlfwGamepads {
+    glfw: glfw::Glfw,
+}
+
+impl GlfwGamepads {
+    pub fn new(glfw: &glfw::Glfw) -> Self {
+        Self { glfw: glfw.clone() }
+    }
+}
+
+impl GamepadSource for GlfwGamepads {
+    fn poll(&mut self) -> Option<GamepadSnapshot> {
+        let state = (0..JOYSTICK_COUNT)
+            .filter_map(JoystickId::from_i32)
+            .map(|id| self.glfw.get_joystick(id))
+            .filter(|joystick| joystick.is_gamepad())
+            .find_map(|joystick| joystick.get_gamepad_state())?;
+
+        let mut snapshot = GamepadSnapshot::default();
+        for input in PadInput::all() {
+            match input {
+                PadInput::Button(button) => {
+                    snapshot.buttons[button as usize] =
+                        state.get_button_state(button) != glfw::Action::Release;
+                }
+                PadInput::AxisPositive(axis) => snapshot.axes[axis as usize] = state.get_axis(axis),
+                PadInput::AxisNegative(_) => {}
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+//Turns the state of the gamepad on each frame into
+//presses and releases of buttons and axes
+#[derive(Default)]
+pub struct GamepadInput {
+    held: Vec<PadInput>,
+}
+
+impl GamepadInput {
+    //Polls the gamepad and returns every input that was pressed (true)
+    //or released (false) since the last update, if the gamepad was
+    //disconnected then every input that was held is released
+    pub fn update(&mut self, source: &mut dyn GamepadSource) -> Vec<(PadInput, bool)> {
+        let snapshot = source.poll().unwrap_or_default();
+        let held: Vec<PadInput> = PadInput::all()
+            .into_iter()
+            .filter(|input| input.is_held(&snapshot))
+            .collect();
+
+        let mut changes = vec![];
+        for input in &self.held {
+            if !held.contains(input) {
+                changes.push((*input, false));
+            }
+        }
+        for input in &held {
+            if !self.held.contains(input) {
+                changes.push((*input, true));
+            }
+        }
+        self.held = held;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::SfxPlayer,
+        game::{input_config::Action, GameScreen, State},
+        level::{
+            difficulty::Difficulty, generate_level::TowerSize, room_template::load_room_templates,
+            Level,
+        },
+    };
+    use std::collections::VecDeque;
+
+    //Plays back a list of gamepad states, one per poll
+    struct MockGamepad {
+        snapshots: VecDeque<Option<GamepadSnapshot>>,
+    }
+
+    impl GamepadSource for MockGamepad {
+        fn poll(&mut self) -> Option<GamepadSnapshot> {
+            self.snapshots.pop_front().flatten()
+        }
+    }
+
+    impl GamepadSnapshot {
+        fn with_button(mut self, button: GamepadButton) -> Self {
+            self.buttons[button as usize] = true;
+            self
+        }
+
+        fn with_axis(mut self, axis: GamepadAxis, value: f32) -> Self {
+            self.axes[axis as usize] = value;
+            self
+        }
+    }
+
+    #[test]
+    fn test_pad_input_names() {
+        for input in PadInput::all() {
+            assert_eq!(PadInput::from_name(&input.name()), Some(input));
+        }
+        assert_eq!(
+            PadInput::from_name("ButtonA"),
+            Some(PadInput::Button(GamepadButton::ButtonA))
+        );
+        assert_eq!(
+            PadInput::from_name("AxisLeftY-"),
+            Some(PadInput::AxisNegative(GamepadAxis::AxisLeftY))
+        );
+        assert_eq!(PadInput::from_name("AxisLeftY"), None);
+    }
+
+    #[test]
+    fn test_gamepad_presses_and_releases() {
+        let stick_right = GamepadSnapshot::default().with_axis(GamepadAxis::AxisLeftX, 0.9);
+        let mut source = MockGamepad {
+            snapshots: VecDeque::from([
+                Some(stick_right),
+                Some(stick_right.with_button(GamepadButton::ButtonA)),
+                Some(
+                    stick_right
+                        .with_button(GamepadButton::ButtonA)
+                        .with_axis(GamepadAxis::AxisLeftX, 0.2),
+                ),
+                //Disconnected
+                None,
+                None,
+            ]),
+        };
+        let mut input = GamepadInput::default();
+        let right = PadInput::AxisPositive(GamepadAxis::AxisLeftX);
+        let button_a = PadInput::Button(GamepadButton::ButtonA);
+        assert_eq!(input.update(&mut source), vec![(right, true)]);
+        assert_eq!(input.update(&mut source), vec![(button_a, true)]);
+        assert_eq!(input.update(&mut source), vec![(right, false)]);
+        assert_eq!(input.update(&mut source), vec![(button_a, false)]);
+        assert_eq!(input.update(&mut source), vec![]);
+    }
+
+    #[test]
+    fn test_gamepad_controls_player() {
+        let sfx_player = SfxPlayer::silent();
+        let mut state = State::starting_state();
+        let templates = load_room_templates("assets/room_templates");
+        let (level, enemies) =
+            Level::generate_level(&templates, 3, TowerSize::PRACTICE, Difficulty::Normal);
+        state.level = level;
+        state.enemies = enemies;
+        state.game_screen = GameScreen::Game;
+
+        let mut source = MockGamepad {
+            snapshots: VecDeque::from([
+                Some(GamepadSnapshot::default().with_button(GamepadButton::ButtonDpadRight)),
+                Some(GamepadSnapshot::default().with_button(GamepadButton::ButtonStart)),
+            ]),
+        };
+        let mut input = GamepadInput::default();
+        for (pad_input, pressed) in input.update(&mut source) {
+            state.handle_pad_input(pad_input, pressed, &sfx_player);
+        }
+        assert!(state.player_velocity().x > 0.0);
+        assert_eq!(state.replay.events[0].action, Action::Right);
+
+        //Releasing the d-pad stops the player and start pauses the game
+        for (pad_input, pressed) in input.update(&mut source) {
+            state.handle_pad_input(pad_input, pressed, &sfx_player);
+        }
+        assert_eq!(state.player_velocity().x, 0.0);
+        assert!(state.game_screen == GameScreen::Paused);
+    }
+}
@@ -1,15 +1,348 @@
-use crate::{gfx::VertexArrayObject, shader::ShaderProgram, ui};
+use super::{profile, replay::Replay, State, SIM_TIMESTEP};
+use crate::{
+    audio::SfxPlayer,
+    gfx::VertexArrayObject,
+    level::{difficulty::Difficulty, room_template::RoomTemplate, Level},
+    parse::parse_value,
+    shader::ShaderProgram,
+    sprite::enemy::{definition::ENEMY_DEFINITIONS_PATH, EnemyType},
+    ui,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
 use std::{
+    cmp::Reverse,
     fs::File,
     io::{Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 const MAX_HIGHSCORES: usize = 5;
+//Version of the high score file, files without a version
+//are from before high scores had names and only have scores
+const HIGHSCORE_VERSION: u32 = 4;
+//Version 3 files do not have difficulties, those runs were played on normal
+const NO_DIFFICULTY_HIGHSCORE_VERSION: u32 = 3;
+//Version 2 files have names but no replays or checksums
+const UNSIGNED_HIGHSCORE_VERSION: u32 = 2;
+//Entries are signed with the key in this file in the data directory,
+//a random key is made the first time the game is run but it can be
+//replaced so that a team can share a key
+pub const KEY_FILE: &str = "hiscore_key";
+//Number of random bytes in a new key
+const KEY_LEN: usize = 32;
+//Maximum number of characters of a name shown in the high score table
+const MAX_NAME_DISPLAY_LEN: usize = 12;
+
+//How a run ended
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RunEnd {
+    //Scores imported from an old high score file
+    Unknown,
+    Won,
+    Fall,
+    Lava,
+    Spikes,
+    Fireball,
+    Arrow,
+    Enemy(EnemyType),
+}
+
+//Every way a run can end other than being killed by an enemy
+pub const OTHER_RUN_ENDS: [RunEnd; 7] = [
+    RunEnd::Unknown,
+    RunEnd::Won,
+    RunEnd::Fall,
+    RunEnd::Lava,
+    RunEnd::Spikes,
+    RunEnd::Fireball,
+    RunEnd::Arrow,
+];
+
+impl RunEnd {
+    pub fn to_text(self) -> &'static str {
+        match self {
+            RunEnd::Unknown => "unknown",
+            RunEnd::Won => "won",
+            RunEnd::Fall => "fall",
+            RunEnd::Lava => "lava",
+            RunEnd::Spikes => "spikes",
+            RunEnd::Fireball => "fireball",
+            RunEnd::Arrow => "arrow",
+            RunEnd::Enemy(enemy_type) => enemy_type.name(),
+        }
+    }
+
+    pub fn from_text(text: &str) -> Option<Self> {
+        OTHER_RUN_ENDS
+            .into_iter()
+            .find(|end| end.to_text() == text)
+            .or_else(|| EnemyType::from_name(text).map(RunEnd::Enemy))
+    }
+
+    //Text shown in the high score table
+    fn description(self) -> String {
+        match self {
+            RunEnd::Unknown => "-".to_string(),
+            RunEnd::Won => "won".to_string(),
+            _ => format!("killed by {}", self.to_text()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    //Highest floor reached, the bottom floor is 0
+    pub floor: u32,
+    //Length of the run in simulation steps
+    pub ticks: u64,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    //SHA-256 digest of the enemy definitions and the difficulty profile
+    //the run was played with, `-` if they are not known
+    pub rules: String,
+    pub end: RunEnd,
+    //Seconds since the unix epoch, 0 if the date is not known
+    pub date: u64,
+    //SHA-256 digest of the replay of the run, `-` if there is no replay
+    pub replay: String,
+    //False if the checksum of the entry did not match when it was
+    //loaded, these entries were edited or come from an old file
+    pub verified: bool,
+}
+
+impl HighScore {
+    //Creates an entry for a score from an old high score file
+    pub fn imported(score: u32) -> Self {
+        Self {
+            name: "player".to_string(),
+            score,
+            floor: 0,
+            ticks: 0,
+            seed: 0,
+            difficulty: Difficulty::Normal,
+            rules: "-".to_string(),
+            end: RunEnd::Unknown,
+            date: 0,
+            replay: "-".to_string(),
+            verified: false,
+        }
+    }
+
+    //Entries are stored on a single line of the form:
+    //score floor ticks seed end date difficulty rules replay checksum name
+    //the checksum is an HMAC of the rest of the line
+    pub fn to_text(&self, key: Option<&[u8]>) -> String {
+        let checksum = match key {
+            Some(key) if self.verified => self.checksum(key, HIGHSCORE_VERSION),
+            //Entries that failed verification are not signed again
+            //and nothing is signed if there is no key
+            _ => "-".to_string(),
+        };
+        format!(
+            "{} {} {} {} {} {} {} {} {} {checksum} {}",
+            self.score,
+            self.floor,
+            self.ticks,
+            self.seed,
+            self.end.to_text(),
+            self.date,
+            self.difficulty.name(),
+            self.rules,
+            self.replay,
+            self.name
+        )
+    }
+
+    //Returns the checksum of the entry in a file with the given version,
+    //the difficulty and rules are only signed in files that have them
+    fn checksum(&self, key: &[u8], version: u32) -> String {
+        let difficulty = if version == NO_DIFFICULTY_HIGHSCORE_VERSION {
+            String::new()
+        } else {
+            format!(" {} {}", self.difficulty.name(), self.rules)
+        };
+        let signed = format!(
+            "{} {} {} {} {} {}{difficulty} {} {}",
+            self.score,
+            self.floor,
+            self.ticks,
+            self.seed,
+            self.end.to_text(),
+            self.date,
+            self.replay,
+            self.name
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(signed.as_bytes());
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    //Parses an entry from a file with the given version
+    pub fn from_text(text: &str, version: u32, key: Option<&[u8]>) -> Result<Self, String> {
+        let split: Vec<&str> = text.split_whitespace().collect();
+        let (name_index, expected) = match version {
+            UNSIGNED_HIGHSCORE_VERSION => (6, "score floor ticks seed end date name"),
+            NO_DIFFICULTY_HIGHSCORE_VERSION => {
+                (8, "score floor ticks seed end date replay checksum name")
+            }
+            _ => (
+                10,
+                "score floor ticks seed end date difficulty rules replay checksum name",
+            ),
+        };
+        if split.len() <= name_index {
+            return Err(format!("expected: {expected}"));
+        }
+
+        //The fields between the date and the name depend on the version
+        let mut fields = split[6..name_index].iter().copied();
+        let (difficulty, rules) = if name_index == 10 {
+            let name = fields.next().unwrap_or_default();
+            let difficulty =
+                Difficulty::from_name(name).ok_or(format!("invalid difficulty: {name}"))?;
+            (difficulty, fields.next().unwrap_or("-"))
+        } else {
+            (Difficulty::Normal, "-")
+        };
+        let replay = fields.next().unwrap_or("-");
+        let checksum = fields.next();
+
+        let mut highscore = Self {
+            score: parse_value(split[0], "score")?,
+            floor: parse_value(split[1], "floor")?,
+            ticks: parse_value(split[2], "ticks")?,
+            seed: parse_value(split[3], "seed")?,
+            difficulty,
+            rules: rules.to_string(),
+            end: RunEnd::from_text(split[4]).ok_or(format!("invalid end: {}", split[4]))?,
+            date: parse_value(split[5], "date")?,
+            replay: replay.to_string(),
+            //Names can have spaces in them
+            name: split[name_index..].join(" "),
+            verified: false,
+        };
+        highscore.verified = key
+            .zip(checksum)
+            .is_some_and(|(key, checksum)| checksum == highscore.checksum(key, version));
+        Ok(highscore)
+    }
+
+    //Returns the length of the run in seconds
+    pub fn time(&self) -> f32 {
+        self.ticks as f32 * SIM_TIMESTEP
+    }
+}
+
+//Field that the high score table is sorted by
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HighScoreSort {
+    Score,
+    Name,
+    Floor,
+    Time,
+    Seed,
+    Difficulty,
+    End,
+    Date,
+}
+
+impl HighScoreSort {
+    //Returns the field that is sorted by after this one
+    pub fn next(self) -> Self {
+        match self {
+            HighScoreSort::Score => HighScoreSort::Name,
+            HighScoreSort::Name => HighScoreSort::Floor,
+            HighScoreSort::Floor => HighScoreSort::Time,
+            HighScoreSort::Time => HighScoreSort::Seed,
+            HighScoreSort::Seed => HighScoreSort::Difficulty,
+            HighScoreSort::Difficulty => HighScoreSort::End,
+            HighScoreSort::End => HighScoreSort::Date,
+            HighScoreSort::Date => HighScoreSort::Score,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HighScoreSort::Score => "score",
+            HighScoreSort::Name => "name",
+            HighScoreSort::Floor => "floor",
+            HighScoreSort::Time => "time",
+            HighScoreSort::Seed => "seed",
+            HighScoreSort::Difficulty => "difficulty",
+            HighScoreSort::End => "end",
+            HighScoreSort::Date => "date",
+        }
+    }
+}
+
+//Returns the high scores in the order they should be displayed, higher
+//scores, higher floors, faster times, harder difficulties and newer dates
+//are listed first
+pub fn sorted_highscores(highscores: &[HighScore], sort: HighScoreSort) -> Vec<&HighScore> {
+    let mut sorted: Vec<&HighScore> = highscores.iter().collect();
+    //Sort by score first so that ties are listed by score
+    sorted.sort_by_key(|highscore| Reverse(highscore.score));
+    match sort {
+        HighScoreSort::Score => {}
+        HighScoreSort::Name => sorted.sort_by(|a, b| a.name.cmp(&b.name)),
+        HighScoreSort::Floor => sorted.sort_by_key(|highscore| Reverse(highscore.floor)),
+        HighScoreSort::Time => sorted.sort_by_key(|highscore| highscore.ticks),
+        HighScoreSort::Seed => sorted.sort_by_key(|highscore| highscore.seed),
+        HighScoreSort::Difficulty => {
+            sorted.sort_by_key(|highscore| Reverse(highscore.difficulty as u8))
+        }
+        HighScoreSort::End => sorted.sort_by_key(|highscore| highscore.end.to_text()),
+        HighScoreSort::Date => sorted.sort_by_key(|highscore| Reverse(highscore.date)),
+    }
+    sorted
+}
+
+//Returns the current date in seconds since the unix epoch
+pub fn current_date() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+//Formats seconds since the unix epoch as year-month-day
+fn format_date(date: u64) -> String {
+    if date == 0 {
+        return "-".to_string();
+    }
+
+    //Convert the number of days since 1970-01-01 into a calendar date
+    //(see http://howardhinnant.github.io/date_algorithms.html)
+    let days = (date / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+//Formats a time in seconds as minutes:seconds
+fn format_time(time: f32) -> String {
+    let seconds = time as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
 
 //Checks if a score is a new high score
-pub fn is_new_highscore(score: u32, highscores: &Vec<u32>) -> bool {
+pub fn is_new_highscore(score: u32, highscores: &[HighScore]) -> bool {
     for highscore in highscores {
-        if score > *highscore {
+        if score > highscore.score {
             return true;
         }
     }
@@ -19,80 +352,258 @@ pub fn is_new_highscore(score: u32, highscores: &Vec<u32>) -> bool {
 
 //Finds the smallest highscore, removes it, and then adds
 //the new highscore value
-pub fn add_highscore(score: u32, highscores: &mut Vec<u32>) {
+pub fn add_highscore(entry: HighScore, highscores: &mut Vec<HighScore>) {
     //If we have less highscores than the maximum number
     //of highscores that the game stores, just add the
     //score to the high score list
     if highscores.len() < MAX_HIGHSCORES {
-        highscores.push(score);
-        highscores.sort();
+        highscores.push(entry);
+        highscores.sort_by_key(|highscore| highscore.score);
         return;
     }
 
-    let mut min = highscores[0];
+    let mut min = highscores[0].score;
     let mut index = 0;
     for (i, highscore) in highscores.iter().enumerate() {
-        if *highscore < min {
-            min = *highscore;
+        if highscore.score < min {
+            min = highscore.score;
             index = i;
         }
     }
 
-    if highscores[index] < score {
-        highscores[index] = score;
+    if highscores[index].score < entry.score {
+        highscores[index] = entry;
     }
-    highscores.sort();
+    highscores.sort_by_key(|highscore| highscore.score);
 }
 
-//Loads high scores from a file
-//we assume that this file has a score printed on each line
-pub fn load_highscores(path: &str) -> Vec<u32> {
+//Parses the contents of a high score file, the first line of the
+//file is the version and each line after that is an entry,
+//files without a version have a score printed on each line
+pub fn highscores_from_text(text: &str, key: Option<&[u8]>) -> Vec<HighScore> {
     let mut highscores = vec![];
+    let mut lines = text.lines().enumerate();
 
-    match File::open(path) {
-        Ok(mut file) => {
-            let mut buf = String::new();
+    match text
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("hiscores "))
+    {
+        Some(version) => {
+            let version = match version.trim().parse() {
+                Ok(
+                    version @ (UNSIGNED_HIGHSCORE_VERSION
+                    | NO_DIFFICULTY_HIGHSCORE_VERSION
+                    | HIGHSCORE_VERSION),
+                ) => version,
+                _ => {
+                    eprintln!("unsupported high score version: {version}");
+                    return highscores;
+                }
+            };
 
-            let res = file.read_to_string(&mut buf);
-            if let Err(msg) = res {
-                eprintln!("{msg}");
-            }
+            lines.next();
+            for (i, line) in lines {
+                if line.trim().is_empty() {
+                    continue;
+                }
 
-            buf.lines().for_each(|line| {
-                if let Ok(val) = line.parse() {
-                    highscores.push(val)
+                match HighScore::from_text(line, version, key) {
+                    Ok(highscore) => {
+                        if !highscore.verified {
+                            eprintln!("line {}: high score failed verification", i + 1);
+                        }
+                        highscores.push(highscore)
+                    }
+                    Err(msg) => eprintln!("line {}: {msg}", i + 1),
                 }
-            });
+            }
         }
-        Err(msg) => eprintln!("{msg}"),
+        None => lines.for_each(|(_, line)| {
+            if let Ok(val) = line.parse() {
+                highscores.push(HighScore::imported(val))
+            }
+        }),
     }
 
-    highscores.sort();
+    highscores.sort_by_key(|highscore| highscore.score);
 
-    //Pop off any extra elements
+    //Remove the lowest scores if there are too many
     while highscores.len() > MAX_HIGHSCORES {
-        highscores.pop();
+        highscores.remove(0);
     }
 
     highscores
 }
 
-//Writes highscores to a file
-pub fn write_highscores(path: &str, highscores: &Vec<u32>) {
-    if highscores.is_empty() {
-        return;
+pub fn highscores_to_text(highscores: &[HighScore], key: Option<&[u8]>) -> String {
+    let mut text = format!("hiscores {HIGHSCORE_VERSION}\n");
+    for highscore in highscores {
+        text.push_str(highscore.to_text(key).as_str());
+        text.push('\n');
+    }
+    text
+}
+
+//Formats a digest as lowercase hexadecimal
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+//Returns the key used to sign high scores, a random key is made if
+//there is none yet. Returns None if a new key could not be saved,
+//entries can not be signed or verified without a key.
+fn load_key() -> Option<Vec<u8>> {
+    let path = profile::data_dir().join(KEY_FILE);
+    if let Ok(key) = std::fs::read(&path) {
+        if !key.is_empty() {
+            return Some(key);
+        }
+    }
+
+    let key = to_hex(&rand::random::<[u8; KEY_LEN]>()).into_bytes();
+    let saved =
+        std::fs::create_dir_all(profile::data_dir()).and_then(|_| std::fs::write(&path, &key));
+    match saved {
+        Ok(()) => Some(key),
+        Err(msg) => {
+            eprintln!("failed to save high score key: {msg}");
+            None
+        }
     }
+}
 
-    let mut highscore_file_contents = String::new();
+//Returns the SHA-256 digest of the enemy definitions and the profile of
+//the difficulty, a run can only be verified with the same files that it
+//was played with. Returns `-` if the files can not be read.
+pub fn rules_digest(difficulty: Difficulty) -> String {
+    let mut text = String::new();
+    for path in [ENEMY_DEFINITIONS_PATH.to_string(), difficulty.path()] {
+        if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+            eprintln!("{path}: {e}");
+            return "-".to_string();
+        }
+    }
+    to_hex(&Sha256::digest(text.as_bytes()))
+}
 
-    for score in highscores {
-        highscore_file_contents.push_str(score.to_string().as_str());
-        highscore_file_contents.push('\n');
+//Returns the SHA-256 digest of a replay that is stored with a high score
+pub fn replay_digest(replay: &Replay) -> String {
+    to_hex(&Sha256::digest(replay.to_text().as_bytes()))
+}
+
+//Saves the replay of a high score in `replay_dir` so that the score
+//can be verified, the file is named after the digest of the replay
+pub fn save_highscore_replay(replay: &Replay, replay_dir: &str) -> Result<(), String> {
+    std::fs::create_dir_all(replay_dir).map_err(|e| e.to_string())?;
+    replay.save(&format!("{replay_dir}/{}", replay_digest(replay)))
+}
+
+//Checks a high score by playing back its replay and making sure
+//the run ends with the same score on the same tick
+pub fn verify_highscore(
+    highscore: &HighScore,
+    replay_dir: &str,
+    room_templates: &[RoomTemplate],
+) -> Result<(), String> {
+    if !highscore.verified {
+        return Err("the checksum of the entry does not match".to_string());
+    }
+
+    let replay = Replay::load(&format!("{replay_dir}/{}", highscore.replay))
+        .map_err(|msg| format!("failed to load replay: {msg}"))?;
+    verify_highscore_replay(highscore, replay, room_templates)
+}
+
+fn verify_highscore_replay(
+    highscore: &HighScore,
+    replay: Replay,
+    room_templates: &[RoomTemplate],
+) -> Result<(), String> {
+    if replay_digest(&replay) != highscore.replay {
+        return Err("the replay does not match the entry".to_string());
+    }
+    if replay.seed != highscore.seed {
+        return Err("the seed of the replay does not match the entry".to_string());
+    }
+    if replay.difficulty != highscore.difficulty {
+        return Err("the difficulty of the replay does not match the entry".to_string());
+    }
+    if replay.rules != highscore.rules {
+        return Err("the rules of the replay do not match the entry".to_string());
+    }
+    //Changing the enemies or the difficulty changes how the run plays out
+    if highscore.rules != rules_digest(highscore.difficulty) {
+        return Err(
+            "the run was played with different enemy definitions or difficulty profile".to_string(),
+        );
+    }
+
+    let sfx_player = SfxPlayer::silent();
+    let mut state = State::starting_state();
+    let (level, enemies) = Level::generate_level(
+        room_templates,
+        replay.seed,
+        replay.tower_size,
+        replay.difficulty,
+    );
+    state.level = level;
+    state.enemies = enemies;
+    state.seed = replay.seed;
+    state.tower_size = replay.tower_size;
+    state.difficulty = replay.difficulty;
+    state.game_screen = super::GameScreen::Game;
+    state.playback = Some(super::replay::ReplayPlayer::new(replay));
+
+    let mut highscores = vec![];
+    while state.tick <= highscore.ticks {
+        state.update_fixed_step(&sfx_player);
+        if state.check_gameover(&mut highscores, &sfx_player) {
+            if state.tick != highscore.ticks || state.player.score != highscore.score {
+                return Err(format!(
+                    "the replay ended with a score of {} after {} ticks",
+                    state.player.score, state.tick
+                ));
+            }
+            return Ok(());
+        }
+    }
+
+    Err(format!(
+        "the run did not end after {} ticks",
+        highscore.ticks
+    ))
+}
+
+//Loads high scores from a file
+pub fn load_highscores(path: &str) -> Vec<HighScore> {
+    match File::open(path) {
+        Ok(mut file) => {
+            let mut buf = String::new();
+
+            let res = file.read_to_string(&mut buf);
+            if let Err(msg) = res {
+                eprintln!("{msg}");
+            }
+
+            highscores_from_text(&buf, load_key().as_deref())
+        }
+        Err(msg) => {
+            eprintln!("{msg}");
+            vec![]
+        }
+    }
+}
+
+//Writes highscores to a file
+pub fn write_highscores(path: &str, highscores: &[HighScore]) {
+    if highscores.is_empty() {
+        return;
     }
 
     match File::create(path) {
         Ok(mut file) => {
-            let res = file.write(highscore_file_contents.as_bytes());
+            let res = file.write(highscores_to_text(highscores, load_key().as_deref()).as_bytes());
             if let Err(msg) = res {
                 eprintln!("{msg}");
             }
@@ -104,16 +615,56 @@ pub fn write_highscores(path: &str, highscores: &Vec<u32>) {
 pub fn display_hiscores(
     rect_vao: &VertexArrayObject,
     text_shader: &ShaderProgram,
-    highscores: &[u32],
+    highscores: &[HighScore],
+    sort: HighScoreSort,
 ) {
-    for (i, score) in highscores.iter().enumerate() {
+    ui::display_ascii_text_centered(
+        rect_vao,
+        text_shader,
+        format!("sorted by {}", sort.name()).as_bytes(),
+        0.0,
+        150.0,
+        8.0,
+    );
+
+    for (i, highscore) in sorted_highscores(highscores, sort).iter().enumerate() {
+        let y = 100.0 - i as f32 * 50.0;
+        let name: String = highscore.name.chars().take(MAX_NAME_DISPLAY_LEN).collect();
         ui::display_ascii_text_centered(
             rect_vao,
             text_shader,
-            format!("{}: {}", highscores.len() - i, score).as_bytes(),
+            format!(
+                "{}: {} {}{}",
+                i + 1,
+                name,
+                highscore.score,
+                if highscore.verified {
+                    ""
+                } else {
+                    " (unverified)"
+                }
+            )
+            .as_bytes(),
             0.0,
-            i as f32 * 16.0 * 2.5 - highscores.len() as f32 / 2.0 * 16.0 * 2.5,
-            16.0,
+            y,
+            12.0,
+        );
+        ui::display_ascii_text_centered(
+            rect_vao,
+            text_shader,
+            format!(
+                "{} floor {} time {} {} {} seed {}",
+                highscore.difficulty.name(),
+                highscore.floor + 1,
+                format_time(highscore.time()),
+                highscore.end.description(),
+                format_date(highscore.date),
+                highscore.seed
+            )
+            .as_bytes(),
+            0.0,
+            y - 22.0,
+            6.0,
         );
     }
 }
@@ -121,56 +672,265 @@ pub fn display_hiscores(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::game::{input_config::Action, GameScreen};
+
+    const TEST_KEY: Option<&[u8]> = Some(b"test key");
+
+    fn scores<'a>(highscores: impl IntoIterator<Item = &'a HighScore>) -> Vec<u32> {
+        highscores
+            .into_iter()
+            .map(|highscore| highscore.score)
+            .collect()
+    }
+
+    fn add_score(score: u32, highscores: &mut Vec<HighScore>) {
+        add_highscore(HighScore::imported(score), highscores);
+    }
 
     #[test]
     fn test_empty_hiscore() {
-        let hiscores: Vec<u32> = vec![];
+        let hiscores: Vec<HighScore> = vec![];
         assert!(is_new_highscore(0, &hiscores));
     }
 
     #[test]
     fn test_add_scores() {
-        let mut hiscores: Vec<u32> = vec![];
-        add_highscore(1, &mut hiscores);
-        add_highscore(2, &mut hiscores);
-        add_highscore(3, &mut hiscores);
-        add_highscore(0, &mut hiscores);
-        add_highscore(4, &mut hiscores);
-        add_highscore(5, &mut hiscores);
-        add_highscore(0, &mut hiscores);
-        assert_eq!(hiscores, vec![1, 2, 3, 4, 5]);
+        let mut hiscores: Vec<HighScore> = vec![];
+        add_score(1, &mut hiscores);
+        add_score(2, &mut hiscores);
+        add_score(3, &mut hiscores);
+        add_score(0, &mut hiscores);
+        add_score(4, &mut hiscores);
+        add_score(5, &mut hiscores);
+        add_score(0, &mut hiscores);
+        assert_eq!(scores(&hiscores), vec![1, 2, 3, 4, 5]);
     }
 
     #[test]
     fn test_check_is_highscore1() {
-        let hiscores: Vec<u32> = vec![1, 2, 3, 4, 5];
+        let hiscores: Vec<HighScore> = (1..=5).map(HighScore::imported).collect();
         assert!(!is_new_highscore(1, &hiscores));
     }
 
     #[test]
     fn test_check_is_highscore2() {
-        let hiscores: Vec<u32> = vec![1, 2, 3, 4, 5];
+        let hiscores: Vec<HighScore> = (1..=5).map(HighScore::imported).collect();
         assert!(is_new_highscore(6, &hiscores));
     }
 
     #[test]
     fn test_check_is_highscore3() {
-        let hiscores: Vec<u32> = vec![1, 2, 3, 4, 5];
+        let hiscores: Vec<HighScore> = (1..=5).map(HighScore::imported).collect();
         assert!(is_new_highscore(2, &hiscores));
     }
 
     #[test]
     fn test_check_is_highscore4() {
-        let mut hiscores: Vec<u32> = vec![];
-        add_highscore(1, &mut hiscores);
-        add_highscore(3, &mut hiscores);
-        add_highscore(4, &mut hiscores);
-        add_highscore(5, &mut hiscores);
-        add_highscore(6, &mut hiscores);
+        let mut hiscores: Vec<HighScore> = vec![];
+        add_score(1, &mut hiscores);
+        add_score(3, &mut hiscores);
+        add_score(4, &mut hiscores);
+        add_score(5, &mut hiscores);
+        add_score(6, &mut hiscores);
         let is_new = is_new_highscore(2, &hiscores);
-        add_highscore(2, &mut hiscores);
+        add_score(2, &mut hiscores);
         assert!(is_new);
         assert!(!is_new_highscore(2, &hiscores));
-        assert_eq!(hiscores, vec![2, 3, 4, 5, 6]);
+        assert_eq!(scores(&hiscores), vec![2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_highscore_text_round_trip() {
+        let highscores = vec![
+            HighScore {
+                name: "first player".to_string(),
+                score: 120,
+                floor: 7,
+                ticks: 9000,
+                seed: 42,
+                difficulty: Difficulty::Hard,
+                rules: "ef56".to_string(),
+                end: RunEnd::Enemy(EnemyType::from_name("demon").expect("no demon")),
+                date: 1_700_000_000,
+                replay: "ab12".to_string(),
+                verified: true,
+            },
+            HighScore {
+                name: "second".to_string(),
+                score: 900,
+                floor: 47,
+                ticks: 30000,
+                seed: u64::MAX,
+                difficulty: Difficulty::Easy,
+                rules: "-".to_string(),
+                end: RunEnd::Won,
+                date: 1_700_100_000,
+                replay: "cd34".to_string(),
+                verified: true,
+            },
+        ];
+        let text = highscores_to_text(&highscores, TEST_KEY);
+        assert_eq!(highscores_from_text(&text, TEST_KEY), highscores);
+
+        //Entries are not verified with a different key
+        let loaded = highscores_from_text(&text, Some(b"another key"));
+        assert!(loaded.iter().all(|h| !h.verified));
+
+        //Nothing is signed or verified without a key
+        assert!(highscores_from_text(&text, None)
+            .iter()
+            .all(|h| !h.verified));
+        let unsigned = highscores_to_text(&highscores, None);
+        assert!(highscores_from_text(&unsigned, TEST_KEY)
+            .iter()
+            .all(|h| !h.verified));
+    }
+
+    #[test]
+    fn test_edited_highscore_fails_verification() {
+        let mut highscore = HighScore::imported(100);
+        highscore.verified = true;
+        let text = highscores_to_text(&[highscore], TEST_KEY);
+        let edited = text.replace("\n100 ", "\n9000 ");
+        let loaded = highscores_from_text(&edited, TEST_KEY);
+        assert_eq!(scores(&loaded), vec![9000]);
+        assert!(!loaded[0].verified);
+
+        //Entries that failed verification stay unverified when written again
+        let rewritten = highscores_to_text(&loaded, TEST_KEY);
+        assert!(!highscores_from_text(&rewritten, TEST_KEY)[0].verified);
+    }
+
+    #[test]
+    fn test_import_old_highscores() {
+        let highscores = highscores_from_text("30\n10\n60\n20\n50\n40\n", TEST_KEY);
+        assert_eq!(scores(&highscores), vec![20, 30, 40, 50, 60]);
+        assert!(highscores.iter().all(|h| h.end == RunEnd::Unknown));
+
+        let highscores = highscores_from_text("hiscores 2\n10 1 600 5 lava 0 some one\n", TEST_KEY);
+        assert_eq!(highscores[0].name, "some one");
+        assert_eq!(highscores[0].end, RunEnd::Lava);
+        assert!(!highscores[0].verified);
+
+        //Signed entries from before there were difficulties are
+        //still verified and were played on normal
+        let mut highscore = HighScore::imported(10);
+        highscore.verified = true;
+        let checksum = highscore.checksum(TEST_KEY.unwrap(), NO_DIFFICULTY_HIGHSCORE_VERSION);
+        let text = format!("hiscores 3\n10 0 0 0 unknown 0 - {checksum} player\n");
+        assert_eq!(highscores_from_text(&text, TEST_KEY), vec![highscore]);
+    }
+
+    #[test]
+    fn test_verify_highscore_replay() {
+        let templates = crate::level::room_template::load_room_templates("assets/room_templates");
+        let replay = Replay::new(
+            3,
+            crate::level::generate_level::TowerSize::PRACTICE,
+            Difficulty::Normal,
+        );
+        let mut highscore = HighScore::imported(0);
+        highscore.seed = 3;
+        highscore.ticks = 20;
+        highscore.rules = replay.rules.clone();
+        highscore.replay = replay_digest(&replay);
+        highscore.verified = true;
+        //Standing still does not end the run
+        assert_eq!(
+            verify_highscore_replay(&highscore, replay.clone(), &templates),
+            Err("the run did not end after 20 ticks".to_string())
+        );
+
+        //Runs played with other enemies or difficulty profiles are rejected
+        let mut edited = replay.clone();
+        edited.rules = "0".to_string();
+        let mut edited_highscore = highscore.clone();
+        edited_highscore.rules = edited.rules.clone();
+        edited_highscore.replay = replay_digest(&edited);
+        assert!(
+            verify_highscore_replay(&edited_highscore, edited, &templates)
+                .is_err_and(|msg| msg.contains("different enemy definitions"))
+        );
+
+        highscore.replay = "0".to_string();
+        assert!(verify_highscore_replay(&highscore, replay, &templates).is_err());
+    }
+
+    #[test]
+    fn test_verify_recorded_run() {
+        let templates = crate::level::room_template::load_room_templates("assets/room_templates");
+        let sfx_player = SfxPlayer::silent();
+        let size = crate::level::generate_level::TowerSize::PRACTICE;
+        let mut state = State::starting_state();
+        let (level, enemies) = Level::generate_level(&templates, 9, size, Difficulty::Normal);
+        state.level = level;
+        state.enemies = enemies;
+        state.seed = 9;
+        state.tower_size = size;
+        state.replay = Replay::new(9, size, Difficulty::Normal);
+        state.game_screen = GameScreen::Game;
+
+        //Run back and forth and jump until the run ends
+        let mut highscores = vec![];
+        let mut ended = false;
+        while !ended && state.tick < 3000 {
+            let (forward, back) = if (state.tick / 240).is_multiple_of(2) {
+                (Action::Right, Action::Left)
+            } else {
+                (Action::Left, Action::Right)
+            };
+            if state.tick.is_multiple_of(240) {
+                state.record_action(back, false, &sfx_player);
+                state.record_action(forward, true, &sfx_player);
+            }
+            match state.tick % 60 {
+                0 => state.record_action(Action::Up, true, &sfx_player),
+                20 => state.record_action(Action::Up, false, &sfx_player),
+                _ => {}
+            }
+            state.update_fixed_step(&sfx_player);
+            ended = state.check_gameover(&mut highscores, &sfx_player);
+        }
+        assert!(ended, "the run did not end");
+
+        //The entry is signed and verified when it is loaded again
+        let text = highscores_to_text(&highscores, TEST_KEY);
+        let loaded = highscores_from_text(&text, TEST_KEY);
+        assert_eq!(loaded.len(), 1);
+        assert!(loaded[0].verified);
+        assert_eq!(
+            verify_highscore_replay(&loaded[0], state.replay.clone(), &templates),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_sort_highscores() {
+        let mut highscores = vec![];
+        let runs = [
+            (10, 3, 500, Difficulty::Hard),
+            (30, 1, 900, Difficulty::Easy),
+            (20, 9, 100, Difficulty::Normal),
+        ];
+        for (score, floor, ticks, difficulty) in runs {
+            let mut highscore = HighScore::imported(score);
+            highscore.floor = floor;
+            highscore.ticks = ticks;
+            highscore.difficulty = difficulty;
+            add_highscore(highscore, &mut highscores);
+        }
+
+        let sorted = |sort| scores(sorted_highscores(&highscores, sort));
+        assert_eq!(sorted(HighScoreSort::Score), vec![30, 20, 10]);
+        assert_eq!(sorted(HighScoreSort::Floor), vec![20, 10, 30]);
+        assert_eq!(sorted(HighScoreSort::Time), vec![20, 10, 30]);
+        assert_eq!(sorted(HighScoreSort::Difficulty), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(86400), "1970-01-02");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
     }
 }
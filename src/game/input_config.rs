@@ -1,70 +1,747 @@
-use glfw::Key;
-use std::{collections::HashMap, fs::File, io, io::BufRead};
+use super::gamepad::PadInput;
+use crate::{gfx::VertexArrayObject, shader::ShaderProgram, ui};
+use glfw::{GamepadAxis, GamepadButton, Key};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Write},
+};
 
 pub type KeyId = i32;
-pub type Action = String;
-pub struct InputConfig {
-    keymap: HashMap<KeyId, Action>,
+
+//Something the player can do, actions are bound to keys in the input
+//settings file and are what gets recorded in replays and saves
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Attack,
+    Sword,
+    Bow,
+    Escape,
+}
+
+//Actions in the order they are listed on the controls
+//screen and in the input settings file
+pub const ACTIONS: [Action; 8] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Attack,
+    Action::Sword,
+    Action::Bow,
+    Action::Escape,
+];
+
+//Actions that are used to get around the menus, their last key and
+//gamepad input can not be taken away so the menus can always be used
+pub const MENU_ACTIONS: [Action; 4] = [Action::Up, Action::Down, Action::Attack, Action::Escape];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Attack => "Attack",
+            Action::Sword => "Sword",
+            Action::Bow => "Bow",
+            Action::Escape => "Escape",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS.iter().copied().find(|action| action.name() == name)
+    }
 }
 
-fn parse_lines(lines: &[String]) -> HashMap<KeyId, Action> {
-    let mut config = HashMap::new();
-    for line in lines {
-        //TODO: implement better parsing of input map config file,
-        //for now we just assume that it is of the format string1=string2
-        //and that there is exactly *1* '=' character in each line
-        let split: Vec<String> = line.split('=').map(|s| s.to_string()).collect();
-        if split.len() < 2 {
+//Section of the input settings file that has the key bindings, lines
+//before the first section are also key bindings since older files
+//did not have sections
+const KEYBOARD_SECTION: &str = "keyboard";
+//Section of the input settings file that has the gamepad bindings
+const GAMEPAD_SECTION: &str = "gamepad";
+
+//Every key that can be bound, the name of a key in the input
+//settings file is the name of its variant in `glfw::Key`
+const KEYS: [Key; 120] = [
+    Key::Space,
+    Key::Apostrophe,
+    Key::Comma,
+    Key::Minus,
+    Key::Period,
+    Key::Slash,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::Semicolon,
+    Key::Equal,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::LeftBracket,
+    Key::Backslash,
+    Key::RightBracket,
+    Key::GraveAccent,
+    Key::World1,
+    Key::World2,
+    Key::Escape,
+    Key::Enter,
+    Key::Tab,
+    Key::Backspace,
+    Key::Insert,
+    Key::Delete,
+    Key::Right,
+    Key::Left,
+    Key::Down,
+    Key::Up,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Home,
+    Key::End,
+    Key::CapsLock,
+    Key::ScrollLock,
+    Key::NumLock,
+    Key::PrintScreen,
+    Key::Pause,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::F13,
+    Key::F14,
+    Key::F15,
+    Key::F16,
+    Key::F17,
+    Key::F18,
+    Key::F19,
+    Key::F20,
+    Key::F21,
+    Key::F22,
+    Key::F23,
+    Key::F24,
+    Key::F25,
+    Key::Kp0,
+    Key::Kp1,
+    Key::Kp2,
+    Key::Kp3,
+    Key::Kp4,
+    Key::Kp5,
+    Key::Kp6,
+    Key::Kp7,
+    Key::Kp8,
+    Key::Kp9,
+    Key::KpDecimal,
+    Key::KpDivide,
+    Key::KpMultiply,
+    Key::KpSubtract,
+    Key::KpAdd,
+    Key::KpEnter,
+    Key::KpEqual,
+    Key::LeftShift,
+    Key::LeftControl,
+    Key::LeftAlt,
+    Key::LeftSuper,
+    Key::RightShift,
+    Key::RightControl,
+    Key::RightAlt,
+    Key::RightSuper,
+    Key::Menu,
+];
+
+//Returns the name of a key, keys that we do not know are written as their code
+pub fn key_name(key_id: KeyId) -> String {
+    match KEYS.iter().find(|key| **key as KeyId == key_id) {
+        Some(key) => format!("{key:?}"),
+        None => key_id.to_string(),
+    }
+}
+
+//Returns the key with a name, older input settings files stored
+//the numeric code of the key so the codes of known keys are accepted as well
+pub fn key_from_name(name: &str) -> Option<KeyId> {
+    KEYS.iter()
+        .find(|key| format!("{key:?}").eq_ignore_ascii_case(name) || key_code(**key) == name)
+        .map(|key| *key as KeyId)
+}
+
+fn key_code(key: Key) -> String {
+    (key as KeyId).to_string()
+}
+
+//Parses the input settings file, every line is either empty, a [section]
+//or an input = action binding and anything after a # is a comment. Every
+//line that could not be parsed is returned with its line number and the
+//actions on those lines keep their default bindings
+fn parse_input_settings(text: &str) -> (InputConfig, Vec<String>) {
+    let mut keymap = HashMap::new();
+    //Files without a gamepad section use the default gamepad bindings
+    let mut padmap = None;
+    let mut errors = vec![];
+    let mut broken_keys = vec![];
+    let mut broken_pad = vec![];
+    let mut section = KEYBOARD_SECTION.to_string();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = name.trim().to_string();
+            if section == GAMEPAD_SECTION {
+                padmap.get_or_insert_with(HashMap::new);
+            } else if section != KEYBOARD_SECTION {
+                errors.push(format!("line {}: unknown section: [{section}]", i + 1));
+            }
             continue;
         }
 
-        if let Ok(scancode) = split[0].trim().parse::<KeyId>() {
-            config.insert(scancode, split[1].trim().to_string());
+        let res = if section == KEYBOARD_SECTION {
+            parse_binding(line, "key", key_from_name).and_then(|(key_id, action)| {
+                match keymap.insert(key_id, action) {
+                    Some(previous) => Err(format!(
+                        "{} is already bound to {}",
+                        key_name(key_id),
+                        previous.name()
+                    )),
+                    None => Ok(()),
+                }
+            })
+        } else if section == GAMEPAD_SECTION {
+            parse_binding(line, "gamepad input", PadInput::from_name).and_then(|(input, action)| {
+                match padmap
+                    .get_or_insert_with(HashMap::new)
+                    .insert(input, action)
+                {
+                    Some(previous) => Err(format!(
+                        "{} is already bound to {}",
+                        input.name(),
+                        previous.name()
+                    )),
+                    None => Ok(()),
+                }
+            })
+        } else {
+            //Errors for the lines of unknown sections were reported already
+            Ok(())
+        };
+
+        if let Err(msg) = res {
+            errors.push(format!("line {}: {msg}", i + 1));
+            let action = line
+                .split_once('=')
+                .and_then(|(_, action)| Action::from_name(action.trim()));
+            match section.as_str() {
+                KEYBOARD_SECTION => broken_keys.extend(action),
+                GAMEPAD_SECTION => broken_pad.extend(action),
+                _ => {}
+            }
+        }
+    }
+
+    let mut config = InputConfig {
+        keymap,
+        padmap: padmap.unwrap_or_else(default_padmap),
+    };
+    //Give the actions on broken lines their default inputs that are not taken
+    let default = InputConfig::default();
+    for (key_id, action) in default.keymap {
+        if broken_keys.contains(&action) {
+            config.keymap.entry(key_id).or_insert(action);
         }
     }
+    for (input, action) in default.padmap {
+        if broken_pad.contains(&action) {
+            config.padmap.entry(input).or_insert(action);
+        }
+    }
+    (config, errors)
+}
 
-    config
+//Parses a single input = action line, `kind` is the kind of input
+//that is named on the left and is used in error messages
+fn parse_binding<T>(
+    line: &str,
+    kind: &str,
+    parse_input: impl Fn(&str) -> Option<T>,
+) -> Result<(T, Action), String> {
+    let (input, action) = line
+        .split_once('=')
+        .ok_or(format!("expected: {kind} = action"))?;
+    let (input, action) = (input.trim(), action.trim());
+    if action.contains('=') {
+        return Err(format!("expected: {kind} = action"));
+    }
+
+    let input = parse_input(input).ok_or(format!("unknown {kind}: {input}"))?;
+    let action = Action::from_name(action).ok_or(format!("unknown action: {action}"))?;
+    Ok((input, action))
+}
+
+fn default_padmap() -> HashMap<PadInput, Action> {
+    HashMap::from([
+        (PadInput::Button(GamepadButton::ButtonDpadUp), Action::Up),
+        (PadInput::AxisNegative(GamepadAxis::AxisLeftY), Action::Up),
+        (PadInput::Button(GamepadButton::ButtonB), Action::Up),
+        (
+            PadInput::Button(GamepadButton::ButtonDpadDown),
+            Action::Down,
+        ),
+        (PadInput::AxisPositive(GamepadAxis::AxisLeftY), Action::Down),
+        (
+            PadInput::Button(GamepadButton::ButtonDpadLeft),
+            Action::Left,
+        ),
+        (PadInput::AxisNegative(GamepadAxis::AxisLeftX), Action::Left),
+        (
+            PadInput::Button(GamepadButton::ButtonDpadRight),
+            Action::Right,
+        ),
+        (
+            PadInput::AxisPositive(GamepadAxis::AxisLeftX),
+            Action::Right,
+        ),
+        (PadInput::Button(GamepadButton::ButtonA), Action::Attack),
+        (
+            PadInput::Button(GamepadButton::ButtonLeftBumper),
+            Action::Sword,
+        ),
+        (
+            PadInput::Button(GamepadButton::ButtonRightBumper),
+            Action::Bow,
+        ),
+        (PadInput::Button(GamepadButton::ButtonStart), Action::Escape),
+    ])
+}
+
+pub struct InputConfig {
+    keymap: HashMap<KeyId, Action>,
+    padmap: HashMap<PadInput, Action>,
 }
 
 impl InputConfig {
     pub fn get_action(&self, key_id: KeyId) -> Option<Action> {
-        self.keymap.get(&key_id).cloned()
+        self.keymap.get(&key_id).copied()
+    }
+
+    pub fn get_pad_action(&self, input: PadInput) -> Option<Action> {
+        self.padmap.get(&input).copied()
+    }
+
+    //Returns the keys bound to an action ordered by key code
+    pub fn keys_for(&self, action: Action) -> Vec<KeyId> {
+        let mut keys: Vec<KeyId> = self
+            .keymap
+            .iter()
+            .filter(|(_, other)| **other == action)
+            .map(|(key_id, _)| *key_id)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    //Returns the gamepad buttons and axes bound to an action
+    pub fn pad_inputs_for(&self, action: Action) -> Vec<PadInput> {
+        let mut inputs: Vec<PadInput> = self
+            .padmap
+            .iter()
+            .filter(|(_, other)| **other == action)
+            .map(|(input, _)| *input)
+            .collect();
+        inputs.sort();
+        inputs
     }
 
-    fn read_config(path: &str) -> Result<Self, String> {
-        let reader = io::BufReader::new(File::open(path).map_err(|e| e.to_string())?);
-        let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
+    //Binds a key to an action, an action can have several keys but a key
+    //only has one action so if the key was already bound to a different
+    //action then that action is returned to report the conflict
+    pub fn bind(&mut self, key_id: KeyId, action: Action) -> Option<Action> {
+        self.keymap
+            .insert(key_id, action)
+            .filter(|previous| *previous != action)
+    }
+
+    //Same as `bind` but for a gamepad button or axis
+    pub fn bind_pad(&mut self, input: PadInput, action: Action) -> Option<Action> {
+        self.padmap
+            .insert(input, action)
+            .filter(|previous| *previous != action)
+    }
 
-        Ok(Self {
-            keymap: parse_lines(&lines),
+    //Returns the menu action that the key is bound to if it is the only key of
+    //that action, binding the key to something else would leave it without keys
+    pub fn last_menu_key(&self, key_id: KeyId) -> Option<Action> {
+        self.get_action(key_id)
+            .filter(|action| MENU_ACTIONS.contains(action) && self.keys_for(*action).len() == 1)
+    }
+
+    //Same as `last_menu_key` but for a gamepad button or axis
+    pub fn last_menu_pad_input(&self, input: PadInput) -> Option<Action> {
+        self.get_pad_action(input).filter(|action| {
+            MENU_ACTIONS.contains(action) && self.pad_inputs_for(*action).len() == 1
         })
     }
 
+    //Removes every key bound to an action
+    pub fn unbind(&mut self, action: Action) {
+        self.keymap.retain(|_, other| *other != action);
+    }
+
+    pub fn unbind_pad(&mut self, action: Action) {
+        self.padmap.retain(|_, other| *other != action);
+    }
+
+    //Returns the actions in `ACTIONS` that have no keys bound to them
+    pub fn unbound_actions(&self) -> Vec<Action> {
+        ACTIONS
+            .iter()
+            .copied()
+            .filter(|action| self.keys_for(*action).is_empty())
+            .collect()
+    }
+
+    //Converts the bindings to the input settings format, one input = action
+    //line per key and then per gamepad input, ordered by action
+    pub fn to_text(&self) -> String {
+        let mut text = format!("[{KEYBOARD_SECTION}]\n");
+        for action in ACTIONS {
+            for key_id in self.keys_for(action) {
+                text.push_str(&format!("{} = {}\n", key_name(key_id), action.name()));
+            }
+        }
+
+        text.push_str(&format!("\n[{GAMEPAD_SECTION}]\n"));
+        for action in ACTIONS {
+            for input in self.pad_inputs_for(action) {
+                text.push_str(&format!("{} = {}\n", input.name(), action.name()));
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
     pub fn default() -> Self {
         let default_map = [
-            (Key::Space as i32, "Attack".to_string()),
-            (Key::Right as i32, "Right".to_string()),
-            (Key::Left as i32, "Left".to_string()),
-            (Key::Up as i32, "Up".to_string()),
-            (Key::Down as i32, "Down".to_string()),
-            (Key::Num1 as i32, "Sword".to_string()),
-            (Key::Num2 as i32, "Bow".to_string()),
-            (Key::Escape as i32, "Escape".to_string()),
+            (Key::Space as i32, Action::Attack),
+            (Key::Right as i32, Action::Right),
+            (Key::Left as i32, Action::Left),
+            (Key::Up as i32, Action::Up),
+            (Key::Down as i32, Action::Down),
+            (Key::Num1 as i32, Action::Sword),
+            (Key::Num2 as i32, Action::Bow),
+            (Key::Escape as i32, Action::Escape),
         ];
 
         Self {
             keymap: HashMap::from(default_map),
+            padmap: default_padmap(),
         }
     }
 
+    //Loads the input settings file, the default bindings are used if it
+    //can not be read and for the actions on lines that could not be parsed
     pub fn new(config_path: &str) -> Self {
-        match Self::read_config(config_path) {
-            Ok(config) => config,
-            Err(msg) => {
-                eprintln!("Failed to open: {config_path}");
-                eprintln!("{msg}");
-                Self::default()
-            }
+        let mut text = String::new();
+        if let Err(e) = File::open(config_path).and_then(|mut file| file.read_to_string(&mut text))
+        {
+            eprintln!("Failed to load: {config_path}");
+            eprintln!("{e}");
+            return Self::default();
+        }
+
+        let (config, errors) = parse_input_settings(&text);
+        if !errors.is_empty() {
+            eprintln!("Problems in {config_path}, using the default bindings for those actions:");
+            eprintln!("{}", errors.join("\n"));
         }
+        config
+    }
+}
+
+//Lists the keys (or gamepad inputs if `gamepad` is set) bound to each
+//action next to the buttons on the controls screen, `rebinding` is the
+//action waiting for a key
+pub fn display_controls(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    input: &InputConfig,
+    gamepad: bool,
+    rebinding: Option<usize>,
+    message: &str,
+) {
+    text_shader.uniform_vec4f("uColor", 1.0, 1.0, 1.0, 1.0);
+    let (device, instructions) = if gamepad {
+        (
+            "gamepad",
+            "click on an action and press a button to bind it",
+        )
+    } else {
+        ("keyboard", "click on an action and press a key to bind it")
+    };
+    ui::display_ascii_text_centered(rect_vao, text_shader, device.as_bytes(), 0.0, 200.0, 8.0);
+    ui::display_ascii_text_centered(
+        rect_vao,
+        text_shader,
+        instructions.as_bytes(),
+        0.0,
+        180.0,
+        6.0,
+    );
+    for (i, action) in ACTIONS.iter().enumerate() {
+        let names: Vec<String> = if gamepad {
+            input
+                .pad_inputs_for(*action)
+                .iter()
+                .map(|input| input.name())
+                .collect()
+        } else {
+            input
+                .keys_for(*action)
+                .iter()
+                .map(|key_id| key_name(*key_id))
+                .collect()
+        };
+        let text = if rebinding == Some(i) && gamepad {
+            "press a button...".to_string()
+        } else if rebinding == Some(i) {
+            "press a key...".to_string()
+        } else if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        };
+        ui::display_ascii_text(
+            rect_vao,
+            text_shader,
+            text.as_bytes(),
+            ui::CONTROLS_KEYS_X,
+            ui::controls_row_y(i),
+            8.0,
+        );
+    }
+
+    ui::display_ascii_text_centered(rect_vao, text_shader, message.as_bytes(), 0.0, -150.0, 8.0);
+    //Actions without a key can not be done, so point them out
+    let unbound = input.unbound_actions();
+    if !unbound.is_empty() {
+        text_shader.uniform_vec4f("uColor", 1.0, 0.4, 0.4, 1.0);
+        ui::display_ascii_text_centered(
+            rect_vao,
+            text_shader,
+            format!(
+                "no keys for: {}",
+                unbound
+                    .iter()
+                    .map(|action| action.name())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )
+            .as_bytes(),
+            0.0,
+            -172.0,
+            8.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Parses input settings and fails if any line has a problem
+    fn from_text(text: &str) -> Result<InputConfig, String> {
+        let (config, errors) = parse_input_settings(text);
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    #[test]
+    fn test_key_names() {
+        assert_eq!(key_name(Key::Space as KeyId), "Space");
+        assert_eq!(key_name(Key::Num1 as KeyId), "Num1");
+        assert_eq!(key_from_name("LeftShift"), Some(Key::LeftShift as KeyId));
+        assert_eq!(key_from_name("w"), Some(Key::W as KeyId));
+        //Numeric codes from older input settings files
+        assert_eq!(key_from_name("262"), Some(Key::Right as KeyId));
+        assert_eq!(key_from_name("NotAKey"), None);
+        //Codes that are not glfw keys
+        assert_eq!(key_from_name("12345"), None);
+        assert_eq!(key_from_name("-1"), None);
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut input = InputConfig::default();
+        assert_eq!(input.bind(Key::W as KeyId, Action::Up), None);
+        assert_eq!(
+            input.keys_for(Action::Up),
+            vec![Key::W as KeyId, Key::Up as KeyId]
+        );
+        //Space was bound to Attack
+        assert_eq!(
+            input.bind(Key::Space as KeyId, Action::Up),
+            Some(Action::Attack)
+        );
+        assert_eq!(input.unbound_actions(), vec![Action::Attack]);
+        input.unbind(Action::Up);
+        assert!(input.keys_for(Action::Up).is_empty());
+
+        //Escape only has one key
+        assert_eq!(
+            input.last_menu_key(Key::Escape as KeyId),
+            Some(Action::Escape)
+        );
+        assert_eq!(input.last_menu_key(Key::Num1 as KeyId), None);
+        assert_eq!(input.last_menu_key(Key::W as KeyId), None);
+        let start = PadInput::Button(GamepadButton::ButtonStart);
+        assert_eq!(input.last_menu_pad_input(start), Some(Action::Escape));
+
+        let loaded = from_text(&input.to_text()).expect("failed to parse");
+        assert_eq!(loaded.keymap, input.keymap);
+        assert_eq!(loaded.padmap, input.padmap);
+        assert!(input.to_text().starts_with("[keyboard]\nDown = Down\n"));
+    }
+
+    #[test]
+    fn test_parse_input_settings() {
+        let text = "# movement\n\
+                    [keyboard]\n\
+                    W = Up # jump\n\
+                    \n\
+                    265 = Up\n\
+                    LeftShift=Attack\n";
+        let input = from_text(text).expect("failed to parse");
+        assert_eq!(input.get_action(Key::W as KeyId), Some(Action::Up));
+        assert_eq!(input.get_action(Key::Up as KeyId), Some(Action::Up));
+        assert_eq!(
+            input.get_action(Key::LeftShift as KeyId),
+            Some(Action::Attack)
+        );
+        assert_eq!(input.get_action(Key::Space as KeyId), None);
+        //Files without a gamepad section use the default gamepad bindings
+        let button_a = PadInput::Button(GamepadButton::ButtonA);
+        assert_eq!(input.get_pad_action(button_a), Some(Action::Attack));
+
+        let input =
+            from_text("[gamepad]\nButtonX = Attack\nAxisLeftX- = Left\n").expect("failed to parse");
+        assert_eq!(
+            input.get_pad_action(PadInput::Button(GamepadButton::ButtonX)),
+            Some(Action::Attack)
+        );
+        assert_eq!(
+            input.get_pad_action(PadInput::AxisNegative(GamepadAxis::AxisLeftX)),
+            Some(Action::Left)
+        );
+        assert_eq!(input.get_pad_action(button_a), None);
+    }
+
+    #[test]
+    fn test_invalid_input_settings() {
+        let errors = |text: &str| from_text(text).err();
+        assert_eq!(
+            errors("Space = Jump\n"),
+            Some("line 1: unknown action: Jump".to_string())
+        );
+        assert_eq!(
+            errors("W = Up\nSpcae = Attack\n"),
+            Some("line 2: unknown key: Spcae".to_string())
+        );
+        assert_eq!(
+            errors("W Up\n"),
+            Some("line 1: expected: key = action".to_string())
+        );
+        assert_eq!(
+            errors("W = Up = Down\n"),
+            Some("line 1: expected: key = action".to_string())
+        );
+        assert_eq!(
+            errors("W = Up\nW = Down\n"),
+            Some("line 2: W is already bound to Up".to_string())
+        );
+        assert_eq!(
+            errors("[gamepad]\nButtonZ = Up\n"),
+            Some("line 2: unknown gamepad input: ButtonZ".to_string())
+        );
+        assert_eq!(
+            errors("[mouse]\nW = Up\n"),
+            Some("line 1: unknown section: [mouse]".to_string())
+        );
+        //Every line with a problem is reported
+        assert_eq!(
+            errors("X = Jump\nW = Up\nFoo = Up\n"),
+            Some("line 1: unknown action: Jump\nline 3: unknown key: Foo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_broken_lines_use_default_bindings() {
+        let text = "W = Up\nSpcae = Attack\nX = Jump\n[gamepad]\nButtonZ = Bow\n";
+        let (input, errors) = parse_input_settings(text);
+        assert_eq!(errors.len(), 3);
+        //The lines that were parsed are kept
+        assert_eq!(input.keys_for(Action::Up), vec![Key::W as KeyId]);
+        //Actions on broken lines get their default bindings
+        assert_eq!(input.keys_for(Action::Attack), vec![Key::Space as KeyId]);
+        assert_eq!(
+            input.pad_inputs_for(Action::Bow),
+            vec![PadInput::Button(GamepadButton::ButtonRightBumper)]
+        );
+        //Other actions are not given their defaults
+        assert!(input.keys_for(Action::Left).is_empty());
+        assert!(input.pad_inputs_for(Action::Attack).is_empty());
     }
 }
@@ -1,6 +1,6 @@
 use super::{Player, GRAVITY, PLAYER_HEIGHT};
-use crate::level::{transparent, Level, Tile, ROOM_SIZE};
-use crate::sprite::Sprite;
+use crate::level::{Level, Tile};
+use crate::sprite::{collision::TileCollision, Sprite};
 use cgmath::vec2;
 
 //Speed the player walks at
@@ -12,26 +12,21 @@ pub const PLAYER_CLIMB_SPEED: f32 = 4.0;
 
 impl Player {
     //Handle collision in the y axis
-    fn handle_collision_y(&mut self, sprite: &Sprite) {
-        if self.player_spr.intersecting(sprite) {
-            if self.player_spr.position.y > sprite.position.y {
-                //If we are supported by a tile then stop falling
-                self.falling = false;
-                self.player_spr.velocity.y = -0.01;
-                self.climbing = false;
-            } else if self.player_spr.position.y < sprite.position.y {
-                //Set y velocity to 0 so we don't "stick" to the tile if the
-                //player decides to hold down the jump key
-                self.player_spr.velocity.y = 0.0;
-                //We hit the bottom of a tile, start falling again
-                self.falling = true;
-            }
+    fn handle_collision_y(&mut self, collision: &TileCollision) {
+        if collision.normal.y > 0.0 {
+            //If we are supported by a tile then stop falling
+            self.falling = false;
+            self.player_spr.velocity.y = -0.01;
+            self.climbing = false;
+        } else if collision.normal.y < 0.0 {
+            //Set y velocity to 0 so we don't "stick" to the tile if the
+            //player decides to hold down the jump key
+            self.player_spr.velocity.y = 0.0;
+            //We hit the bottom of a tile, start falling again
+            self.falling = true;
         }
     }
 
-    //NOTE: collision detection isn't perfect, if the sprite is moving
-    //too fast or the framerate drops too low, then the sprite may end
-    //up clipping through tiles
     pub fn update(&mut self, dt: f32, level: &Level) {
         //Determine if to display whether the sprite is flipped based on
         //the x velocity of the sprite and what direction the sprite is heading
@@ -42,35 +37,8 @@ impl Player {
         }
 
         //Update x
-        self.player_spr.position.x += self.player_spr.velocity.x * dt;
-        //Handle collision
-        let top_left = vec2(self.player_spr.position.x, self.player_spr.position.y)
-            - vec2(
-                self.player_spr.dimensions.x.ceil() / 2.0 + 1.0,
-                self.player_spr.dimensions.y.ceil() / 2.0 + 1.0,
-            );
-        let bot_right = vec2(self.player_spr.position.x, self.player_spr.position.y)
-            + vec2(
-                self.player_spr.dimensions.x.ceil() / 2.0 + 1.0,
-                self.player_spr.dimensions.y.ceil() / 2.0 + 1.0,
-            );
-        let (top_left_x, top_left_y) = (top_left.x.floor() as i32, top_left.y.floor() as i32);
-        let (bot_right_x, bot_right_y) = (bot_right.x.ceil() as i32, bot_right.y.ceil() as i32);
-
-        //Scan the level for tiles the sprite might have collided with
-        //and then uncollide the sprite from the tiles
-        for x in top_left_x..bot_right_x {
-            for y in top_left_y..bot_right_y {
-                if level.out_of_bounds(x, y) {
-                    continue;
-                }
-
-                if !transparent(level.get_tile(x as u32, y as u32)) {
-                    let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
-                    self.player_spr.uncollide_x(&hitbox);
-                }
-            }
-        }
+        self.player_spr
+            .sweep_x(self.player_spr.velocity.x * dt, level);
 
         //Cap speed of player when they are on a ladder
         if self.climbing {
@@ -79,14 +47,22 @@ impl Player {
         }
 
         //Update y
-        self.player_spr.position.y += self.player_spr.velocity.y / 2.0 * dt;
+        let mut dy = self.player_spr.velocity.y / 2.0 * dt;
         //Accelerate due to gravity
         if self.falling && !self.climbing {
             self.player_spr.velocity.y -= GRAVITY * dt;
         }
-        self.player_spr.position.y += self.player_spr.velocity.y / 2.0 * dt;
+        dy += self.player_spr.velocity.y / 2.0 * dt;
+
+        self.falling = true;
+        self.climbing = false;
+
+        //Stop at any tile in the way and also determine if the sprite is falling
+        if let Some(collision) = self.player_spr.sweep_y(dy, level) {
+            self.handle_collision_y(&collision);
+        }
 
-        //Handle collision
+        //Check if the player is on a ladder
         let top_left = vec2(self.player_spr.position.x, self.player_spr.position.y)
             - vec2(
                 self.player_spr.dimensions.x.ceil() / 2.0 + 1.0,
@@ -100,21 +76,13 @@ impl Player {
         let (top_left_x, top_left_y) = (top_left.x.floor() as i32, top_left.y.floor() as i32);
         let (bot_right_x, bot_right_y) = (bot_right.x.ceil() as i32, bot_right.y.ceil() as i32);
 
-        self.falling = true;
-        self.climbing = false;
-
-        //Uncollide from any tiles and also determine if the sprite is falling
         for x in top_left_x..bot_right_x {
             for y in top_left_y..bot_right_y {
                 if level.out_of_bounds(x, y) {
                     continue;
                 }
 
-                if !transparent(level.get_tile(x as u32, y as u32)) {
-                    let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
-                    self.handle_collision_y(&hitbox);
-                    self.player_spr.uncollide_y(&hitbox);
-                } else if level.get_tile(x as u32, y as u32) == Tile::Ladder {
+                if level.get_tile(x as u32, y as u32) == Tile::Ladder {
                     let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
                     if self.player_spr.intersecting(&hitbox) {
                         self.falling = false;
@@ -129,7 +97,7 @@ impl Player {
             .player_spr
             .position
             .x
-            .clamp(0.0, ROOM_SIZE as f32 + 1.0);
+            .clamp(0.0, level.w() as f32 - 1.0);
 
         //Clamp the player's y position to prevent them from falling through the
         //floor of the world
@@ -0,0 +1,263 @@
+use super::hiscore::KEY_FILE;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+//Name of the profile that is used if no other profile was picked
+pub const DEFAULT_PROFILE_NAME: &str = "player";
+//Maximum number of characters in the name of a profile
+pub const MAX_PROFILE_NAME_LEN: usize = 20;
+//Setting this environment variable changes where profiles are kept
+const DATA_DIR_VAR: &str = "SCALE_THE_TOWER_DATA";
+//File in the data directory that stores the last profile that was used
+const LAST_PROFILE_FILE: &str = "last_profile";
+
+//Files that are kept in the directory of each profile
+pub const HISCORES_FILE: &str = "hiscores";
+pub const INPUT_SETTINGS_FILE: &str = "input_settings";
+pub const SAVE_FILE: &str = "savegame";
+pub const LAST_REPLAY_FILE: &str = "last_replay";
+pub const HISCORE_REPLAY_DIR: &str = "hiscore_replays";
+pub const STATS_FILE: &str = "statistics";
+pub const SETTINGS_FILE: &str = "settings";
+
+//Files from before there were profiles, these are copied from the
+//working directory into the first profile that is created
+const LEGACY_FILES: [&str; 3] = [HISCORES_FILE, INPUT_SETTINGS_FILE, LAST_REPLAY_FILE];
+//Directories from before there were profiles that are copied the same way
+const LEGACY_DIRS: [&str; 1] = [HISCORE_REPLAY_DIR];
+
+//Returns the directory that all profiles are kept in, this is the
+//per user data directory of the platform unless it is overridden
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(DATA_DIR_VAR) {
+        return PathBuf::from(dir);
+    }
+
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    };
+
+    base.unwrap_or_else(|| PathBuf::from("."))
+        .join("scale-the-tower")
+}
+
+//Checks that a profile name can be used as the name of a directory
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return Err(format!(
+            "a profile name must have 1 to {MAX_PROFILE_NAME_LEN} characters"
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+    {
+        return Err(format!(
+            "invalid profile name: {name} (only letters, digits, _ and - are allowed)"
+        ));
+    }
+
+    Ok(())
+}
+
+//A player profile, each profile has its own key bindings,
+//high scores, replays, statistics and saved run
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Profile {
+    pub name: String,
+    dir: PathBuf,
+}
+
+impl Profile {
+    //Profile that uses the files in the working directory,
+    //this is how the game stored files before profiles
+    pub fn working_dir() -> Self {
+        Self {
+            name: DEFAULT_PROFILE_NAME.to_string(),
+            dir: PathBuf::from("."),
+        }
+    }
+
+    //Opens the profile with the name in `profiles_dir`, the profile is
+    //created if it does not exist yet
+    pub fn open_in(profiles_dir: &Path, name: &str) -> Result<Self, String> {
+        Self::open_with_legacy(profiles_dir, Path::new("."), name)
+    }
+
+    //Opens a profile, the first profile that is created takes over the
+    //files in `legacy_dir` and the high score key is moved to the directory
+    //that `profiles_dir` is in so that old high scores can still be verified
+    fn open_with_legacy(
+        profiles_dir: &Path,
+        legacy_dir: &Path,
+        name: &str,
+    ) -> Result<Self, String> {
+        validate_name(name)?;
+        let dir = profiles_dir.join(name);
+        if !dir.is_dir() {
+            let first_profile = list_in(profiles_dir).is_empty();
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            for file in LEGACY_FILES {
+                let legacy = legacy_dir.join(file);
+                if (first_profile || file == INPUT_SETTINGS_FILE) && legacy.is_file() {
+                    fs::copy(legacy, dir.join(file)).map_err(|e| e.to_string())?;
+                }
+            }
+
+            if first_profile {
+                for legacy in LEGACY_DIRS {
+                    if legacy_dir.join(legacy).is_dir() {
+                        copy_dir(&legacy_dir.join(legacy), &dir.join(legacy))
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+
+                let key = profiles_dir
+                    .parent()
+                    .map(|data_dir| data_dir.join(KEY_FILE));
+                if let Some(key) = key.filter(|key| !key.exists()) {
+                    if legacy_dir.join(KEY_FILE).is_file() {
+                        fs::copy(legacy_dir.join(KEY_FILE), key).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            dir,
+        })
+    }
+
+    pub fn open(name: &str) -> Result<Self, String> {
+        Self::open_in(&data_dir().join("profiles"), name)
+    }
+
+    //Opens the profile that was used last, or the default profile
+    pub fn open_last() -> Result<Self, String> {
+        let last = fs::read_to_string(data_dir().join(LAST_PROFILE_FILE)).unwrap_or_default();
+        match Self::open(last.trim()) {
+            Ok(profile) => Ok(profile),
+            Err(_) => Self::open(DEFAULT_PROFILE_NAME),
+        }
+    }
+
+    //Remembers this profile so that it is opened the next time the game starts
+    pub fn set_last(&self) -> Result<(), String> {
+        fs::create_dir_all(data_dir()).map_err(|e| e.to_string())?;
+        fs::write(data_dir().join(LAST_PROFILE_FILE), &self.name).map_err(|e| e.to_string())
+    }
+
+    //Returns the path of a file in the directory of the profile
+    pub fn path(&self, file: &str) -> String {
+        self.dir.join(file).to_string_lossy().to_string()
+    }
+}
+
+//Copies the files in a directory into another directory
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.path().is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+//Returns the names of all profiles in `profiles_dir` in alphabetical order
+pub fn list_in(profiles_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(profiles_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| validate_name(name).is_ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+}
+
+pub fn list() -> Vec<String> {
+    list_in(&data_dir().join("profiles"))
+}
+
+//Returns the name of the profile after `name`, used to cycle
+//through the profiles on the main menu
+pub fn next_profile_name(names: &[String], name: &str) -> Option<String> {
+    let index = names.iter().position(|other| other == name);
+    let next = index.map(|index| (index + 1) % names.len()).unwrap_or(0);
+    names.get(next).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("alice").is_ok());
+        assert!(validate_name("bob_2-b").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../alice").is_err());
+        assert!(validate_name("a b").is_err());
+        assert!(validate_name(&"a".repeat(MAX_PROFILE_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_profiles_have_separate_files() {
+        let dir = env::temp_dir().join(format!("scale-the-tower-profiles-{}", std::process::id()));
+        let alice = Profile::open_in(&dir, "alice").expect("failed to create profile");
+        let bob = Profile::open_in(&dir, "bob").expect("failed to create profile");
+        assert_ne!(alice.path(HISCORES_FILE), bob.path(HISCORES_FILE));
+        assert_eq!(list_in(&dir), vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(Profile::open_in(&dir, "alice"), Ok(alice));
+
+        let names = list_in(&dir);
+        assert_eq!(next_profile_name(&names, "alice"), Some("bob".to_string()));
+        assert_eq!(next_profile_name(&names, "bob"), Some("alice".to_string()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate_legacy_files() {
+        let dir = env::temp_dir().join(format!("scale-the-tower-migrate-{}", std::process::id()));
+        let legacy_dir = dir.join("legacy");
+        let profiles_dir = dir.join("data").join("profiles");
+        fs::create_dir_all(legacy_dir.join(HISCORE_REPLAY_DIR)).expect("failed to create dir");
+        fs::write(legacy_dir.join(HISCORES_FILE), "hiscores 3\n").expect("failed to write");
+        fs::write(legacy_dir.join(KEY_FILE), "key").expect("failed to write");
+        fs::write(legacy_dir.join(HISCORE_REPLAY_DIR).join("ab12"), "replay")
+            .expect("failed to write");
+
+        let alice = Profile::open_with_legacy(&profiles_dir, &legacy_dir, "alice")
+            .expect("failed to create profile");
+        assert!(Path::new(&alice.path(HISCORES_FILE)).is_file());
+        assert!(Path::new(&alice.path(HISCORE_REPLAY_DIR))
+            .join("ab12")
+            .is_file());
+        assert_eq!(
+            fs::read_to_string(dir.join("data").join(KEY_FILE)).ok(),
+            Some("key".to_string())
+        );
+
+        //Only the first profile takes over the high scores
+        let bob = Profile::open_with_legacy(&profiles_dir, &legacy_dir, "bob")
+            .expect("failed to create profile");
+        assert!(!Path::new(&bob.path(HISCORES_FILE)).exists());
+        assert!(!Path::new(&bob.path(HISCORE_REPLAY_DIR)).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
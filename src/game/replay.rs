@@ -0,0 +1,281 @@
+use super::{hiscore::rules_digest, input_config::Action};
+use crate::level::{difficulty::Difficulty, generate_level::TowerSize};
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+//A single action that was pressed or released on a certain tick
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayEvent {
+    pub tick: u64,
+    pub action: Action,
+    pub pressed: bool,
+}
+
+//Stores the seed, size and difficulty of the tower and every action
+//the player pressed or released so that a run can be played back
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub tower_size: TowerSize,
+    pub difficulty: Difficulty,
+    //Digest of the enemy definitions and difficulty profile the run
+    //was played with, `-` if it is not known
+    pub rules: String,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, tower_size: TowerSize, difficulty: Difficulty) -> Self {
+        Self {
+            seed,
+            tower_size,
+            difficulty,
+            rules: rules_digest(difficulty),
+            events: vec![],
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, action: Action, pressed: bool) {
+        self.events.push(ReplayEvent {
+            tick,
+            action,
+            pressed,
+        });
+    }
+
+    //Replays are stored as text, the first line is the seed, the second
+    //line is the size of the tower, the third line is the difficulty, the
+    //fourth line is the digest of the rules and each line after that is
+    //an event of the form: tick press/release action
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "seed {}\ntower {}\ndifficulty {}\nrules {}\n",
+            self.seed,
+            self.tower_size.to_text(),
+            self.difficulty.name(),
+            self.rules
+        );
+        for event in &self.events {
+            let state = if event.pressed { "press" } else { "release" };
+            text.push_str(format!("{} {} {}\n", event.tick, state, event.action.name()).as_str());
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().peekable();
+
+        let seed = match lines.next() {
+            Some((_, line)) => line
+                .strip_prefix("seed ")
+                .and_then(|seed| seed.trim().parse().ok())
+                .ok_or("replay is missing a seed")?,
+            None => return Err("replay is empty".to_string()),
+        };
+
+        //Replays recorded before the size of the tower could be
+        //changed do not have a size and use the standard size
+        let tower_size = match lines
+            .peek()
+            .and_then(|(_, line)| line.strip_prefix("tower "))
+        {
+            Some(size) => {
+                let size = TowerSize::parse(size.trim()).map_err(|msg| format!("line 2: {msg}"))?;
+                lines.next();
+                size
+            }
+            None => TowerSize::STANDARD,
+        };
+
+        //Replays recorded before there were difficulties use normal
+        let difficulty = match lines.peek().and_then(|(i, line)| {
+            line.strip_prefix("difficulty ")
+                .map(|name| (i + 1, name.trim()))
+        }) {
+            Some((line, name)) => {
+                let difficulty = Difficulty::from_name(name)
+                    .ok_or(format!("line {line}: invalid difficulty: {name}"))?;
+                lines.next();
+                difficulty
+            }
+            None => Difficulty::Normal,
+        };
+
+        //The rules of older replays are not known
+        let rules = match lines
+            .peek()
+            .and_then(|(_, line)| line.strip_prefix("rules "))
+        {
+            Some(rules) => {
+                let rules = rules.trim().to_string();
+                lines.next();
+                rules
+            }
+            None => "-".to_string(),
+        };
+
+        let mut replay = Self::new(seed, tower_size, difficulty);
+        replay.rules = rules;
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let split: Vec<&str> = line.split_whitespace().collect();
+            if split.len() != 3 {
+                return Err(format!(
+                    "line {}: expected: tick press/release action",
+                    i + 1
+                ));
+            }
+
+            let tick = split[0]
+                .parse()
+                .map_err(|_| format!("line {}: invalid tick: {}", i + 1, split[0]))?;
+            let pressed = match split[1] {
+                "press" => true,
+                "release" => false,
+                other => return Err(format!("line {}: invalid event: {other}", i + 1)),
+            };
+
+            let action = Action::from_name(split[2]).ok_or(format!(
+                "line {}: invalid action: {}",
+                i + 1,
+                split[2]
+            ))?;
+            replay.record(tick, action, pressed);
+        }
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).map_err(|e| e.to_string())?;
+        Self::from_text(&buf)
+    }
+}
+
+//Feeds the events of a replay back into the game one tick at a time
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_event: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_event: 0,
+        }
+    }
+
+    //Returns all events that should be applied before simulating `tick`
+    pub fn events_for_tick(&mut self, tick: u64) -> Vec<ReplayEvent> {
+        let mut events = vec![];
+        while self.next_event < self.replay.events.len()
+            && self.replay.events[self.next_event].tick <= tick
+        {
+            events.push(self.replay.events[self.next_event].clone());
+            self.next_event += 1;
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::SfxPlayer,
+        game::{GameScreen, State},
+        level::{room_template::load_room_templates, Level},
+    };
+
+    fn start_run(seed: u64) -> State {
+        let mut state = State::starting_state();
+        let templates = load_room_templates("assets/room_templates");
+        let (level, enemies) =
+            Level::generate_level(&templates, seed, TowerSize::PRACTICE, Difficulty::Normal);
+        state.level = level;
+        state.enemies = enemies;
+        state.seed = seed;
+        state.replay = Replay::new(seed, TowerSize::PRACTICE, Difficulty::Normal);
+        state.game_screen = GameScreen::Game;
+        state
+    }
+
+    #[test]
+    fn test_replay_text_round_trip() {
+        let size = TowerSize::new(10, 2).expect("invalid tower size");
+        let mut replay = Replay::new(99, size, Difficulty::Hard);
+        replay.record(0, Action::Right, true);
+        replay.record(30, Action::Up, true);
+        replay.record(45, Action::Right, false);
+        assert_eq!(Replay::from_text(&replay.to_text()), Ok(replay));
+    }
+
+    #[test]
+    fn test_invalid_replay() {
+        assert!(Replay::from_text("").is_err());
+        assert!(Replay::from_text("seed 1\n10 hold Up\n").is_err());
+        assert!(Replay::from_text("seed 1\nten press Up\n").is_err());
+        assert!(Replay::from_text("seed 1\ntower 0x0\n").is_err());
+        assert!(Replay::from_text("seed 1\n10 press Jump\n").is_err());
+        assert!(Replay::from_text("seed 1\ntower 8x1\ndifficulty brutal\n").is_err());
+        //Replays without a tower size or difficulty use the standard size on normal
+        let replay = Replay::from_text("seed 1\n10 press Up\n");
+        assert_eq!(
+            replay.as_ref().map(|r| r.tower_size),
+            Ok(TowerSize::STANDARD)
+        );
+        assert_eq!(
+            replay.as_ref().map(|r| r.difficulty),
+            Ok(Difficulty::Normal)
+        );
+        assert_eq!(replay.map(|r| r.rules), Ok("-".to_string()));
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let sfx_player = SfxPlayer::silent();
+        let inputs = [
+            (0, Action::Right, true),
+            (60, Action::Up, true),
+            (61, Action::Up, false),
+            (300, Action::Right, false),
+            (300, Action::Left, true),
+            (420, Action::Attack, true),
+            (421, Action::Attack, false),
+        ];
+
+        //Play the game by applying actions directly and record them
+        let mut state = start_run(7);
+        for tick in 0..600 {
+            for (_, action, pressed) in inputs.iter().filter(|(t, _, _)| *t == tick) {
+                state.record_action(*action, *pressed, &sfx_player);
+            }
+            state.update_fixed_step(&sfx_player);
+        }
+
+        //Play it back and check that we end up in the same place
+        let mut playback = start_run(7);
+        playback.playback = Some(ReplayPlayer::new(state.replay.clone()));
+        for _ in 0..600 {
+            playback.update_fixed_step(&sfx_player);
+        }
+
+        assert_eq!(state.player_position(), playback.player_position());
+        assert_eq!(state.player.score, playback.player.score);
+        assert_eq!(state.enemies.len(), playback.enemies.len());
+    }
+}
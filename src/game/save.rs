@@ -0,0 +1,389 @@
+use super::{
+    hiscore::rules_digest, input_config::Action, GameScreen, Player, Projectile, State, Weapon,
+};
+use crate::{
+    audio::SfxPlayer,
+    level::{
+        difficulty::Difficulty, generate_level::TowerSize, room_template::RoomTemplate,
+        InteractiveTileSprite, Level,
+    },
+    parse::parse_value,
+    sprite::{enemy::Enemy, Sprite},
+};
+use cgmath::vec2;
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+//Movement actions that are released when a run is continued since
+//the keys that were held when the run was saved are no longer held
+const MOVEMENT_ACTIONS: [Action; 4] = [Action::Left, Action::Right, Action::Up, Action::Down];
+
+/*
+ * Saved runs are stored as text, the tower is generated again from the
+ * seed, size and difficulty so only the parts of the run that change
+ * are saved:
+ *
+ * seed 1234
+ * tower 48x1
+ * difficulty normal
+ * rules digest
+ * tick 3600
+ * player x y velocity_x velocity_y flipped score health max_health
+ *        damage_cooldown falling climbing attack_cooldown attack_timer
+ *        arrows weapon highest_floor
+ * tile type x y
+ * enemy type x y velocity_x velocity_y flipped health falling
+ *       damage_cooldown attack_cooldown idle_cooldown state
+ *       [target x y] [seen x y search_cooldown]
+ * projectile type x y w h velocity_x velocity_y flipped
+ * event tick press/release action
+ *
+ * There is a line for every pickup, enemy and projectile that is left
+ * in the tower and a line for every event in the replay of the run.
+ * Runs saved before there were difficulties are continued on normal,
+ * the rules line is the digest of the enemy definitions and difficulty
+ * profile that are stored in the replay of the run.
+ * */
+
+fn player_to_text(player: &Player) -> String {
+    let spr = &player.player_spr;
+    let weapon = match player.weapon {
+        Weapon::Sword => "sword",
+        Weapon::Bow => "bow",
+    };
+    format!(
+        "{} {} {} {} {} {} {} {} {} {} {} {} {} {} {weapon} {}",
+        spr.position.x,
+        spr.position.y,
+        spr.velocity.x,
+        spr.velocity.y,
+        spr.flipped,
+        player.score,
+        player.player_health,
+        player.max_player_health,
+        player.damage_cooldown,
+        player.falling,
+        player.climbing,
+        player.attack_cooldown,
+        player.attack_timer,
+        player.arrows,
+        player.highest_floor,
+    )
+}
+
+fn player_from_text(text: &str) -> Result<Player, String> {
+    let split: Vec<&str> = text.split_whitespace().collect();
+    if split.len() != 16 {
+        return Err("expected: x y velocity_x velocity_y flipped score health max_health damage_cooldown falling climbing attack_cooldown attack_timer arrows weapon highest_floor".to_string());
+    }
+
+    let mut player = Player::start_state();
+    let spr = &mut player.player_spr;
+    spr.position = vec2(parse_value(split[0], "x")?, parse_value(split[1], "y")?);
+    spr.save_position();
+    spr.velocity = vec2(
+        parse_value(split[2], "velocity_x")?,
+        parse_value(split[3], "velocity_y")?,
+    );
+    spr.flipped = parse_value(split[4], "flipped")?;
+    player.score = parse_value(split[5], "score")?;
+    player.player_health = parse_value(split[6], "health")?;
+    player.max_player_health = parse_value(split[7], "max_health")?;
+    player.damage_cooldown = parse_value(split[8], "damage_cooldown")?;
+    player.falling = parse_value(split[9], "falling")?;
+    player.climbing = parse_value(split[10], "climbing")?;
+    player.attack_cooldown = parse_value(split[11], "attack_cooldown")?;
+    player.attack_timer = parse_value(split[12], "attack_timer")?;
+    player.arrows = parse_value(split[13], "arrows")?;
+    player.weapon = match split[14] {
+        "sword" => Weapon::Sword,
+        "bow" => Weapon::Bow,
+        other => return Err(format!("invalid weapon: {other}")),
+    };
+    player.highest_floor = parse_value(split[15], "highest_floor")?;
+    Ok(player)
+}
+
+fn projectile_to_text(projectile: Projectile, spr: &Sprite) -> Option<String> {
+    let projectile = match projectile {
+        Projectile::Fireball => "fireball",
+        Projectile::Arrow => "arrow",
+        Projectile::Destroyed => return None,
+    };
+    Some(format!(
+        "{projectile} {} {} {} {} {} {} {}",
+        spr.position.x,
+        spr.position.y,
+        spr.dimensions.x,
+        spr.dimensions.y,
+        spr.velocity.x,
+        spr.velocity.y,
+        spr.flipped
+    ))
+}
+
+fn projectile_from_text(text: &str) -> Result<(Projectile, Sprite), String> {
+    let split: Vec<&str> = text.split_whitespace().collect();
+    if split.len() != 8 {
+        return Err("expected: type x y w h velocity_x velocity_y flipped".to_string());
+    }
+
+    let projectile = match split[0] {
+        "fireball" => Projectile::Fireball,
+        "arrow" => Projectile::Arrow,
+        other => return Err(format!("invalid projectile: {other}")),
+    };
+    let mut spr = Sprite::new(
+        parse_value(split[1], "x")?,
+        parse_value(split[2], "y")?,
+        parse_value(split[3], "w")?,
+        parse_value(split[4], "h")?,
+    );
+    spr.velocity = vec2(
+        parse_value(split[5], "velocity_x")?,
+        parse_value(split[6], "velocity_y")?,
+    );
+    spr.flipped = parse_value(split[7], "flipped")?;
+    Ok((projectile, spr))
+}
+
+impl State {
+    pub fn run_to_text(&self) -> String {
+        let mut text = format!(
+            "seed {}\ntower {}\ndifficulty {}\nrules {}\ntick {}\nplayer {}\n",
+            self.seed,
+            self.tower_size.to_text(),
+            self.difficulty.name(),
+            self.replay.rules,
+            self.tick,
+            player_to_text(&self.player)
+        );
+
+        for tile in self.level.interactive_tiles() {
+            text.push_str(format!("tile {}\n", tile.to_text()).as_str());
+        }
+
+        for enemy in &self.enemies {
+            text.push_str(format!("enemy {}\n", enemy.to_text()).as_str());
+        }
+
+        for (projectile, spr) in &self.projectiles {
+            if let Some(projectile) = projectile_to_text(*projectile, spr) {
+                text.push_str(format!("projectile {projectile}\n").as_str());
+            }
+        }
+
+        for event in &self.replay.events {
+            let state = if event.pressed { "press" } else { "release" };
+            text.push_str(
+                format!("event {} {state} {}\n", event.tick, event.action.name()).as_str(),
+            );
+        }
+
+        text
+    }
+
+    //Restores a run from text created by `run_to_text`, the tower is
+    //generated again from the saved seed with `room_templates`
+    pub fn load_run_text(
+        &mut self,
+        text: &str,
+        room_templates: &[RoomTemplate],
+    ) -> Result<(), String> {
+        let mut seed = None;
+        let mut tower_size = None;
+        let mut difficulty = Difficulty::Normal;
+        let mut rules = None;
+        let mut tick = None;
+        let mut player = None;
+        let mut tiles = vec![];
+        let mut enemies = vec![];
+        let mut projectiles = vec![];
+        let mut events = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let res = match key {
+                "seed" => parse_value(value, "seed").map(|value| seed = Some(value)),
+                "tower" => TowerSize::parse(value).map(|value| tower_size = Some(value)),
+                "difficulty" => Difficulty::from_name(value)
+                    .map(|value| difficulty = value)
+                    .ok_or(format!("invalid difficulty: {value}")),
+                "rules" => {
+                    rules = Some(value.to_string());
+                    Ok(())
+                }
+                "tick" => parse_value(value, "tick").map(|value| tick = Some(value)),
+                "player" => player_from_text(value).map(|value| player = Some(value)),
+                "tile" => InteractiveTileSprite::from_text(value).map(|tile| tiles.push(tile)),
+                "enemy" => Enemy::from_text(value).map(|enemy| enemies.push(enemy)),
+                "projectile" => projectile_from_text(value).map(|proj| projectiles.push(proj)),
+                "event" => {
+                    let split: Vec<&str> = value.split_whitespace().collect();
+                    if split.len() != 3 {
+                        Err("expected: tick press/release action".to_string())
+                    } else {
+                        match split[1] {
+                            "press" => Ok(true),
+                            "release" => Ok(false),
+                            other => Err(format!("invalid event: {other}")),
+                        }
+                        .and_then(|pressed| {
+                            let tick: u64 = parse_value(split[0], "tick")?;
+                            let action = Action::from_name(split[2])
+                                .ok_or(format!("invalid action: {}", split[2]))?;
+                            events.push((tick, action, pressed));
+                            Ok(())
+                        })
+                    }
+                }
+                other => Err(format!("unknown entry: {other}")),
+            };
+            res.map_err(|msg| format!("line {}: {msg}", i + 1))?;
+        }
+
+        let seed = seed.ok_or("saved run is missing a seed")?;
+        let tower_size = tower_size.ok_or("saved run is missing the tower size")?;
+        let tick = tick.ok_or("saved run is missing the tick")?;
+        let player = player.ok_or("saved run is missing the player")?;
+
+        let (mut level, _) = Level::generate_level(room_templates, seed, tower_size, difficulty);
+        level.set_interactive_tiles(tiles);
+
+        self.seed = seed;
+        self.tower_size = tower_size;
+        self.difficulty = difficulty;
+        self.tick = tick;
+        self.player = player;
+        self.level = level;
+        self.enemies = enemies;
+        //Boss floors whose boss was already defeated are opened again
+        self.unlock_boss_floors();
+        self.projectiles = projectiles;
+        self.particles.clear();
+        self.new_highscore = false;
+        self.playback = None;
+        self.replay.seed = seed;
+        self.replay.tower_size = tower_size;
+        self.replay.difficulty = difficulty;
+        //Runs saved before the rules were stored are continued with the current rules
+        self.replay.rules = rules.unwrap_or_else(|| rules_digest(difficulty));
+        self.replay.events.clear();
+        for (event_tick, action, pressed) in events {
+            self.replay.record(event_tick, action, pressed);
+        }
+
+        //Release any movement keys that were held when the run was saved
+        let sfx_player = SfxPlayer::silent();
+        for action in MOVEMENT_ACTIONS {
+            self.record_action(action, false, &sfx_player);
+        }
+
+        self.game_screen = GameScreen::Game;
+        Ok(())
+    }
+
+    pub fn save_run(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.run_to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn load_run(&mut self, path: &str, room_templates: &[RoomTemplate]) -> Result<(), String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).map_err(|e| e.to_string())?;
+        self.load_run_text(&buf, room_templates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::replay::Replay;
+    use crate::level::room_template::load_room_templates;
+
+    fn start_run(seed: u64, templates: &[RoomTemplate]) -> State {
+        let mut state = State::starting_state();
+        let (level, enemies) =
+            Level::generate_level(templates, seed, TowerSize::PRACTICE, Difficulty::Hard);
+        state.level = level;
+        state.enemies = enemies;
+        state.seed = seed;
+        state.tower_size = TowerSize::PRACTICE;
+        state.difficulty = Difficulty::Hard;
+        state.replay = Replay::new(seed, TowerSize::PRACTICE, Difficulty::Hard);
+        state.game_screen = GameScreen::Game;
+        state
+    }
+
+    #[test]
+    fn test_saved_run_round_trip() {
+        let sfx_player = SfxPlayer::silent();
+        let templates = load_room_templates("assets/room_templates");
+        let mut state = start_run(11, &templates);
+        state.record_action(Action::Right, true, &sfx_player);
+        for _ in 0..240 {
+            state.update_fixed_step(&sfx_player);
+        }
+        state.record_action(Action::Right, false, &sfx_player);
+        state.record_action(Action::Bow, true, &sfx_player);
+        state.record_action(Action::Attack, true, &sfx_player);
+        state.update_fixed_step(&sfx_player);
+
+        let text = state.run_to_text();
+        //Loading a run releases the movement keys
+        for action in MOVEMENT_ACTIONS {
+            state.record_action(action, false, &sfx_player);
+        }
+        let mut loaded = State::starting_state();
+        assert_eq!(loaded.load_run_text(&text, &templates), Ok(()));
+        assert_eq!(loaded.tick, state.tick);
+        assert_eq!(loaded.difficulty, Difficulty::Hard);
+        assert_eq!(loaded.player_position(), state.player_position());
+        assert_eq!(loaded.player.score, state.player.score);
+        assert_eq!(loaded.player.arrows, state.player.arrows);
+        assert_eq!(loaded.player.highest_floor, state.player.highest_floor);
+        assert!(loaded.player.weapon == Weapon::Bow);
+        assert_eq!(loaded.enemies.len(), state.enemies.len());
+        assert_eq!(loaded.projectiles.len(), state.projectiles.len());
+        assert!(loaded.level.interactive_tiles() == state.level.interactive_tiles());
+        assert_eq!(loaded.replay, state.replay);
+
+        //Both runs should keep going the same way after being loaded
+        for _ in 0..240 {
+            state.update_fixed_step(&sfx_player);
+            loaded.update_fixed_step(&sfx_player);
+        }
+        assert_eq!(loaded.player_position(), state.player_position());
+        assert_eq!(loaded.enemies.len(), state.enemies.len());
+    }
+
+    #[test]
+    fn test_invalid_saved_run() {
+        let templates = load_room_templates("assets/room_templates");
+        let mut state = State::starting_state();
+        assert!(state.load_run_text("", &templates).is_err());
+        assert!(state
+            .load_run_text("seed 1\ntower 8x1\ntick 0\n", &templates)
+            .is_err());
+        assert!(state
+            .load_run_text("seed 1\ntower 8x1\ntick 0\nenemy dragon 1 1\n", &templates)
+            .is_err());
+        assert!(state
+            .load_run_text(
+                "seed 1\ntower 8x1\ntick 0\nenemy slime 1 1 0 0 false 1 false 0 0 0 search seen 1\n",
+                &templates
+            )
+            .is_err());
+        assert!(state
+            .load_run_text("seed 1\ntower 8x1\ndifficulty brutal\ntick 0\n", &templates)
+            .is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{
+    gfx::VertexArrayObject,
+    level::difficulty::{Difficulty, DIFFICULTIES},
+    parse::parse_value,
+    shader::ShaderProgram,
+    ui,
+};
+use cgmath::{Deg, Matrix4};
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+//Window sizes that can be picked when the game is not fullscreen
+pub const RESOLUTIONS: [(u32, u32); 6] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1366, 768),
+    (1600, 900),
+    (1920, 1080),
+];
+const VOLUME_STEP: u32 = 10;
+const MAX_VOLUME: u32 = 100;
+const FOV_STEP: u32 = 5;
+const MIN_FOV: u32 = 60;
+const MAX_FOV: u32 = 110;
+
+//Rows of the settings screen, in the order they are listed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting {
+    MasterVolume,
+    SfxVolume,
+    Fullscreen,
+    Resolution,
+    Vsync,
+    FieldOfView,
+    ScreenShake,
+    Particles,
+    Difficulty,
+}
+
+pub const SETTINGS: [Setting; 9] = [
+    Setting::MasterVolume,
+    Setting::SfxVolume,
+    Setting::Fullscreen,
+    Setting::Resolution,
+    Setting::Vsync,
+    Setting::FieldOfView,
+    Setting::ScreenShake,
+    Setting::Particles,
+    Setting::Difficulty,
+];
+
+impl Setting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::MasterVolume => "Master Volume",
+            Setting::SfxVolume => "Sound Effects",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::Resolution => "Resolution",
+            Setting::Vsync => "VSync",
+            Setting::FieldOfView => "Field of View",
+            Setting::ScreenShake => "Screen Shake",
+            Setting::Particles => "Particles",
+            Setting::Difficulty => "Difficulty",
+        }
+    }
+}
+
+//Options that are picked on the settings screen, every profile has its own
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Settings {
+    //Volumes are percentages
+    pub master_volume: u32,
+    pub sfx_volume: u32,
+    pub fullscreen: bool,
+    //Size of the window when the game is not fullscreen
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    //Vertical field of view in degrees
+    pub fov: u32,
+    pub screen_shake: bool,
+    pub particles: bool,
+    //Difficulty of the towers that are started from the main menu
+    pub difficulty: Difficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: MAX_VOLUME,
+            sfx_volume: MAX_VOLUME,
+            fullscreen: false,
+            resolution: RESOLUTIONS[0],
+            vsync: true,
+            fov: 75,
+            screen_shake: true,
+            particles: true,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+//Adds `step` to a value and keeps it between min and max
+fn step_value(value: u32, step: i32, min: u32, max: u32) -> u32 {
+    (value as i64 + step as i64).clamp(min as i64, max as i64) as u32
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+impl Settings {
+    //Volume that sound effects are played at, from 0 to 1
+    pub fn effects_volume(&self) -> f32 {
+        (self.master_volume * self.sfx_volume) as f32 / (MAX_VOLUME * MAX_VOLUME) as f32
+    }
+
+    //Projection matrix for a window with this aspect ratio (width / height)
+    pub fn perspective(&self, aspect: f32) -> Matrix4<f32> {
+        cgmath::perspective(Deg(self.fov as f32), aspect, 0.1, 1000.0)
+    }
+
+    //Changes a setting by one step in the direction of `direction`
+    //(1 or -1), settings that are on or off are toggled
+    pub fn change(&mut self, setting: Setting, direction: i32) {
+        match setting {
+            Setting::MasterVolume => {
+                let step = direction * VOLUME_STEP as i32;
+                self.master_volume = step_value(self.master_volume, step, 0, MAX_VOLUME);
+            }
+            Setting::SfxVolume => {
+                let step = direction * VOLUME_STEP as i32;
+                self.sfx_volume = step_value(self.sfx_volume, step, 0, MAX_VOLUME);
+            }
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::Resolution => {
+                //Sizes that are not in the list (from editing the file) go back to the first one
+                let index = RESOLUTIONS
+                    .iter()
+                    .position(|size| *size == self.resolution)
+                    .map(|index| {
+                        step_value(index as u32, direction, 0, RESOLUTIONS.len() as u32 - 1)
+                    })
+                    .unwrap_or(0);
+                self.resolution = RESOLUTIONS[index as usize];
+            }
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::FieldOfView => {
+                let step = direction * FOV_STEP as i32;
+                self.fov = step_value(self.fov, step, MIN_FOV, MAX_FOV);
+            }
+            Setting::ScreenShake => self.screen_shake = !self.screen_shake,
+            Setting::Particles => self.particles = !self.particles,
+            Setting::Difficulty => {
+                let index = DIFFICULTIES
+                    .iter()
+                    .position(|difficulty| *difficulty == self.difficulty)
+                    .unwrap_or(0) as u32;
+                let index = step_value(index, direction, 0, DIFFICULTIES.len() as u32 - 1);
+                self.difficulty = DIFFICULTIES[index as usize];
+            }
+        }
+    }
+
+    //Returns the value of a setting as it is shown on the settings screen
+    pub fn value_text(&self, setting: Setting) -> String {
+        match setting {
+            Setting::MasterVolume => format!("{}%", self.master_volume),
+            Setting::SfxVolume => format!("{}%", self.sfx_volume),
+            Setting::Fullscreen => on_off(self.fullscreen),
+            Setting::Resolution => format!("{}x{}", self.resolution.0, self.resolution.1),
+            Setting::Vsync => on_off(self.vsync),
+            Setting::FieldOfView => format!("{}", self.fov),
+            Setting::ScreenShake => on_off(self.screen_shake),
+            Setting::Particles => on_off(self.particles),
+            Setting::Difficulty => self.difficulty.name().to_string(),
+        }
+    }
+
+    //Settings are stored as text with one setting on each line:
+    //name value
+    pub fn to_text(self) -> String {
+        format!(
+            "master_volume {}\nsfx_volume {}\nfullscreen {}\nresolution {}x{}\nvsync {}\nfov {}\nscreen_shake {}\nparticles {}\ndifficulty {}\n",
+            self.master_volume,
+            self.sfx_volume,
+            self.fullscreen,
+            self.resolution.0,
+            self.resolution.1,
+            self.vsync,
+            self.fov,
+            self.screen_shake,
+            self.particles,
+            self.difficulty.name()
+        )
+    }
+
+    //Settings that are missing from the text keep their default value
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut settings = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let split: Vec<&str> = line.split_whitespace().collect();
+            let res = match split[..] {
+                [] => Ok(()),
+                ["master_volume", volume] => parse_value(volume, "master_volume")
+                    .map(|volume: u32| settings.master_volume = volume.min(MAX_VOLUME)),
+                ["sfx_volume", volume] => parse_value(volume, "sfx_volume")
+                    .map(|volume: u32| settings.sfx_volume = volume.min(MAX_VOLUME)),
+                ["fullscreen", value] => {
+                    parse_value(value, "fullscreen").map(|value| settings.fullscreen = value)
+                }
+                ["resolution", size] => match size.split_once('x') {
+                    Some((w, h)) => parse_value(w, "width").and_then(|w| {
+                        parse_value(h, "height").map(|h| settings.resolution = (w, h))
+                    }),
+                    None => Err(format!("invalid resolution: {size}")),
+                },
+                ["vsync", value] => parse_value(value, "vsync").map(|value| settings.vsync = value),
+                ["fov", fov] => parse_value(fov, "fov")
+                    .map(|fov: u32| settings.fov = fov.clamp(MIN_FOV, MAX_FOV)),
+                ["screen_shake", value] => {
+                    parse_value(value, "screen_shake").map(|value| settings.screen_shake = value)
+                }
+                ["particles", value] => {
+                    parse_value(value, "particles").map(|value| settings.particles = value)
+                }
+                ["difficulty", name] => Difficulty::from_name(name)
+                    .map(|difficulty| settings.difficulty = difficulty)
+                    .ok_or(format!("invalid difficulty: {name}")),
+                _ => Err(format!("invalid setting: {line}")),
+            };
+            res.map_err(|msg| format!("line {}: {msg}", i + 1))?;
+        }
+        Ok(settings)
+    }
+
+    //Loads settings from a file, a profile without a
+    //settings file starts with the default settings
+    pub fn load(path: &str) -> Self {
+        let mut buf = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+            Ok(_) => Self::from_text(&buf).unwrap_or_else(|msg| {
+                eprintln!("{path}: {msg}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+}
+
+//Shows the value of each setting between its buttons on the settings screen
+pub fn display_settings(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    settings: &Settings,
+) {
+    text_shader.uniform_vec4f("uColor", 1.0, 1.0, 1.0, 1.0);
+    for (i, setting) in SETTINGS.iter().enumerate() {
+        ui::display_ascii_text_centered(
+            rect_vao,
+            text_shader,
+            settings.value_text(*setting).as_bytes(),
+            ui::SETTINGS_VALUE_X,
+            ui::controls_row_y(i),
+            8.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_text_round_trip() {
+        let mut settings = Settings::default();
+        settings.change(Setting::MasterVolume, -1);
+        settings.change(Setting::Fullscreen, 1);
+        settings.change(Setting::Resolution, 1);
+        settings.change(Setting::FieldOfView, 1);
+        settings.change(Setting::ScreenShake, 1);
+        settings.change(Setting::Particles, 1);
+        settings.change(Setting::Difficulty, 1);
+        assert_eq!(Settings::from_text(&settings.to_text()), Ok(settings));
+        assert_eq!(settings.master_volume, 90);
+        assert_eq!(settings.resolution, (1024, 768));
+        assert_eq!(settings.fov, 80);
+        assert!(settings.fullscreen && !settings.screen_shake && !settings.particles);
+        assert_eq!(settings.difficulty, Difficulty::Hard);
+    }
+
+    #[test]
+    fn test_change_settings() {
+        let mut settings = Settings::default();
+        //Values stop at the ends of their range
+        settings.change(Setting::SfxVolume, 1);
+        assert_eq!(settings.sfx_volume, 100);
+        for _ in 0..20 {
+            settings.change(Setting::FieldOfView, -1);
+            settings.change(Setting::Resolution, -1);
+        }
+        assert_eq!(settings.fov, MIN_FOV);
+        assert_eq!(settings.resolution, RESOLUTIONS[0]);
+        settings.change(Setting::MasterVolume, -1);
+        settings.change(Setting::SfxVolume, -1);
+        assert!((settings.effects_volume() - 0.81).abs() < 0.001);
+        assert_eq!(settings.value_text(Setting::Vsync), "on");
+        settings.change(Setting::Vsync, -1);
+        assert_eq!(settings.value_text(Setting::Vsync), "off");
+        settings.change(Setting::Difficulty, -1);
+        settings.change(Setting::Difficulty, -1);
+        assert_eq!(settings.value_text(Setting::Difficulty), "easy");
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(Settings::from_text("fov wide\n").is_err());
+        assert!(Settings::from_text("resolution 800\n").is_err());
+        assert!(Settings::from_text("brightness 10\n").is_err());
+        assert!(Settings::from_text("difficulty brutal\n").is_err());
+        //Missing settings keep their default value
+        let settings = Settings::from_text("vsync false\nfov 500\n").expect("failed to parse");
+        assert!(!settings.vsync);
+        assert_eq!(settings.fov, MAX_FOV);
+        assert_eq!(settings.master_volume, 100);
+    }
+}
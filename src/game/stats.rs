@@ -0,0 +1,402 @@
+use super::hiscore::RunEnd;
+use crate::{
+    gfx::VertexArrayObject, level::InteractiveTile, parse::parse_value, shader::ShaderProgram,
+    sprite::enemy::EnemyType, ui,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{Read, Write},
+};
+
+const PICKUPS: [InteractiveTile; 5] = [
+    InteractiveTile::SmallGold,
+    InteractiveTile::Gold,
+    InteractiveTile::Heal,
+    InteractiveTile::HealthBoost,
+    InteractiveTile::Arrows,
+];
+
+//Deaths that are not caused by an enemy,
+//these are listed before the deaths by each enemy
+const HAZARD_DEATHS: [RunEnd; 5] = [
+    RunEnd::Lava,
+    RunEnd::Spikes,
+    RunEnd::Fall,
+    RunEnd::Fireball,
+    RunEnd::Arrow,
+];
+
+pub struct Achievement {
+    //Name used in the statistics file
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    unlocked: fn(&Statistics) -> bool,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 10] = [
+    Achievement {
+        id: "first_blood",
+        name: "First Blood",
+        description: "Defeat an enemy",
+        unlocked: |stats| stats.total_kills() >= 1,
+    },
+    Achievement {
+        id: "exterminator",
+        name: "Exterminator",
+        description: "Defeat 100 enemies",
+        unlocked: |stats| stats.total_kills() >= 100,
+    },
+    Achievement {
+        id: "demon_hunter",
+        name: "Demon Hunter",
+        description: "Defeat 10 demons",
+        unlocked: |stats| stats.kills("demon") >= 10,
+    },
+    Achievement {
+        id: "gold_digger",
+        name: "Gold Digger",
+        description: "Pick up 100 piles of gold",
+        unlocked: |stats| {
+            stats.pickups(InteractiveTile::SmallGold) + stats.pickups(InteractiveTile::Gold) >= 100
+        },
+    },
+    Achievement {
+        id: "archer",
+        name: "Archer",
+        description: "Fire 100 arrows",
+        unlocked: |stats| stats.arrows_fired >= 100,
+    },
+    Achievement {
+        id: "halfway_there",
+        name: "Halfway There",
+        description: "Reach floor 24",
+        unlocked: |stats| stats.highest_floor >= 23,
+    },
+    Achievement {
+        id: "scaled_the_tower",
+        name: "Scaled the Tower",
+        description: "Make it to the top of the tower",
+        unlocked: |stats| stats.wins >= 1,
+    },
+    Achievement {
+        id: "hot_feet",
+        name: "Hot Feet",
+        description: "Fall into lava",
+        unlocked: |stats| stats.deaths(RunEnd::Lava.to_text()) >= 1,
+    },
+    Achievement {
+        id: "pecked",
+        name: "Pecked to Death",
+        description: "Be defeated by a chicken",
+        unlocked: |stats| stats.deaths("chicken") >= 1,
+    },
+    Achievement {
+        id: "persistent",
+        name: "Persistent",
+        description: "Play 25 runs",
+        unlocked: |stats| stats.runs >= 25,
+    },
+];
+
+//Statistics that are kept across every run of a profile
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Statistics {
+    pub runs: u32,
+    pub wins: u32,
+    //Highest floor reached in any run, the bottom floor is 0
+    pub highest_floor: u32,
+    pub arrows_fired: u32,
+    //Counts are stored by the name of the enemy, pickup or cause of death
+    kills: BTreeMap<String, u32>,
+    pickups: BTreeMap<String, u32>,
+    deaths: BTreeMap<String, u32>,
+    //Ids of the achievements that have been unlocked
+    achievements: BTreeSet<String>,
+}
+
+impl Statistics {
+    //Enemies and causes of death are looked up by name so that the counts
+    //of enemies that are no longer defined are still kept
+    pub fn kills(&self, enemy: &str) -> u32 {
+        self.kills.get(enemy).copied().unwrap_or(0)
+    }
+
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+
+    pub fn pickups(&self, tile: InteractiveTile) -> u32 {
+        self.pickups.get(tile.name()).copied().unwrap_or(0)
+    }
+
+    pub fn deaths(&self, cause: &str) -> u32 {
+        self.deaths.get(cause).copied().unwrap_or(0)
+    }
+
+    pub fn add_kill(&mut self, enemy_type: EnemyType) {
+        *self.kills.entry(enemy_type.name().to_string()).or_default() += 1;
+    }
+
+    pub fn add_pickup(&mut self, tile: InteractiveTile) {
+        *self.pickups.entry(tile.name().to_string()).or_default() += 1;
+    }
+
+    //Records the end of a run
+    pub fn add_run(&mut self, end: RunEnd, highest_floor: u32) {
+        self.runs += 1;
+        self.highest_floor = self.highest_floor.max(highest_floor);
+        if end == RunEnd::Won {
+            self.wins += 1;
+        } else {
+            *self.deaths.entry(end.to_text().to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn achievement_unlocked(&self, achievement: &Achievement) -> bool {
+        self.achievements.contains(achievement.id)
+    }
+
+    //Unlocks any achievements that were earned and returns their names
+    pub fn unlock_achievements(&mut self) -> Vec<&'static str> {
+        let mut unlocked = vec![];
+        for achievement in &ACHIEVEMENTS {
+            if !self.achievement_unlocked(achievement) && (achievement.unlocked)(self) {
+                self.achievements.insert(achievement.id.to_string());
+                unlocked.push(achievement.name);
+            }
+        }
+        unlocked
+    }
+
+    //Statistics are stored as text with one value on each line:
+    //runs/wins/highest_floor/arrows_fired count
+    //kill/pickup/death name count
+    //achievement id
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "runs {}\nwins {}\nhighest_floor {}\narrows_fired {}\n",
+            self.runs, self.wins, self.highest_floor, self.arrows_fired
+        );
+        for (key, counts) in [
+            ("kill", &self.kills),
+            ("pickup", &self.pickups),
+            ("death", &self.deaths),
+        ] {
+            for (name, count) in counts {
+                text.push_str(format!("{key} {name} {count}\n").as_str());
+            }
+        }
+        for id in &self.achievements {
+            text.push_str(format!("achievement {id}\n").as_str());
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut stats = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let split: Vec<&str> = line.split_whitespace().collect();
+            let res = match split[..] {
+                [] => Ok(()),
+                ["runs", count] => parse_value(count, "runs").map(|count| stats.runs = count),
+                ["wins", count] => parse_value(count, "wins").map(|count| stats.wins = count),
+                ["highest_floor", floor] => {
+                    parse_value(floor, "highest_floor").map(|floor| stats.highest_floor = floor)
+                }
+                ["arrows_fired", count] => {
+                    parse_value(count, "arrows_fired").map(|count| stats.arrows_fired = count)
+                }
+                ["kill", name, count] => parse_value(count, "count")
+                    .map(|count| stats.kills.insert(name.to_string(), count))
+                    .map(|_| ()),
+                ["pickup", name, count] => parse_value(count, "count")
+                    .map(|count| stats.pickups.insert(name.to_string(), count))
+                    .map(|_| ()),
+                ["death", name, count] => parse_value(count, "count")
+                    .map(|count| stats.deaths.insert(name.to_string(), count))
+                    .map(|_| ()),
+                ["achievement", id] => {
+                    stats.achievements.insert(id.to_string());
+                    Ok(())
+                }
+                _ => Err(format!("invalid statistic: {line}")),
+            };
+            res.map_err(|msg| format!("line {}: {msg}", i + 1))?;
+        }
+        Ok(stats)
+    }
+
+    //Loads statistics from a file, a profile without a
+    //statistics file starts with empty statistics
+    pub fn load(path: &str) -> Self {
+        let mut buf = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut buf)) {
+            Ok(_) => Self::from_text(&buf).unwrap_or_else(|msg| {
+                eprintln!("{path}: {msg}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+}
+
+//Displays a column of lines of text centered on x
+fn display_column(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    lines: &[String],
+    x: f32,
+    ch_size: f32,
+) {
+    for (i, line) in lines.iter().enumerate() {
+        ui::display_ascii_text_centered(
+            rect_vao,
+            text_shader,
+            line.as_bytes(),
+            x,
+            160.0 - i as f32 * 20.0,
+            ch_size,
+        );
+    }
+}
+
+pub fn display_statistics(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    stats: &Statistics,
+) {
+    let mut left = vec![
+        format!("runs: {}", stats.runs),
+        format!("wins: {}", stats.wins),
+        format!("highest floor: {}", stats.highest_floor + 1),
+        format!("arrows fired: {}", stats.arrows_fired),
+        String::new(),
+    ];
+    for tile in PICKUPS {
+        left.push(format!(
+            "{}: {}",
+            tile.name().replace('_', " "),
+            stats.pickups(tile)
+        ));
+    }
+
+    let mut right = vec![];
+    let enemy_types = EnemyType::all();
+    for enemy_type in &enemy_types {
+        right.push(format!(
+            "{} kills: {}",
+            enemy_type.name(),
+            stats.kills(enemy_type.name())
+        ));
+    }
+    right.push(String::new());
+    let enemy_deaths = enemy_types
+        .iter()
+        .map(|enemy_type| RunEnd::Enemy(*enemy_type));
+    for cause in HAZARD_DEATHS.into_iter().chain(enemy_deaths) {
+        right.push(format!(
+            "deaths by {}: {}",
+            cause.to_text(),
+            stats.deaths(cause.to_text())
+        ));
+    }
+
+    display_column(rect_vao, text_shader, &left, -200.0, 8.0);
+    display_column(rect_vao, text_shader, &right, 200.0, 8.0);
+}
+
+pub fn display_achievements(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    stats: &Statistics,
+) {
+    let lines: Vec<String> = ACHIEVEMENTS
+        .iter()
+        .map(|achievement| {
+            let check = if stats.achievement_unlocked(achievement) {
+                "x"
+            } else {
+                " "
+            };
+            format!(
+                "[{check}] {}: {}",
+                achievement.name, achievement.description
+            )
+        })
+        .collect();
+    display_column(rect_vao, text_shader, &lines, 0.0, 6.0);
+}
+
+//Lists the achievements that were unlocked at the end of a run below y
+pub fn display_new_achievements(
+    rect_vao: &VertexArrayObject,
+    text_shader: &ShaderProgram,
+    names: &[&str],
+    y: f32,
+) {
+    for (i, name) in names.iter().enumerate() {
+        ui::display_ascii_text_centered(
+            rect_vao,
+            text_shader,
+            format!("achievement unlocked: {name}").as_bytes(),
+            0.0,
+            y - i as f32 * 20.0,
+            8.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enemy(name: &str) -> EnemyType {
+        EnemyType::from_name(name).expect("enemy is not defined")
+    }
+
+    #[test]
+    fn test_statistics_text_round_trip() {
+        let mut stats = Statistics {
+            arrows_fired: 12,
+            ..Default::default()
+        };
+        stats.add_kill(enemy("slime"));
+        stats.add_kill(enemy("slime"));
+        stats.add_kill(enemy("demon"));
+        stats.add_pickup(InteractiveTile::Gold);
+        stats.add_run(RunEnd::Lava, 5);
+        stats.add_run(RunEnd::Won, 47);
+        stats.unlock_achievements();
+
+        let loaded = Statistics::from_text(&stats.to_text());
+        assert_eq!(loaded, Ok(stats.clone()));
+        assert_eq!(stats.kills("slime"), 2);
+        assert_eq!(stats.total_kills(), 3);
+        assert_eq!(stats.deaths("lava"), 1);
+        assert_eq!(stats.highest_floor, 47);
+        assert!(Statistics::from_text("runs many\n").is_err());
+    }
+
+    #[test]
+    fn test_unlock_achievements() {
+        let mut stats = Statistics::default();
+        assert!(stats.unlock_achievements().is_empty());
+
+        stats.add_kill(enemy("chicken"));
+        stats.add_run(RunEnd::Enemy(enemy("chicken")), 2);
+        assert_eq!(
+            stats.unlock_achievements(),
+            vec!["First Blood", "Pecked to Death"]
+        );
+        //Achievements are only unlocked once
+        assert!(stats.unlock_achievements().is_empty());
+    }
+}
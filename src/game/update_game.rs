@@ -1,11 +1,12 @@
-use super::{hiscore, player::PLAYER_CLIMB_SPEED, GameScreen, Projectile, State};
+use super::{
+    hiscore::{self, HighScore, RunEnd},
+    player::PLAYER_CLIMB_SPEED,
+    GameScreen, Projectile, State, SIM_TIMESTEP,
+};
 use crate::{
     audio::{sfx_ids, SfxPlayer},
-    level::{transparent, Tile},
-    sprite::{
-        particle::{Particle, ParticleType},
-        Sprite,
-    },
+    level::{floor_at, transparent, Tile, ROOM_SIZE},
+    sprite::particle::{Particle, ParticleType},
 };
 use cgmath::vec2;
 
@@ -44,6 +45,7 @@ impl State {
                 continue;
             }
 
+            self.enemies[i].sprite.save_position();
             self.enemies[i].sprite.update_animation_frame(dt);
             self.enemies[i].update(dt, &self.level, &player_pos, &mut self.projectiles);
 
@@ -88,7 +90,11 @@ impl State {
                     self.player.score += self.enemies[i].score();
                 }
             } else if self.player.player_spr.intersecting(&self.enemies[i].sprite) {
-                if self.player.apply_damage(self.enemies[i].get_damage()) {
+                let source = RunEnd::Enemy(self.enemies[i].enemy_type);
+                if self
+                    .player
+                    .apply_damage(self.enemies[i].get_damage(), source)
+                {
                     self.add_particles(
                         self.player_position().x,
                         self.player_position().y,
@@ -102,10 +108,27 @@ impl State {
                 self.enemies[i].reset_attack_cooldown();
             }
 
+            //Bosses are only hurt by arrows and their own fireballs fly through them
+            let mut arrow_hit = false;
             for (projectile, sprite) in &mut self.projectiles {
-                if self.enemies[i].sprite.intersecting(sprite) {
+                if !self.enemies[i].sprite.intersecting(sprite) {
+                    continue;
+                }
+
+                if !self.enemies[i].is_boss() {
                     self.enemies[i].health = 0;
                     *projectile = Projectile::Destroyed;
+                } else if *projectile == Projectile::Arrow {
+                    arrow_hit |= self.enemies[i].apply_damage(1);
+                    *projectile = Projectile::Destroyed;
+                }
+            }
+
+            if arrow_hit {
+                self.add_particles(enemy_pos.x, enemy_pos.y, 0.15, 3.0, ParticleType::Blood, 8);
+                sfx_player.play(sfx_ids::ENEMY_HIT);
+                if self.enemies[i].health <= 0 {
+                    self.player.score += self.enemies[i].score();
                 }
             }
         }
@@ -122,12 +145,36 @@ impl State {
             }
 
             if let Some(i) = index {
+                if self.tracking_stats() {
+                    self.stats.add_kill(self.enemies[i].enemy_type);
+                }
                 let enemy_pos = self.enemies[i].sprite.position;
                 self.add_particles(enemy_pos.x, enemy_pos.y, 0.15, 3.0, ParticleType::Blood, 32);
                 self.enemies.remove(i);
                 sfx_player.play(sfx_ids::EXPLODE);
             }
         }
+
+        if self.unlock_boss_floors() {
+            sfx_player.play(sfx_ids::POWERUP);
+        }
+    }
+
+    //Unlocks every boss floor that has no bosses left on it,
+    //returns true if a floor was unlocked
+    pub fn unlock_boss_floors(&mut self) -> bool {
+        let mut unlocked = false;
+        for floor in self.level.locked_floors().to_vec() {
+            let boss_alive = self
+                .enemies
+                .iter()
+                .any(|enemy| enemy.is_boss() && floor_at(enemy.sprite.position.y) == floor);
+            if !boss_alive {
+                self.level.unlock_floor(floor);
+                unlocked = true;
+            }
+        }
+        unlocked
     }
 
     pub fn update_particles(&mut self, dt: f32) {
@@ -137,6 +184,7 @@ impl State {
                 continue;
             }
 
+            particle.sprite.save_position();
             particle.update(&self.level, dt);
         }
 
@@ -169,39 +217,34 @@ impl State {
                 continue;
             }
 
-            if sprite.position.x.abs() >= 64.0 {
+            sprite.save_position();
+
+            //Projectiles that leave the sides of the tower are destroyed
+            if sprite.position.x < -1.0 || sprite.position.x > self.level.w() as f32 {
                 *projectile = Projectile::Destroyed;
             }
 
-            let top_left = vec2(sprite.position.x, sprite.position.y)
-                - vec2(
-                    sprite.dimensions.x.ceil() / 2.0 + 1.0,
-                    sprite.dimensions.y.ceil() / 2.0 + 1.0,
-                );
-            let bot_right = vec2(sprite.position.x, sprite.position.y)
-                + vec2(
-                    sprite.dimensions.x.ceil() / 2.0 + 1.0,
-                    sprite.dimensions.y.ceil() / 2.0 + 1.0,
-                );
-            let (top_left_x, top_left_y) = (top_left.x.floor() as i32, top_left.y.floor() as i32);
-            let (bot_right_x, bot_right_y) = (bot_right.x.ceil() as i32, bot_right.y.ceil() as i32);
-            for x in top_left_x..bot_right_x {
-                for y in top_left_y..bot_right_y {
-                    if !self.level.out_of_bounds(x, y)
-                        && !transparent(self.level.get_tile(x as u32, y as u32))
-                    {
-                        let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
-                        if sprite.intersecting(&hitbox) {
-                            if *projectile == Projectile::Fireball {
-                                destroyed_fireballs.push(sprite.position);
-                            }
-                            *projectile = Projectile::Destroyed;
-                        }
-                    }
+            //Projectiles that start inside of a tile or run into one are destroyed
+            let (tile_x, tile_y) = (
+                sprite.position.x.round() as i32,
+                sprite.position.y.round() as i32,
+            );
+            let inside_tile = !self.level.out_of_bounds(tile_x, tile_y)
+                && !transparent(self.level.get_tile(tile_x as u32, tile_y as u32));
+            let collisions = sprite.move_and_collide(sprite.velocity * dt, &self.level);
+            if inside_tile {
+                if *projectile == Projectile::Fireball {
+                    destroyed_fireballs.push(sprite.position);
+                }
+                *projectile = Projectile::Destroyed;
+            } else if let Some(collision) = collisions.first() {
+                //Burst into flames on the face of the tile that was hit
+                if *projectile == Projectile::Fireball {
+                    let hit_tile = vec2(collision.tile_x as f32, collision.tile_y as f32);
+                    destroyed_fireballs.push(hit_tile + collision.normal * 0.5);
                 }
+                *projectile = Projectile::Destroyed;
             }
-
-            sprite.position += sprite.velocity * dt;
         }
 
         for fireball in destroyed_fireballs {
@@ -225,17 +268,28 @@ impl State {
         }
     }
 
+    fn update_highest_floor(&mut self) {
+        let floor_height = ROOM_SIZE + 1;
+        let top_floor = (self.level.h().saturating_sub(1) / floor_height).saturating_sub(1);
+        let floor = (self.player_position().y.max(0.0) as u32 / floor_height).min(top_floor);
+        self.player.highest_floor = self.player.highest_floor.max(floor);
+    }
+
     pub fn update_game_screen(&mut self, dt: f32, sfx_player: &SfxPlayer) {
         let mut hit = false;
 
         //Update the player
         let falling = self.player.falling();
         let velocity_y = self.player.player_spr.velocity.y;
+        self.player.player_spr.save_position();
         self.player.update(dt, &self.level);
+        self.update_highest_floor();
         //Hit the ground, apply fall damage if player is travelling fast enough
         if falling && !self.player.falling() && velocity_y < -MAX_SAFE_FALL_SPEED {
-            self.player
-                .apply_damage(-((velocity_y + MAX_SAFE_FALL_SPEED) / 12.0).floor() as i32);
+            self.player.apply_damage(
+                -((velocity_y + MAX_SAFE_FALL_SPEED) / 12.0).floor() as i32,
+                RunEnd::Fall,
+            );
 
             sfx_player.play(sfx_ids::PLAYER_HIT);
             let player_pos = self.player_position();
@@ -255,6 +309,7 @@ impl State {
             .touching_tile(Tile::Lava, &self.level)
         {
             self.player.player_health = 0;
+            self.player.last_hit = RunEnd::Lava;
         }
         //Lose 2 health when you fall on a spike
         if self
@@ -263,18 +318,26 @@ impl State {
             .touching_tile(Tile::Spikes, &self.level)
             && self.player.player_spr.velocity.y < -PLAYER_CLIMB_SPEED
         {
-            hit = self.player.apply_damage(2);
+            hit = self.player.apply_damage(2, RunEnd::Spikes);
         }
         self.player.player_spr.update_animation_frame(dt);
         self.player.update_animation_state();
-        self.level
+        let pickup = self
+            .level
             .update_interactive_tiles(&mut self.player, sfx_player);
+        if let Some(tile) = pickup.filter(|_| self.tracking_stats()) {
+            self.stats.add_pickup(tile);
+        }
         self.player.damage_cooldown -= dt;
 
         let player_pos = self.player_position();
         for (projectile, sprite) in &mut self.projectiles {
             if self.player.player_spr.intersecting(sprite) {
-                hit = self.player.apply_damage(1);
+                let source = match projectile {
+                    Projectile::Arrow => RunEnd::Arrow,
+                    _ => RunEnd::Fireball,
+                };
+                hit = self.player.apply_damage(1, source);
                 *projectile = Projectile::Destroyed;
             }
         }
@@ -299,7 +362,27 @@ impl State {
         self.update_particles(dt);
     }
 
-    pub fn check_gameover(&mut self, highscores: &mut Vec<u32>, sfx_player: &SfxPlayer) {
+    //Simulates a single step of the game, if a replay is being played
+    //back then the actions for this step are applied first
+    pub fn update_fixed_step(&mut self, sfx_player: &SfxPlayer) {
+        if let Some(playback) = &mut self.playback {
+            let events = playback.events_for_tick(self.tick);
+            for event in events {
+                self.apply_action(event.action, event.pressed, sfx_player);
+            }
+        }
+
+        self.update_game_screen(SIM_TIMESTEP, sfx_player);
+        self.tick += 1;
+    }
+
+    //Returns true if the run ended this update, if the player got a new
+    //high score then it is added to the list of high scores
+    pub fn check_gameover(
+        &mut self,
+        highscores: &mut Vec<HighScore>,
+        sfx_player: &SfxPlayer,
+    ) -> bool {
         if self.player.player_health <= 0
             || (self.player_position().y > self.level.h() as f32 - 1.0 && !self.player.falling())
         {
@@ -323,15 +406,132 @@ impl State {
                 self.game_screen = GameScreen::WinScreen;
             }
 
-            //Check if the player got a new high score and if they
-            //did, write that highscore to a file
-            if hiscore::is_new_highscore(self.player.score, highscores) {
-                hiscore::add_highscore(self.player.score, highscores);
-                hiscore::write_highscores("hiscores", highscores);
+            let end = if self.game_screen == GameScreen::WinScreen {
+                RunEnd::Won
+            } else {
+                self.player.last_hit
+            };
+
+            self.new_achievements.clear();
+            if self.tracking_stats() {
+                self.stats.add_run(end, self.player.highest_floor);
+                self.new_achievements = self.stats.unlock_achievements();
+            }
+
+            //Check if the player got a new high score, runs that are played
+            //back from a replay are never added to the high scores
+            if self.playback.is_none() && hiscore::is_new_highscore(self.player.score, highscores) {
+                let entry = HighScore {
+                    name: self.player_name.clone(),
+                    score: self.player.score,
+                    floor: self.player.highest_floor,
+                    ticks: self.tick,
+                    seed: self.seed,
+                    difficulty: self.difficulty,
+                    rules: self.replay.rules.clone(),
+                    end,
+                    date: hiscore::current_date(),
+                    replay: hiscore::replay_digest(&self.replay),
+                    verified: true,
+                };
+                hiscore::add_highscore(entry, highscores);
                 self.new_highscore = true;
             } else {
                 self.new_highscore = false;
             }
+
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{player, replay::ReplayPlayer};
+    use crate::level::{generate_level::ceiling_gap, room_template::load_room_templates, Level};
+
+    const TEST_DT: f32 = 1.0 / 60.0;
+
+    fn start_run(seed: u64) -> State {
+        let mut state = State::starting_state();
+        let templates = load_room_templates("assets/room_templates");
+        let (level, enemies) =
+            Level::generate_level(&templates, seed, state.tower_size, state.difficulty);
+        state.level = level;
+        state.enemies = enemies;
+        state.game_screen = GameScreen::Game;
+        state
+    }
+
+    #[test]
+    fn test_boss_floor_unlocks() {
+        let sfx_player = SfxPlayer::silent();
+        let mut state = start_run(5);
+        assert!(state.level.locked_floors().contains(&15));
+        state.update_enemies(TEST_DT, &sfx_player);
+        assert!(state.level.locked_floors().contains(&15));
+
+        //Defeating the boss opens the ceiling of its floor
+        for enemy in &mut state.enemies {
+            if enemy.is_boss() && floor_at(enemy.sprite.position.y) == 15 {
+                enemy.health = 0;
+            }
         }
+        state.update_enemies(TEST_DT, &sfx_player);
+        assert!(!state.level.locked_floors().contains(&15));
+        assert!(state.level.locked_floors().contains(&31));
+        let y = 16 * (ROOM_SIZE + 1);
+        assert!(
+            ceiling_gap().all(|x| matches!(state.level.get_tile(x, y), Tile::Air | Tile::Ladder))
+        );
+        //The ladder of the boss room continues through the gap
+        assert!(ceiling_gap().any(|x| state.level.get_tile(x, y) == Tile::Ladder));
+    }
+
+    #[test]
+    fn test_simulate_without_gl() {
+        let sfx_player = SfxPlayer::silent();
+        let mut state = start_run(42);
+        let mut highscores = vec![];
+
+        for tick in 0..5000 {
+            //Run back and forth and jump every so often
+            if tick % 600 == 0 {
+                state.set_player_velocity_x(player::PLAYER_SPEED);
+            } else if tick % 600 == 300 {
+                state.set_player_velocity_x(-player::PLAYER_SPEED);
+            }
+
+            if tick % 45 == 0 && !state.player.falling() {
+                state.set_player_velocity_y(player::PLAYER_JUMP_SPEED);
+            }
+
+            if state.game_screen == GameScreen::Game {
+                state.update_game_screen(TEST_DT, &sfx_player);
+                state.check_gameover(&mut highscores, &sfx_player);
+            } else {
+                state.update_enemies(TEST_DT, &sfx_player);
+                state.update_projectiles(TEST_DT);
+                state.update_particles(TEST_DT);
+            }
+
+            let pos = state.player_position();
+            assert!(pos.x >= 0.0 && pos.y >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_playback_does_not_add_highscores() {
+        let sfx_player = SfxPlayer::silent();
+        let mut state = start_run(7);
+        state.playback = Some(ReplayPlayer::new(state.replay.clone()));
+        state.player.player_health = 0;
+        let mut highscores = vec![];
+        assert!(state.check_gameover(&mut highscores, &sfx_player));
+        assert!(highscores.is_empty());
+        assert!(!state.new_highscore);
     }
 }
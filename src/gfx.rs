@@ -29,6 +29,21 @@ pub fn load_image_pixels(path: &str) -> Result<(Vec<u32>, png::OutputInfo), Stri
     Ok((pixels, info))
 }
 
+//Saves pixels of the format alpha, b, g, r to a PNG file
+pub fn save_image_pixels(path: &str, pixels: &[u32], w: u32, h: u32) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(file, w, h);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+
+    let buf: Vec<u8> = pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .collect();
+    writer.write_image_data(&buf).map_err(|e| e.to_string())
+}
+
 pub struct Texture {
     id: u32,
 }
@@ -1,10 +1,15 @@
-use cgmath::{vec2, Vector2};
-
+pub mod difficulty;
 pub mod display_level;
 pub mod generate_level;
+pub mod navigation;
+pub mod raycast;
 pub mod room_template;
+pub mod traversal;
 pub mod update_level;
 
+use crate::parse::parse_value;
+use generate_level::ceiling_gap;
+
 //The distance of the level from the camera
 pub const LEVEL_Z: f32 = -8.0;
 //Size of a "chunk" of tiles in the level
@@ -15,8 +20,10 @@ pub const LEVEL_Z: f32 = -8.0;
 const CHUNK_SIZE: u32 = 16;
 //Size of a room
 pub const ROOM_SIZE: u32 = 16;
+//Tile that fills the gaps in the ceiling of a locked floor
+const LOCKED_GAP_TILE: Tile = Tile::BrickTile2;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
 #[allow(clippy::enum_variant_names)]
 pub enum Tile {
@@ -45,7 +52,7 @@ pub enum BackgroundTile {
     BigWindowBottom,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum InteractiveTile {
     SmallGold,
     Gold,
@@ -54,50 +61,92 @@ pub enum InteractiveTile {
     Arrows,
 }
 
-#[derive(Copy, Clone)]
+impl InteractiveTile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InteractiveTile::SmallGold => "small_gold",
+            InteractiveTile::Gold => "gold",
+            InteractiveTile::Heal => "heal",
+            InteractiveTile::HealthBoost => "health_boost",
+            InteractiveTile::Arrows => "arrows",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "small_gold" => Some(InteractiveTile::SmallGold),
+            "gold" => Some(InteractiveTile::Gold),
+            "heal" => Some(InteractiveTile::Heal),
+            "health_boost" => Some(InteractiveTile::HealthBoost),
+            "arrows" => Some(InteractiveTile::Arrows),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
 pub struct InteractiveTileSprite {
     tile_type: InteractiveTile,
     tile_x: f32,
     tile_y: f32,
 }
 
+impl InteractiveTileSprite {
+    //Interactive tiles are saved as text of the form: type x y
+    pub fn to_text(self) -> String {
+        format!("{} {} {}", self.tile_type.name(), self.tile_x, self.tile_y)
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let split: Vec<&str> = text.split_whitespace().collect();
+        if split.len() != 3 {
+            return Err("expected: type x y".to_string());
+        }
+
+        let tile_type =
+            InteractiveTile::from_name(split[0]).ok_or(format!("invalid tile: {}", split[0]))?;
+
+        Ok(Self {
+            tile_type,
+            tile_x: parse_value(split[1], "x")?,
+            tile_y: parse_value(split[2], "y")?,
+        })
+    }
+}
+
 pub fn transparent(tile: Tile) -> bool {
     matches!(tile, Tile::Air | Tile::Ladder | Tile::Lava | Tile::Spikes)
 }
 
+//Returns the floor that a height in the level is on, the bottom floor is 0
+pub fn floor_at(y: f32) -> u32 {
+    y.max(0.0) as u32 / (ROOM_SIZE + 1)
+}
+
 pub struct Level {
     tiles: Vec<Tile>,
     background_tiles: Vec<BackgroundTile>,
     interactive_tiles: Vec<InteractiveTileSprite>,
+    //Floors that can not be left until the boss on them is defeated
+    locked_floors: Vec<u32>,
+    //Links between the tiles enemies can walk to, shared by every enemy
+    navigation: navigation::NavGraph,
     width: u32,
     height: u32,
-
-    level_chunks: Vec<u32>,
-    level_chunk_vertex_buffers: Vec<u32>,
-    level_chunk_texture_coordinates: Vec<u32>,
-    level_chunk_animation: Vec<u32>,
-    level_chunk_vertex_count: Vec<u32>,
-    level_chunk_position: Vec<Vector2<f32>>,
 }
 
 impl Level {
     pub fn new(w: u32, h: u32) -> Self {
-        let sz = ((w / CHUNK_SIZE) as usize + 1) * ((h / CHUNK_SIZE) as usize + 1);
         //Creates a level filled with bricks that can
         //be used to generate a more complex level
         Self {
             tiles: vec![Tile::Brick; w as usize * h as usize],
             interactive_tiles: Vec::new(),
+            locked_floors: Vec::new(),
+            navigation: navigation::NavGraph::new(h),
             background_tiles: vec![BackgroundTile::Wall; w as usize * h as usize],
             width: w,
             height: h,
-
-            level_chunks: vec![0; sz],
-            level_chunk_vertex_buffers: vec![0; sz],
-            level_chunk_texture_coordinates: vec![0; sz],
-            level_chunk_animation: vec![0; sz],
-            level_chunk_vertex_count: vec![0; sz],
-            level_chunk_position: vec![vec2(0.0, 0.0); sz],
         }
     }
 
@@ -133,6 +182,7 @@ impl Level {
         }
 
         self.tiles[((self.width * y) + x) as usize] = tile;
+        self.navigation.invalidate(y);
     }
 
     pub fn set_background_tile(&mut self, x: u32, y: u32, tile: BackgroundTile) {
@@ -144,29 +194,58 @@ impl Level {
         self.background_tiles[((self.width * y) + x) as usize] = tile;
     }
 
+    pub fn w(&self) -> u32 {
+        self.width
+    }
+
     pub fn h(&self) -> u32 {
         self.height
     }
-}
 
-impl Drop for Level {
-    fn drop(&mut self) {
-        //When the level gets dropped, make sure to delete all of the
-        //vertex array objects and all of the vertex buffers
-        unsafe {
-            gl::DeleteVertexArrays(self.level_chunks.len() as i32, self.level_chunks.as_ptr());
-            gl::DeleteBuffers(
-                self.level_chunk_vertex_buffers.len() as i32,
-                self.level_chunk_vertex_buffers.as_ptr(),
-            );
-            gl::DeleteBuffers(
-                self.level_chunk_texture_coordinates.len() as i32,
-                self.level_chunk_texture_coordinates.as_ptr(),
-            );
-            gl::DeleteBuffers(
-                self.level_chunk_animation.len() as i32,
-                self.level_chunk_animation.as_ptr(),
-            );
+    pub fn interactive_tiles(&self) -> &[InteractiveTileSprite] {
+        &self.interactive_tiles
+    }
+
+    //Replaces the interactive tiles, used to restore the
+    //pickups that were left in a saved run
+    pub fn set_interactive_tiles(&mut self, tiles: Vec<InteractiveTileSprite>) {
+        self.interactive_tiles = tiles;
+    }
+
+    pub fn locked_floors(&self) -> &[u32] {
+        &self.locked_floors
+    }
+
+    //Fills the gaps in the ceilings of every room on a floor, when a gap is
+    //opened the ladders that reach the ceiling are continued through it since
+    //climbing off the top of a ladder is not enough to get into the gap
+    fn set_ceiling_gaps(&mut self, floor: u32, tile: Tile) {
+        let y = (floor + 1) * (ROOM_SIZE + 1);
+        for room_x in 0..(self.width / (ROOM_SIZE + 1)) {
+            for x in ceiling_gap() {
+                let x = x + room_x * (ROOM_SIZE + 1);
+                if tile == Tile::Air && self.get_tile(x, y - 1) == Tile::Ladder {
+                    self.set_tile(x, y, Tile::Ladder);
+                } else {
+                    self.set_tile(x, y, tile);
+                }
+            }
+        }
+    }
+
+    //Closes the ceiling of a floor so that the player can not leave it
+    pub fn lock_floor(&mut self, floor: u32) {
+        if !self.locked_floors.contains(&floor) {
+            self.set_ceiling_gaps(floor, LOCKED_GAP_TILE);
+            self.locked_floors.push(floor);
+        }
+    }
+
+    //Opens the ceiling of a locked floor again
+    pub fn unlock_floor(&mut self, floor: u32) {
+        if self.locked_floors.contains(&floor) {
+            self.set_ceiling_gaps(floor, Tile::Air);
+            self.locked_floors.retain(|locked| *locked != floor);
         }
     }
 }
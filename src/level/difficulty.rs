@@ -0,0 +1,442 @@
+use super::InteractiveTile;
+use crate::{parse::parse_value, sprite::enemy::EnemyType};
+use rand::{rngs::StdRng, Rng};
+use std::{fs::File, io::Read, sync::OnceLock};
+
+/*
+ * A difficulty profile is made up of tables of weights that decide what
+ * is spawned at the spawn points of the rooms. Each table starts with
+ * the name of the table and its columns followed by rows of weights:
+ *
+ * //Comments start with two slashes
+ * table enemies eyeball chicken slime
+ * 0 40 0 60
+ * 8 20 30 50
+ *
+ * The first number of a row is the floor and the rest are the weights
+ * of the columns on that floor. Weights on the floors between two rows
+ * are interpolated, below the first row the first row is used and above
+ * the last row the last row is used. Every row of a table has to add up
+ * to the same total and a column is picked with a chance of its weight
+ * divided by that total.
+ * */
+
+pub const DIFFICULTY_DIR: &str = "assets/difficulty";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+pub const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+static PROFILES: [OnceLock<Result<DifficultyProfile, String>>; 3] = [const { OnceLock::new() }; 3];
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DIFFICULTIES
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn path(&self) -> String {
+        format!("{DIFFICULTY_DIR}/{}.txt", self.name())
+    }
+
+    //Returns the profile of the difficulty, it is loaded
+    //from `path` the first time this is called
+    pub fn profile(self) -> Result<&'static DifficultyProfile, String> {
+        PROFILES[self as usize]
+            .get_or_init(|| DifficultyProfile::load(&self.path()))
+            .as_ref()
+            .map_err(|msg| msg.clone())
+    }
+}
+
+//Weights of a list of choices that change from floor to floor
+#[derive(Clone, PartialEq, Debug)]
+pub struct WeightTable<T> {
+    columns: Vec<T>,
+    //Pairs of (floor, weight of each column), sorted by floor
+    rows: Vec<(u32, Vec<u32>)>,
+    //Every row adds up to this
+    total: u32,
+}
+
+impl<T: Copy> WeightTable<T> {
+    //Returns the weight of each column on a floor, the weights are
+    //interpolated between the rows around the floor and always add
+    //up to the total of the table
+    pub fn weights(&self, floor: u32) -> Vec<u32> {
+        let next = self
+            .rows
+            .iter()
+            .position(|(row_floor, _)| *row_floor > floor);
+        let (low, high) = match next {
+            Some(0) => return self.rows[0].1.clone(),
+            Some(i) => (&self.rows[i - 1], &self.rows[i]),
+            None => return self.rows[self.rows.len() - 1].1.clone(),
+        };
+
+        let t = (floor - low.0) as f32 / (high.0 - low.0) as f32;
+        let exact: Vec<f32> = low
+            .1
+            .iter()
+            .zip(&high.1)
+            .map(|(a, b)| *a as f32 + (*b as f32 - *a as f32) * t)
+            .collect();
+        //Round every weight down and then give what is left over to the
+        //weights that were rounded down the most, ties go to the first column
+        let mut weights: Vec<u32> = exact.iter().map(|weight| weight.floor() as u32).collect();
+        let left_over = self.total.saturating_sub(weights.iter().sum()) as usize;
+        let mut order: Vec<usize> = (0..exact.len()).collect();
+        order.sort_by(|a, b| exact[*b].fract().total_cmp(&exact[*a].fract()));
+        for i in order.into_iter().cycle().take(left_over) {
+            weights[i] += 1;
+        }
+        weights
+    }
+
+    //Picks a column on a floor, a column is picked with a chance of
+    //its weight divided by the total
+    pub fn sample(&self, floor: u32, rng: &mut StdRng) -> T {
+        let roll = rng.gen::<u32>() % self.total;
+        let mut cumulative = 0;
+        for (column, weight) in self.columns.iter().zip(self.weights(floor)) {
+            cumulative += weight;
+            if cumulative > roll {
+                return *column;
+            }
+        }
+
+        //The weights always add up to the total so this is never reached
+        self.columns[self.columns.len() - 1]
+    }
+}
+
+//A table as it is written in the file, before its columns are checked
+struct TableText {
+    name: String,
+    line: usize,
+    columns: Vec<String>,
+    rows: Vec<(u32, Vec<u32>)>,
+}
+
+impl TableText {
+    //Checks the table and converts the names of the columns
+    //with `parse_column`, returns every problem that was found
+    fn build<T: Copy>(
+        &self,
+        parse_column: fn(&str) -> Option<T>,
+    ) -> Result<WeightTable<T>, Vec<String>> {
+        let mut problems = vec![];
+        let mut columns = vec![];
+        for (i, name) in self.columns.iter().enumerate() {
+            if self.columns[..i].contains(name) {
+                problems.push(format!("column {name} is listed twice"));
+            }
+            match parse_column(name) {
+                Some(column) => columns.push(column),
+                None => problems.push(format!("unknown column: {name}")),
+            }
+        }
+
+        if columns.is_empty() && problems.is_empty() {
+            problems.push("table has no columns".to_string());
+        }
+        if self.rows.is_empty() {
+            problems.push("table has no rows".to_string());
+        }
+        if self.rows.windows(2).any(|rows| rows[0].0 >= rows[1].0) {
+            problems.push("rows must be sorted by floor".to_string());
+        }
+        if self
+            .rows
+            .iter()
+            .any(|(_, weights)| weights.len() != self.columns.len())
+        {
+            problems.push(format!("rows must have {} weights", self.columns.len()));
+        }
+
+        let totals: Vec<u32> = self
+            .rows
+            .iter()
+            .map(|(_, weights)| weights.iter().sum())
+            .collect();
+        let total = totals.first().copied().unwrap_or(0);
+        if totals.iter().any(|row_total| *row_total != total) {
+            problems.push("every row must add up to the same total".to_string());
+        } else if !self.rows.is_empty() && total == 0 {
+            problems.push("weights must add up to more than 0".to_string());
+        }
+
+        if !problems.is_empty() {
+            return Err(problems
+                .into_iter()
+                .map(|problem| format!("line {}: table {}: {problem}", self.line, self.name))
+                .collect());
+        }
+
+        Ok(WeightTable {
+            columns,
+            rows: self.rows.clone(),
+            total,
+        })
+    }
+}
+
+fn parse_enemy_chance(name: &str) -> Option<bool> {
+    match name {
+        "enemy" => Some(true),
+        "none" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_loot(name: &str) -> Option<Option<InteractiveTile>> {
+    match name {
+        "none" => Some(None),
+        _ => InteractiveTile::from_name(name).map(Some),
+    }
+}
+
+//Tables that decide what is spawned in a tower
+#[derive(Clone, PartialEq, Debug)]
+pub struct DifficultyProfile {
+    //If a spawn point that may have an enemy gets one
+    pub enemy_chance: WeightTable<bool>,
+    //Enemy that is spawned at an enemy spawn point
+    pub enemies: WeightTable<EnemyType>,
+    //Loot at spawn points that may have treasure
+    pub treasure: WeightTable<Option<InteractiveTile>>,
+    //Loot at pickup spawn points
+    pub pickups: WeightTable<Option<InteractiveTile>>,
+}
+
+const TABLE_NAMES: [&str; 4] = ["enemy_chance", "enemies", "treasure", "pickups"];
+
+impl DifficultyProfile {
+    //Reads a profile from text, all problems that are found
+    //are returned in the error, one on each line
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut problems = vec![];
+        let mut tables: Vec<TableText> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let split: Vec<&str> = line.split_whitespace().collect();
+            let res = match (&split[..], tables.last_mut()) {
+                (["table", name, columns @ ..], _) => {
+                    if !TABLE_NAMES.contains(name) {
+                        Err(format!("unknown table: {name}"))
+                    } else if tables.iter().any(|table| table.name == *name) {
+                        Err(format!("table {name} is listed twice"))
+                    } else {
+                        tables.push(TableText {
+                            name: name.to_string(),
+                            line: i + 1,
+                            columns: columns.iter().map(|column| column.to_string()).collect(),
+                            rows: vec![],
+                        });
+                        Ok(())
+                    }
+                }
+                ([floor, weights @ ..], Some(table)) => {
+                    parse_value(floor, "floor").and_then(|floor| {
+                        let weights = weights
+                            .iter()
+                            .map(|weight| parse_value(weight, "weight"))
+                            .collect::<Result<Vec<u32>, String>>()?;
+                        table.rows.push((floor, weights));
+                        Ok(())
+                    })
+                }
+                _ => Err("expected: table name columns".to_string()),
+            };
+            if let Err(msg) = res {
+                problems.push(format!("line {}: {msg}", i + 1));
+            }
+        }
+
+        for name in TABLE_NAMES {
+            if !tables.iter().any(|table| table.name == name) {
+                problems.push(format!("missing table: {name}"));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+
+        let table = |name: &str| {
+            tables
+                .iter()
+                .find(|table| table.name == name)
+                .expect("every table was checked")
+        };
+        let enemy_chance = table("enemy_chance").build(parse_enemy_chance);
+        let enemies = table("enemies").build(EnemyType::from_name);
+        let treasure = table("treasure").build(parse_loot);
+        let pickups = table("pickups").build(parse_loot);
+        match (enemy_chance, enemies, treasure, pickups) {
+            (Ok(enemy_chance), Ok(enemies), Ok(treasure), Ok(pickups)) => Ok(Self {
+                enemy_chance,
+                enemies,
+                treasure,
+                pickups,
+            }),
+            (enemy_chance, enemies, treasure, pickups) => {
+                let problems: Vec<String> = [
+                    enemy_chance.err(),
+                    enemies.err(),
+                    treasure.err(),
+                    pickups.err(),
+                ]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .collect();
+                Err(problems.join("\n"))
+            }
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut buf = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut buf))
+            .map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&buf).map_err(|msg| format!("{path}:\n{msg}"))
+    }
+}
+
+//Loads the profile of every difficulty so that problems
+//in the files are found when the game starts
+pub fn load_profiles() -> Result<(), String> {
+    for difficulty in DIFFICULTIES {
+        difficulty.profile()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn table(text: &str) -> WeightTable<bool> {
+        let text = format!("table enemy_chance enemy none\n{text}table enemies slime\n0 1\ntable treasure none\n0 1\ntable pickups none\n0 1\n");
+        DifficultyProfile::from_text(&text)
+            .expect("failed to parse profile")
+            .enemy_chance
+    }
+
+    #[test]
+    fn test_interpolate_weights() {
+        let chance = table("0 0 100\n10 100 0\n20 100 0\n");
+        assert_eq!(chance.weights(0), vec![0, 100]);
+        assert_eq!(chance.weights(3), vec![30, 70]);
+        assert_eq!(chance.weights(5), vec![50, 50]);
+        assert_eq!(chance.weights(15), vec![100, 0]);
+        assert_eq!(chance.weights(50), vec![100, 0]);
+
+        //Weights that are not whole numbers are rounded so that the total stays the same
+        let chance = table("0 1 2\n3 3 0\n");
+        assert_eq!(chance.weights(1), vec![2, 1]);
+        assert_eq!(chance.weights(2), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_weight_tables_add_up() {
+        for difficulty in DIFFICULTIES {
+            let profile = difficulty.profile().expect("failed to load difficulty");
+            for floor in 0..100 {
+                let sum = |weights: Vec<u32>| weights.iter().sum::<u32>();
+                let enemy_chance = &profile.enemy_chance;
+                assert_eq!(sum(enemy_chance.weights(floor)), enemy_chance.total);
+                assert_eq!(sum(profile.enemies.weights(floor)), profile.enemies.total);
+                assert_eq!(sum(profile.treasure.weights(floor)), profile.treasure.total);
+                assert_eq!(sum(profile.pickups.weights(floor)), profile.pickups.total);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normal_difficulty_weights() {
+        //The normal difficulty has the same chances as before there were difficulties
+        let profile = Difficulty::Normal
+            .profile()
+            .expect("failed to load difficulty");
+        let names: Vec<&str> = profile.enemies.columns.iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["eyeball", "chicken", "skeleton", "demon", "slime"]);
+        let floors = [
+            (3, [40, 0, 0, 0, 60]),
+            (4, [30, 20, 0, 0, 50]),
+            (11, [20, 30, 30, 5, 15]),
+            (12, [15, 15, 15, 15, 40]),
+            (27, [10, 10, 20, 20, 40]),
+            (35, [5, 5, 25, 25, 40]),
+            (90, [5, 5, 30, 30, 30]),
+        ];
+        for (floor, weights) in floors {
+            assert_eq!(profile.enemies.weights(floor), weights);
+        }
+        assert_eq!(profile.enemy_chance.weights(20), [60, 40]);
+        assert_eq!(profile.treasure.total, 100);
+        assert_eq!(profile.pickups.total, 16);
+    }
+
+    #[test]
+    fn test_sampled_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        const SAMPLES: usize = 100_000;
+        for difficulty in DIFFICULTIES {
+            let enemies = &difficulty
+                .profile()
+                .expect("failed to load difficulty")
+                .enemies;
+            //Floor 17 is between two rows in every profile
+            let weights = enemies.weights(17);
+            let mut counts = vec![0; weights.len()];
+            for _ in 0..SAMPLES {
+                let enemy = enemies.sample(17, &mut rng);
+                let column = enemies.columns.iter().position(|e| *e == enemy);
+                counts[column.expect("sampled enemy is not in the table")] += 1;
+            }
+
+            for (count, weight) in counts.iter().zip(weights) {
+                let expected = weight as f32 / enemies.total as f32;
+                let sampled = *count as f32 / SAMPLES as f32;
+                assert!((expected - sampled).abs() < 0.01, "{expected} {sampled}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        let msg = DifficultyProfile::from_text("table enemies slime\n0 1\n")
+            .expect_err("profile should be invalid");
+        assert!(msg.contains("missing table: treasure"));
+
+        let text = "table enemy_chance enemy none\n0 60 40\n5 50 40\ntable enemies slime dragon\n0 1 1\ntable treasure gold\n4 1\n2 1\ntable pickups none heal\n0 1\n";
+        let msg = DifficultyProfile::from_text(text).expect_err("profile should be invalid");
+        assert!(msg.contains("line 1: table enemy_chance: every row must add up to the same total"));
+        assert!(msg.contains("line 4: table enemies: unknown column: dragon"));
+        assert!(msg.contains("line 6: table treasure: rows must be sorted by floor"));
+        assert!(msg.contains("line 9: table pickups: rows must have 2 weights"));
+        assert!(DifficultyProfile::from_text("0 1 2\n").is_err());
+        assert!(DifficultyProfile::from_text("table monsters slime\n").is_err());
+    }
+}
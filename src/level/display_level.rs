@@ -300,24 +300,138 @@ impl Level {
         vertices
     }
 
-    pub fn build_chunk(&mut self, chunk_x: u32, chunk_y: u32) {
+    //Display interactive tiles
+    pub fn display_interactive_tiles(
+        &self,
+        cube_vao: &VertexArrayObject,
+        shader_program: &ShaderProgram,
+        player_position: &Vector2<f32>,
+        timer: f32,
+    ) {
+        for tile in &self.interactive_tiles {
+            if (tile.tile_y - player_position.y).abs() > SPRITE_RENDER_DISTANCE {
+                continue;
+            }
+
+            match tile.tile_type {
+                InteractiveTile::Gold => {
+                    shader_program.uniform_vec2f("uTexOffset", 0.0, 2.0 / 8.0);
+
+                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
+                        tile.tile_x - 0.2,
+                        tile.tile_y - 0.4,
+                        0.0,
+                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+
+                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
+                        tile.tile_x + 0.2,
+                        tile.tile_y - 0.4,
+                        0.0,
+                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+
+                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
+                        tile.tile_x,
+                        tile.tile_y - 0.1,
+                        0.0,
+                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+                }
+                InteractiveTile::SmallGold => {
+                    shader_program.uniform_vec2f("uTexOffset", 0.0, 2.0 / 8.0);
+
+                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
+                        tile.tile_x,
+                        tile.tile_y - 0.4,
+                        0.0,
+                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.2);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+                }
+                InteractiveTile::Heal => {
+                    shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 2.0 / 8.0);
+
+                    let transform_matrix =
+                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
+                            * Matrix4::from_angle_y(Rad(timer * 2.0))
+                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+                }
+                InteractiveTile::HealthBoost => {
+                    shader_program.uniform_vec2f("uTexOffset", 2.0 / 8.0, 2.0 / 8.0);
+
+                    let transform_matrix =
+                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
+                            * Matrix4::from_angle_y(Rad(timer * 2.0))
+                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+                }
+                InteractiveTile::Arrows => {
+                    shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 3.0 / 8.0);
+
+                    let transform_matrix =
+                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
+                            * Matrix4::from_angle_y(Rad(timer * 2.0))
+                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
+                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+                    cube_vao.draw_arrays();
+                }
+            }
+        }
+    }
+}
+
+//Holds the OpenGL vertex arrays and buffers used to draw the tiles of a
+//level, this is kept separate from the level so that the level can be
+//generated and simulated without an OpenGL context
+pub struct LevelRenderer {
+    chunks_wide: u32,
+    vertex_arrays: Vec<u32>,
+    chunk_vertex_buffers: Vec<u32>,
+    chunk_texture_coordinates: Vec<u32>,
+    chunk_animation: Vec<u32>,
+    chunk_vertex_count: Vec<u32>,
+    chunk_position: Vec<Vector2<f32>>,
+}
+
+impl LevelRenderer {
+    //Creates a renderer that has no chunks to draw
+    pub fn new() -> Self {
+        Self {
+            chunks_wide: 0,
+            vertex_arrays: vec![],
+            chunk_vertex_buffers: vec![],
+            chunk_texture_coordinates: vec![],
+            chunk_animation: vec![],
+            chunk_vertex_count: vec![],
+            chunk_position: vec![],
+        }
+    }
+
+    fn build_chunk(&mut self, level: &Level, chunk_x: u32, chunk_y: u32) {
         let chunk_pos = vec2(
             chunk_x as f32 * CHUNK_SIZE as f32,
             chunk_y as f32 * CHUNK_SIZE as f32,
         );
-        let index = (chunk_x + chunk_y * (self.width / CHUNK_SIZE + 1)) as usize;
-        self.level_chunk_position[index] = chunk_pos;
+        let index = (chunk_x + chunk_y * self.chunks_wide) as usize;
+        self.chunk_position[index] = chunk_pos;
         //Vertices is a vector of f32 values that represent the vertices of
         //the chunk of tiles
-        let vertices = self.get_chunk_vertices(chunk_x, chunk_y);
+        let vertices = level.get_chunk_vertices(chunk_x, chunk_y);
         //Number of vertices is equal to vertices.len() / VERTEX_LEN,
         //this is stored so that we know how many vertices to draw onto the
         //screen when we need to draw the chunk
-        self.level_chunk_vertex_count[index] = (vertices.len() / VERTEX_LEN) as u32;
+        self.chunk_vertex_count[index] = (vertices.len() / VERTEX_LEN) as u32;
         unsafe {
-            gl::BindVertexArray(self.level_chunks[index]);
+            gl::BindVertexArray(self.vertex_arrays[index]);
 
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.level_chunk_vertex_buffers[index]);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.chunk_vertex_buffers[index]);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (vertices.len() * size_of::<f32>()) as isize,
@@ -334,10 +448,7 @@ impl Level {
             );
             gl::EnableVertexAttribArray(0);
 
-            gl::BindBuffer(
-                gl::ARRAY_BUFFER,
-                self.level_chunk_texture_coordinates[index],
-            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.chunk_texture_coordinates[index]);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (vertices.len() * size_of::<f32>()) as isize,
@@ -354,10 +465,7 @@ impl Level {
             );
             gl::EnableVertexAttribArray(1);
 
-            gl::BindBuffer(
-                gl::ARRAY_BUFFER,
-                self.level_chunk_texture_coordinates[index],
-            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.chunk_texture_coordinates[index]);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (vertices.len() * size_of::<f32>()) as isize,
@@ -376,134 +484,92 @@ impl Level {
         }
     }
 
-    //Builds the chunk meshes for each chunk of tiles,
+    //Builds the chunk meshes for each chunk of tiles in the level,
     //this is done to reduce OpenGL calls and reduce CPU usage
-    pub fn build_chunks(&mut self) {
+    pub fn build(level: &Level) -> Self {
+        let chunks_wide = level.width / CHUNK_SIZE + 1;
+        let chunks_tall = level.height / CHUNK_SIZE + 1;
+        let sz = (chunks_wide * chunks_tall) as usize;
+        let mut renderer = Self {
+            chunks_wide,
+            vertex_arrays: vec![0; sz],
+            chunk_vertex_buffers: vec![0; sz],
+            chunk_texture_coordinates: vec![0; sz],
+            chunk_animation: vec![0; sz],
+            chunk_vertex_count: vec![0; sz],
+            chunk_position: vec![vec2(0.0, 0.0); sz],
+        };
+
         //Generate vertex arrays and vertex buffers
         unsafe {
             gl::GenVertexArrays(
-                self.level_chunks.len() as i32,
-                self.level_chunks.as_mut_ptr(),
+                renderer.vertex_arrays.len() as i32,
+                renderer.vertex_arrays.as_mut_ptr(),
             );
             gl::GenBuffers(
-                self.level_chunk_vertex_buffers.len() as i32,
-                self.level_chunk_vertex_buffers.as_mut_ptr(),
+                renderer.chunk_vertex_buffers.len() as i32,
+                renderer.chunk_vertex_buffers.as_mut_ptr(),
             );
             gl::GenBuffers(
-                self.level_chunk_texture_coordinates.len() as i32,
-                self.level_chunk_texture_coordinates.as_mut_ptr(),
+                renderer.chunk_texture_coordinates.len() as i32,
+                renderer.chunk_texture_coordinates.as_mut_ptr(),
             );
             gl::GenBuffers(
-                self.level_chunk_animation.len() as i32,
-                self.level_chunk_animation.as_mut_ptr(),
+                renderer.chunk_animation.len() as i32,
+                renderer.chunk_animation.as_mut_ptr(),
             );
         }
 
-        for chunk_x in 0..(self.width / CHUNK_SIZE + 1) {
-            for chunk_y in 0..(self.height / CHUNK_SIZE + 1) {
-                self.build_chunk(chunk_x, chunk_y);
+        for chunk_x in 0..chunks_wide {
+            for chunk_y in 0..chunks_tall {
+                renderer.build_chunk(level, chunk_x, chunk_y);
             }
         }
+
+        renderer
     }
 
     //Display the level
     pub fn display(&self, player_position: &Vector2<f32>) {
-        for i in 0..self.level_chunks.len() {
-            if (player_position.y - self.level_chunk_position[i].y).abs() > CHUNK_SIZE as f32 * 2.0
+        for i in 0..self.vertex_arrays.len() {
+            if (player_position.y - self.chunk_position[i].y).abs() > CHUNK_SIZE as f32 * 2.0
+                || (player_position.x - self.chunk_position[i].x).abs() > CHUNK_SIZE as f32 * 2.0
             {
                 continue;
             }
 
             unsafe {
-                gl::BindVertexArray(self.level_chunks[i]);
-                gl::DrawArrays(gl::TRIANGLES, 0, self.level_chunk_vertex_count[i] as i32);
+                gl::BindVertexArray(self.vertex_arrays[i]);
+                gl::DrawArrays(gl::TRIANGLES, 0, self.chunk_vertex_count[i] as i32);
             }
         }
     }
+}
 
-    //Display interactive tiles
-    pub fn display_interactive_tiles(
-        &self,
-        cube_vao: &VertexArrayObject,
-        shader_program: &ShaderProgram,
-        player_position: &Vector2<f32>,
-        timer: f32,
-    ) {
-        for tile in &self.interactive_tiles {
-            if (tile.tile_y - player_position.y).abs() > SPRITE_RENDER_DISTANCE {
-                continue;
-            }
-
-            match tile.tile_type {
-                InteractiveTile::Gold => {
-                    shader_program.uniform_vec2f("uTexOffset", 0.0, 2.0 / 8.0);
-
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        tile.tile_x - 0.2,
-                        tile.tile_y - 0.4,
-                        0.0,
-                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        tile.tile_x + 0.2,
-                        tile.tile_y - 0.4,
-                        0.0,
-                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        tile.tile_x,
-                        tile.tile_y - 0.1,
-                        0.0,
-                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.3);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-                }
-                InteractiveTile::SmallGold => {
-                    shader_program.uniform_vec2f("uTexOffset", 0.0, 2.0 / 8.0);
-
-                    let transform_matrix = Matrix4::from_translation(cgmath::vec3(
-                        tile.tile_x,
-                        tile.tile_y - 0.4,
-                        0.0,
-                    )) * Matrix4::from_nonuniform_scale(0.2, 0.15, 0.2);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-                }
-                InteractiveTile::Heal => {
-                    shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 2.0 / 8.0);
-
-                    let transform_matrix =
-                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
-                            * Matrix4::from_angle_y(Rad(timer * 2.0))
-                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-                }
-                InteractiveTile::HealthBoost => {
-                    shader_program.uniform_vec2f("uTexOffset", 2.0 / 8.0, 2.0 / 8.0);
-
-                    let transform_matrix =
-                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
-                            * Matrix4::from_angle_y(Rad(timer * 2.0))
-                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-                }
-                InteractiveTile::Arrows => {
-                    shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 3.0 / 8.0);
+impl Drop for LevelRenderer {
+    fn drop(&mut self) {
+        //If the chunks were never built then there are
+        //no OpenGL objects to delete
+        if self.vertex_arrays.is_empty() {
+            return;
+        }
 
-                    let transform_matrix =
-                        Matrix4::from_translation(cgmath::vec3(tile.tile_x, tile.tile_y, 0.0))
-                            * Matrix4::from_angle_y(Rad(timer * 2.0))
-                            * Matrix4::from_nonuniform_scale(0.5 * 0.6, 0.5 * 0.6, 0.0);
-                    shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-                    cube_vao.draw_arrays();
-                }
-            }
+        //When the renderer gets dropped, make sure to delete all of the
+        //vertex array objects and all of the vertex buffers
+        unsafe {
+            gl::DeleteVertexArrays(self.vertex_arrays.len() as i32, self.vertex_arrays.as_ptr());
+            gl::DeleteBuffers(
+                self.chunk_vertex_buffers.len() as i32,
+                self.chunk_vertex_buffers.as_ptr(),
+            );
+            gl::DeleteBuffers(
+                self.chunk_texture_coordinates.len() as i32,
+                self.chunk_texture_coordinates.as_ptr(),
+            );
+            gl::DeleteBuffers(
+                self.chunk_animation.len() as i32,
+                self.chunk_animation.as_ptr(),
+            );
         }
     }
 }
@@ -1,82 +1,116 @@
-use crate::sprite::enemy::{Enemy, EnemyType};
+use crate::sprite::enemy::{definition::Behaviour, Enemy, EnemyType};
 
 use super::{
+    difficulty::{Difficulty, DifficultyProfile},
     room_template::{RoomTemplate, Spawn, SpawnType},
+    traversal::room_passable,
     BackgroundTile, InteractiveTile, InteractiveTileSprite, Level, Tile, ROOM_SIZE,
 };
-use rand::{rngs::ThreadRng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, ops::Range};
 
-fn generate_enemy_type(rand_value: u32, weights: &[(u32, EnemyType)]) -> EnemyType {
-    let mut total = 0;
-    for (probability, enemy_type) in weights {
-        total += probability;
-        if total > rand_value {
-            return *enemy_type;
+//Returns the x coordinates of the gap that is cut into the
+//ceiling of each room so that the player can reach the next floor
+pub fn ceiling_gap() -> Range<u32> {
+    (ROOM_SIZE / 2 - 1)..(ROOM_SIZE / 2 + 3)
+}
+
+//A boss floor comes after every BOSS_FLOOR_INTERVAL floors
+pub const BOSS_FLOOR_INTERVAL: u32 = 16;
+
+//Returns true if a floor of a tower with `floors` floors has a boss room,
+//the top floor of the tower always has one
+pub fn boss_floor(floor: u32, floors: u32) -> bool {
+    floor > 0 && (floor == floors - 1 || (floor + 1).is_multiple_of(BOSS_FLOOR_INTERVAL))
+}
+
+//Most rooms that can be placed next to each other on a floor
+pub const MAX_ROOMS_PER_FLOOR: u32 = 8;
+//Most floors a tower can have
+pub const MAX_FLOORS: u32 = 1000;
+
+//Number of floors in a tower and the number of rooms on each floor
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TowerSize {
+    pub floors: u32,
+    pub rooms_per_floor: u32,
+}
+
+impl TowerSize {
+    pub const PRACTICE: Self = Self {
+        floors: 8,
+        rooms_per_floor: 1,
+    };
+    pub const STANDARD: Self = Self {
+        floors: 48,
+        rooms_per_floor: 1,
+    };
+    pub const MARATHON: Self = Self {
+        floors: 96,
+        rooms_per_floor: 1,
+    };
+
+    pub fn new(floors: u32, rooms_per_floor: u32) -> Result<Self, String> {
+        if !(1..=MAX_FLOORS).contains(&floors) {
+            return Err(format!("a tower must have 1 to {MAX_FLOORS} floors"));
+        }
+
+        if !(1..=MAX_ROOMS_PER_FLOOR).contains(&rooms_per_floor) {
+            return Err(format!(
+                "a floor must have 1 to {MAX_ROOMS_PER_FLOOR} rooms"
+            ));
+        }
+
+        Ok(Self {
+            floors,
+            rooms_per_floor,
+        })
+    }
+
+    //Parses either the name of a size (practice, standard or marathon)
+    //or the size written as <floors>x<rooms per floor>
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "practice" => Ok(Self::PRACTICE),
+            "standard" => Ok(Self::STANDARD),
+            "marathon" => Ok(Self::MARATHON),
+            _ => {
+                let invalid = || format!("invalid tower size: {text}");
+                let (floors, rooms_per_floor) = text.split_once('x').ok_or_else(invalid)?;
+                Self::new(
+                    floors.parse().map_err(|_| invalid())?,
+                    rooms_per_floor.parse().map_err(|_| invalid())?,
+                )
+            }
         }
     }
 
-    //Return a slime as the default enemy
-    EnemyType::Slime
+    pub fn to_text(self) -> String {
+        format!("{}x{}", self.floors, self.rooms_per_floor)
+    }
+
+    //Width of the tower in tiles
+    pub fn width(&self) -> u32 {
+        self.rooms_per_floor * (ROOM_SIZE + 1) + 1
+    }
+
+    //Height of the tower in tiles
+    pub fn height(&self) -> u32 {
+        self.floors * (ROOM_SIZE + 1) + 1
+    }
 }
 
 fn spawn_enemy(
     enemies: &mut Vec<Enemy>,
-    rng: &mut ThreadRng,
+    profile: &DifficultyProfile,
+    rng: &mut StdRng,
     spawn_location: &Spawn,
     room_x: u32,
     room_y: u32,
 ) {
-    let rand_value = rng.gen::<u32>() % 100;
+    let enemy_type = profile.enemies.sample(room_y, rng);
     let flipped = rng.gen::<bool>();
 
-    let enemy_type = if room_y < 4 {
-        let weights = [(40, EnemyType::Eyeball)];
-        generate_enemy_type(rand_value, &weights)
-    } else if room_y < 8 {
-        let weights = [(30, EnemyType::Eyeball), (20, EnemyType::Chicken)];
-        generate_enemy_type(rand_value, &weights)
-    } else if room_y < 12 {
-        let weights = [
-            (20, EnemyType::Eyeball),
-            (30, EnemyType::Chicken),
-            (30, EnemyType::Skeleton),
-            (5, EnemyType::Demon),
-        ];
-        generate_enemy_type(rand_value, &weights)
-    } else if room_y < 20 {
-        let weights = [
-            (15, EnemyType::Eyeball),
-            (15, EnemyType::Chicken),
-            (15, EnemyType::Skeleton),
-            (15, EnemyType::Demon),
-        ];
-        generate_enemy_type(rand_value, &weights)
-    } else if room_y < 28 {
-        let weights = [
-            (10, EnemyType::Eyeball),
-            (10, EnemyType::Chicken),
-            (20, EnemyType::Skeleton),
-            (20, EnemyType::Demon),
-        ];
-        generate_enemy_type(rand_value, &weights)
-    } else if room_y < 36 {
-        let weights = [
-            (5, EnemyType::Eyeball),
-            (5, EnemyType::Chicken),
-            (25, EnemyType::Skeleton),
-            (25, EnemyType::Demon),
-        ];
-        generate_enemy_type(rand_value, &weights)
-    } else {
-        let weights = [
-            (5, EnemyType::Eyeball),
-            (5, EnemyType::Chicken),
-            (30, EnemyType::Skeleton),
-            (30, EnemyType::Demon),
-        ];
-        generate_enemy_type(rand_value, &weights)
-    };
-
     //Spawn enemy
     enemies.push(Enemy::new(
         (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
@@ -86,6 +120,91 @@ fn spawn_enemy(
     ));
 }
 
+//Spawns one of the enemies with the boss behaviour, nothing
+//is spawned if none of the enemies are bosses
+fn spawn_boss(
+    enemies: &mut Vec<Enemy>,
+    rng: &mut StdRng,
+    spawn_location: &Spawn,
+    room_x: u32,
+    room_y: u32,
+) {
+    let bosses: Vec<EnemyType> = EnemyType::all()
+        .into_iter()
+        .filter(|enemy| enemy.def().behaviour == Behaviour::Boss)
+        .collect();
+    if bosses.is_empty() {
+        return;
+    }
+
+    let enemy_type = bosses[rng.gen_range(0..bosses.len())];
+    enemies.push(Enemy::new(
+        (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
+        (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
+        enemy_type,
+        rng.gen::<bool>(),
+    ));
+}
+
+//Picks a random template that can appear on `floor` based on the weights
+//of the templates, rooms tagged "rest" are never placed on two floors in a
+//row and the templates in `excluded` are never picked. Rooms tagged "boss"
+//are only picked for boss rooms and boss rooms only use those templates.
+//Returns None if no template can appear on the floor
+fn choose_template(
+    template_list: &[RoomTemplate],
+    floor: u32,
+    boss: bool,
+    previous: Option<usize>,
+    excluded: &[usize],
+    rng: &mut StdRng,
+) -> Option<usize> {
+    let previous_rest = previous.is_some_and(|i| template_list[i].has_tag("rest"));
+    let weights: Vec<u32> = template_list
+        .iter()
+        .enumerate()
+        .map(|(i, template)| {
+            if (previous_rest && template.has_tag("rest"))
+                || excluded.contains(&i)
+                || template.has_tag("boss") != boss
+            {
+                0
+            } else {
+                template.weight_on_floor(floor)
+            }
+        })
+        .collect();
+
+    let total: u32 = weights.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut rand_value = rng.gen_range(0..total);
+    for (i, weight) in weights.iter().enumerate() {
+        if rand_value < *weight {
+            return Some(i);
+        }
+        rand_value -= weight;
+    }
+
+    None
+}
+
+//What is shared by the rooms of a tower while it is generated
+struct TowerGenerator<'a> {
+    template_list: &'a [RoomTemplate],
+    //Nothing is spawned if the difficulty profile could not be loaded
+    profile: Option<&'a DifficultyProfile>,
+    rng: StdRng,
+    enemies: Vec<Enemy>,
+    //Whether the player can get through each template on the bottom floor
+    //and on the floors above it, this only depends on the tiles of the template
+    passable: HashMap<(usize, bool), bool>,
+    //Number of templates that were rerolled because they could not be passed
+    rerolls: u32,
+}
+
 impl Level {
     fn empty_room(&mut self, room_x: u32, room_y: u32) {
         for x in 0..ROOM_SIZE {
@@ -97,11 +216,20 @@ impl Level {
         }
     }
 
-    fn generate_room_from_template(
+    //Empty room with a ladder up to the gap in the ceiling, this is
+    //used when none of the templates can be placed on a floor
+    fn ladder_room(&mut self, room_x: u32, room_y: u32) {
+        self.empty_room(room_x, room_y);
+        let tile_x = ceiling_gap().start + room_x * (ROOM_SIZE + 1);
+        for y in 0..ROOM_SIZE {
+            self.set_tile(tile_x, y + room_y * (ROOM_SIZE + 1) + 1, Tile::Ladder);
+        }
+    }
+
+    fn generate_room_tiles(
         &mut self,
-        enemies: &mut Vec<Enemy>,
         template: &RoomTemplate,
-        rng: &mut ThreadRng,
+        rng: &mut StdRng,
         room_x: u32,
         room_y: u32,
     ) {
@@ -119,92 +247,545 @@ impl Level {
                 }
             }
         }
+    }
 
+    fn add_loot(
+        &mut self,
+        loot: Option<InteractiveTile>,
+        spawn_location: &Spawn,
+        room_x: u32,
+        room_y: u32,
+    ) {
+        if let Some(tile_type) = loot {
+            self.interactive_tiles.push(InteractiveTileSprite {
+                tile_type,
+                tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
+                tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
+            });
+        }
+    }
+
+    //Spawns the treasure, pickups and enemies of a room, what is
+    //spawned is picked from the tables of the difficulty profile
+    fn generate_room_spawns(
+        &mut self,
+        enemies: &mut Vec<Enemy>,
+        template: &RoomTemplate,
+        profile: &DifficultyProfile,
+        rng: &mut StdRng,
+        room_x: u32,
+        room_y: u32,
+    ) {
         for spawn_location in template.get_spawns() {
             match spawn_location.spawn_type {
                 SpawnType::MaybeTreasure => {
-                    let rand_number = rng.gen::<u32>() % 100;
-
-                    if rand_number < 10 {
-                        self.interactive_tiles.push(InteractiveTileSprite {
-                            tile_type: InteractiveTile::Gold,
-                            tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                            tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                        });
-                    } else if rand_number < 50 {
-                        self.interactive_tiles.push(InteractiveTileSprite {
-                            tile_type: InteractiveTile::SmallGold,
-                            tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                            tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                        });
-                    }
+                    let loot = profile.treasure.sample(room_y, rng);
+                    self.add_loot(loot, spawn_location, room_x, room_y);
                 }
                 SpawnType::Treasure => {
-                    self.interactive_tiles.push(InteractiveTileSprite {
-                        tile_type: InteractiveTile::Gold,
-                        tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                        tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                    });
+                    let loot = Some(InteractiveTile::Gold);
+                    self.add_loot(loot, spawn_location, room_x, room_y);
                 }
                 SpawnType::MaybeEnemy => {
-                    //Spawn enemy
-                    let rand_value = rng.gen::<u32>() % 100;
-                    if rand_value < 60 {
-                        spawn_enemy(enemies, rng, spawn_location, room_x, room_y);
+                    if profile.enemy_chance.sample(room_y, rng) {
+                        spawn_enemy(enemies, profile, rng, spawn_location, room_x, room_y);
                     }
                 }
                 SpawnType::Enemy => {
-                    //Spawn enemy
-                    spawn_enemy(enemies, rng, spawn_location, room_x, room_y);
+                    spawn_enemy(enemies, profile, rng, spawn_location, room_x, room_y);
                 }
                 SpawnType::Pickup => {
-                    let rand_value = rng.gen::<u32>() % 16;
-
-                    if rand_value < 2 {
-                        self.interactive_tiles.push(InteractiveTileSprite {
-                            tile_type: InteractiveTile::HealthBoost,
-                            tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                            tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                        });
-                    } else if rand_value < 5 {
-                        self.interactive_tiles.push(InteractiveTileSprite {
-                            tile_type: InteractiveTile::Heal,
-                            tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                            tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                        });
-                    } else if rand_value < 10 {
-                        self.interactive_tiles.push(InteractiveTileSprite {
-                            tile_type: InteractiveTile::Arrows,
-                            tile_x: (spawn_location.tile_x + 1 + room_x * (ROOM_SIZE + 1)) as f32,
-                            tile_y: (spawn_location.tile_y + 1 + room_y * (ROOM_SIZE + 1)) as f32,
-                        });
+                    let loot = profile.pickups.sample(room_y, rng);
+                    self.add_loot(loot, spawn_location, room_x, room_y);
+                }
+                SpawnType::Boss => {
+                    spawn_boss(enemies, rng, spawn_location, room_x, room_y);
+                }
+            }
+        }
+    }
+
+    //Places a random template in a room that the player can get through,
+    //templates that can not be passed are rerolled and if none of them can be
+    //used then a ladder room is placed instead
+    fn generate_passable_room(
+        &mut self,
+        tower: &mut TowerGenerator,
+        boss: bool,
+        previous: Option<usize>,
+        room_x: u32,
+        room_y: u32,
+    ) -> Option<usize> {
+        let template_list = tower.template_list;
+        let mut excluded = vec![];
+        while let Some(i) = choose_template(
+            template_list,
+            room_y,
+            boss,
+            previous,
+            &excluded,
+            &mut tower.rng,
+        ) {
+            let key = (i, room_y == 0);
+            if tower.passable.get(&key) == Some(&false) {
+                excluded.push(i);
+                tower.rerolls += 1;
+                continue;
+            }
+
+            self.generate_room_tiles(&template_list[i], &mut tower.rng, room_x, room_y);
+            let can_pass = *tower
+                .passable
+                .entry(key)
+                .or_insert_with(|| room_passable(self, room_x, room_y));
+
+            if can_pass {
+                if let Some(profile) = tower.profile {
+                    self.generate_room_spawns(
+                        &mut tower.enemies,
+                        &template_list[i],
+                        profile,
+                        &mut tower.rng,
+                        room_x,
+                        room_y,
+                    );
+                }
+                return Some(i);
+            }
+            excluded.push(i);
+            tower.rerolls += 1;
+        }
+
+        self.ladder_room(room_x, room_y);
+        None
+    }
+
+    //Generates a tower from the room templates, the same seed will always
+    //produce the same tiles, decorations, treasure and enemies. Every room
+    //is checked so that the player can always reach the top of the tower.
+    //The difficulty decides how likely enemies and loot are on each floor.
+    //One room on each boss floor is a boss room and the floor stays
+    //locked until the boss is defeated.
+    pub fn generate_level(
+        template_list: &[RoomTemplate],
+        seed: u64,
+        size: TowerSize,
+        difficulty: Difficulty,
+    ) -> (Self, Vec<Enemy>) {
+        let (level, enemies, _) = Self::generate_tower(template_list, seed, size, difficulty);
+        (level, enemies)
+    }
+
+    //Same as `generate_level` but also returns how many times a
+    //template was rerolled because the player could not pass it
+    fn generate_tower(
+        template_list: &[RoomTemplate],
+        seed: u64,
+        size: TowerSize,
+        difficulty: Difficulty,
+    ) -> (Self, Vec<Enemy>, u32) {
+        let mut level = Self::new(size.width(), size.height());
+        let mut tower = TowerGenerator {
+            template_list,
+            profile: difficulty.profile().ok(),
+            rng: StdRng::seed_from_u64(seed),
+            enemies: vec![],
+            passable: HashMap::new(),
+            rerolls: 0,
+        };
+
+        //The gaps and doorways are cut before the rooms are placed
+        //so that each room can be checked once it is placed
+        for room_y in 0..size.floors {
+            for room_x in 0..size.rooms_per_floor {
+                let offset_x = room_x * (ROOM_SIZE + 1);
+                ceiling_gap().for_each(|x| {
+                    level.set_tile(x + offset_x, (room_y + 1) * (ROOM_SIZE + 1), Tile::Air)
+                });
+
+                //Doorway to the room on the left at the bottom of the room
+                if room_x > 0 {
+                    for y in 1..=2 {
+                        level.set_tile(offset_x, room_y * (ROOM_SIZE + 1) + y, Tile::Air);
                     }
                 }
             }
         }
+
+        let mut previous = vec![None; size.rooms_per_floor as usize];
+        let mut boss_floors = vec![];
+        for room_y in 0..size.floors {
+            //Boss floors are only placed if there is a template for the boss room
+            let boss_room = (boss_floor(room_y, size.floors)
+                && template_list.iter().any(|template| {
+                    template.has_tag("boss") && template.weight_on_floor(room_y) > 0
+                }))
+            .then(|| tower.rng.gen_range(0..size.rooms_per_floor));
+
+            let first_enemy = tower.enemies.len();
+            for room_x in 0..size.rooms_per_floor {
+                previous[room_x as usize] = level.generate_passable_room(
+                    &mut tower,
+                    boss_room == Some(room_x),
+                    previous[room_x as usize],
+                    room_x,
+                    room_y,
+                );
+            }
+            level.set_ceiling_gaps(room_y, Tile::Air);
+
+            if tower.enemies[first_enemy..]
+                .iter()
+                .any(|enemy| enemy.is_boss())
+            {
+                boss_floors.push(room_y);
+            }
+        }
+
+        //Floors are locked once every room is placed since the rooms
+        //above them are checked by entering through the ceiling gaps
+        for floor in boss_floors {
+            level.lock_floor(floor);
+        }
+
+        (level, tower.enemies, tower.rerolls)
     }
+}
 
-    pub fn generate_level(template_list: &[RoomTemplate]) -> (Self, Vec<Enemy>) {
-        let floors = 48;
-        let mut level = Self::new(18, ROOM_SIZE * floors + floors + 1);
-        let mut enemies = Vec::<Enemy>::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{floor_at, room_template::load_room_templates, transparent};
+    use std::collections::VecDeque;
 
-        let mut rng = rand::thread_rng();
+    fn same_enemies(a: &[Enemy], b: &[Enemy]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(e1, e2)| {
+                e1.enemy_type == e2.enemy_type
+                    && e1.sprite.position == e2.sprite.position
+                    && e1.sprite.flipped == e2.sprite.flipped
+            })
+    }
 
-        for room_y in 0..floors {
-            if template_list.is_empty() {
-                level.empty_room(0, room_y);
-                continue;
+    #[test]
+    fn test_same_seed_same_level() {
+        let templates = load_room_templates("assets/room_templates");
+        let (level1, enemies1) =
+            Level::generate_level(&templates, 1234, TowerSize::STANDARD, Difficulty::Normal);
+        let (level2, enemies2) =
+            Level::generate_level(&templates, 1234, TowerSize::STANDARD, Difficulty::Normal);
+        assert!(level1.tiles == level2.tiles);
+        assert!(level1.background_tiles == level2.background_tiles);
+        assert!(level1.interactive_tiles == level2.interactive_tiles);
+        assert!(same_enemies(&enemies1, &enemies2));
+    }
+
+    #[test]
+    fn test_different_seed_different_level() {
+        let templates = load_room_templates("assets/room_templates");
+        let (level1, enemies1) =
+            Level::generate_level(&templates, 1, TowerSize::STANDARD, Difficulty::Normal);
+        let (level2, enemies2) =
+            Level::generate_level(&templates, 2, TowerSize::STANDARD, Difficulty::Normal);
+        assert!(level1.tiles != level2.tiles || !same_enemies(&enemies1, &enemies2));
+    }
+
+    //Creates an empty template with the given metadata
+    fn template_with(metadata: &str) -> RoomTemplate {
+        let rows = vec![".".repeat(ROOM_SIZE as usize); ROOM_SIZE as usize];
+        let text = format!("{metadata}\ntiles\n{}", rows.join("\n"));
+        RoomTemplate::from_text(&text).expect("failed to parse template")
+    }
+
+    #[test]
+    fn test_template_floor_range() {
+        let templates = [
+            template_with("max_floor 9"),
+            template_with("min_floor 10\ntags lava"),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        for floor in 0..48 {
+            for _ in 0..20 {
+                let chosen = choose_template(&templates, floor, false, None, &[], &mut rng);
+                assert_eq!(chosen, Some(if floor < 10 { 0 } else { 1 }));
             }
+        }
+
+        //No template can appear above floor 20
+        let templates = [template_with("max_floor 20")];
+        assert_eq!(
+            choose_template(&templates, 21, false, None, &[], &mut rng),
+            None
+        );
+    }
 
-            let random_template = &template_list[rng.gen::<usize>() % template_list.len()];
+    #[test]
+    fn test_template_weights() {
+        let templates = [template_with("weight 1"), template_with("weight 3")];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 2];
+        for _ in 0..4000 {
+            if let Some(i) = choose_template(&templates, 0, false, None, &[], &mut rng) {
+                counts[i] += 1;
+            }
+        }
+        //The second template should be chosen about 3 times as often
+        assert!((800..1200).contains(&counts[0]), "{counts:?}");
+        assert!((2800..3200).contains(&counts[1]), "{counts:?}");
 
-            level.generate_room_from_template(&mut enemies, random_template, &mut rng, 0, room_y);
+        //Rest rooms are never placed on two floors in a row
+        let templates = [template_with("tags rest"), template_with("weight 0")];
+        assert_eq!(
+            choose_template(&templates, 0, false, None, &[], &mut rng),
+            Some(0)
+        );
+        assert_eq!(
+            choose_template(&templates, 1, false, Some(0), &[], &mut rng),
+            None
+        );
+    }
 
-            ((ROOM_SIZE / 2 - 1)..(ROOM_SIZE / 2 + 3))
-                .for_each(|x| level.set_tile(x, (room_y + 1) * (ROOM_SIZE + 1), Tile::Air));
+    //Returns true if every room of the tower can be passed once every boss
+    //is defeated, rooms that have the same tiles are only checked once
+    fn tower_passable(level: &mut Level, checked: &mut HashMap<(bool, Vec<u8>), bool>) -> bool {
+        for floor in level.locked_floors().to_vec() {
+            level.unlock_floor(floor);
         }
 
-        (level, enemies)
+        let (floors, rooms_per_floor) = (level.h() / (ROOM_SIZE + 1), level.w() / (ROOM_SIZE + 1));
+        (0..floors).all(|room_y| {
+            (0..rooms_per_floor).all(|room_x| {
+                let mut tiles = vec![];
+                for y in 0..(ROOM_SIZE + 2) {
+                    for x in 1..=ROOM_SIZE {
+                        let (x, y) = (x + room_x * (ROOM_SIZE + 1), y + room_y * (ROOM_SIZE + 1));
+                        tiles.push(level.get_tile(x, y) as u8);
+                    }
+                }
+                //The bottom floor is checked from a different entrance
+                *checked
+                    .entry((room_y == 0, tiles))
+                    .or_insert_with(|| room_passable(level, room_x, room_y))
+            })
+        })
+    }
+
+    //Returns true if the player can get from the start of the tower to the
+    //gap in its roof, where the run is won, once every boss is defeated.
+    //This does not use the traversal module so that a mistake there can not
+    //also hide a tower that can not be finished. Instead the player moves on
+    //the grid of tiles: they stand above a solid tile or on a ladder, walk
+    //and climb a tile at a time and walk over gaps that are a tile wide.
+    //They fall straight down and spikes only do not hurt them when they are
+    //walked onto. A jump lands up to 4 tiles across, or 3 tiles across when
+    //it lands 1 or 2 tiles higher, and the top of a jump can grab a ladder.
+    //Climbing off the top of a ladder moves the player up to 3 tiles across.
+    fn tower_reachable(level: &mut Level) -> bool {
+        for floor in level.locked_floors().to_vec() {
+            level.unlock_floor(floor);
+        }
+
+        let (w, h) = (level.w() as i32, level.h() as i32);
+        let tile = |x: i32, y: i32| {
+            (!level.out_of_bounds(x, y)).then(|| level.get_tile(x as u32, y as u32))
+        };
+        //The player can be inside of air and ladders without being hurt
+        let open = |x, y| matches!(tile(x, y), Some(Tile::Air | Tile::Ladder));
+        let spikes = |x, y| tile(x, y) == Some(Tile::Spikes);
+        let ladder = |x, y| tile(x, y) == Some(Tile::Ladder);
+        let ground = |x, y| tile(x, y).is_some_and(|tile| !transparent(tile));
+        //Tile the player stops in after falling from a tile,
+        //None if they fall into lava or onto spikes
+        let land = |x, mut y| loop {
+            if !open(x, y) {
+                return None;
+            }
+            if ground(x, y - 1) || ladder(x, y) || ladder(x, y - 1) {
+                return Some((x, y));
+            }
+            y -= 1;
+        };
+
+        let mut visited = vec![false; (w * h) as usize];
+        let mut queue = VecDeque::from([(1, 1)]);
+        visited[(w + 1) as usize] = true;
+        while let Some((x, y)) = queue.pop_front() {
+            if y == h - 1 {
+                return true;
+            }
+
+            //Tiles the player can move into, they then fall until they are supported
+            let mut moves = vec![(x - 1, y), (x + 1, y)];
+            for dir in [-1, 1] {
+                if open(x + dir, y) {
+                    moves.push((x + 2 * dir, y));
+                }
+            }
+            //Most tiles across a jump can go after going up by the index
+            //of the list, empty if the player can not jump
+            let mut max_across: &[usize] = &[];
+            if ladder(x, y) {
+                moves.extend([(x, y + 1), (x, y - 1)]);
+            } else if ladder(x, y - 1) {
+                moves.push((x, y - 1));
+                max_across = &[3];
+            } else if ground(x, y - 1) {
+                max_across = &[4, 3, 3];
+            }
+
+            if !max_across.is_empty() {
+                //Tiles the player can be in during a jump with how many
+                //tiles up and across they have moved
+                for dir in [-1, 1] {
+                    let mut jump = vec![(x, y, 0, 0)];
+                    while let Some((jump_x, jump_y, up, across)) = jump.pop() {
+                        if jump_y == h - 1 {
+                            return true;
+                        }
+                        moves.push((jump_x, jump_y));
+                        if up + 1 == max_across.len() && ladder(jump_x, jump_y + 1) {
+                            moves.push((jump_x, jump_y + 1));
+                        }
+                        let can_go_up = max_across.get(up + 1).is_some_and(|max| across <= *max);
+                        if can_go_up && open(jump_x, jump_y + 1) {
+                            jump.push((jump_x, jump_y + 1, up + 1, across));
+                        }
+                        if across < max_across[up] && open(jump_x + dir, jump_y) {
+                            jump.push((jump_x + dir, jump_y, up, across + 1));
+                        }
+                    }
+                }
+            }
+
+            let mut next: Vec<(i32, i32)> =
+                moves.into_iter().filter_map(|(x, y)| land(x, y)).collect();
+            next.extend(
+                [x - 1, x + 1]
+                    .into_iter()
+                    .filter(|&x| spikes(x, y) && ground(x, y - 1))
+                    .map(|x| (x, y)),
+            );
+            for (x, y) in next {
+                let index = (y * w + x) as usize;
+                if !visited[index] {
+                    visited[index] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_generated_towers_are_passable() {
+        //An empty room can not be passed so it has to be rerolled every time
+        let mut templates = load_room_templates("assets/room_templates");
+        templates.push(template_with("weight 20"));
+        let mut checked = HashMap::new();
+        let sizes = [
+            TowerSize::PRACTICE,
+            TowerSize::STANDARD,
+            TowerSize::new(12, 3).expect("invalid tower size"),
+            TowerSize::new(30, 2).expect("invalid tower size"),
+        ];
+        let mut total_rerolls = 0;
+        for size in sizes {
+            for seed in 0..6 {
+                let (mut level, _, rerolls) =
+                    Level::generate_tower(&templates, seed, size, Difficulty::Normal);
+                total_rerolls += rerolls;
+                assert_eq!((level.w(), level.h()), (size.width(), size.height()));
+                assert!(
+                    tower_passable(&mut level, &mut checked),
+                    "seed {seed} size {}",
+                    size.to_text()
+                );
+                assert!(
+                    tower_reachable(&mut level),
+                    "seed {seed} size {}",
+                    size.to_text()
+                );
+            }
+        }
+        //The empty room has to have been rerolled for the checks to be tested
+        assert!(total_rerolls > 0);
+
+        //A tower with an empty room in it can not be finished
+        let (mut level, _) =
+            Level::generate_level(&templates, 0, TowerSize::PRACTICE, Difficulty::Normal);
+        level.empty_room(0, 3);
+        assert!(!tower_reachable(&mut level));
+    }
+
+    #[test]
+    fn test_boss_floors() {
+        assert!(!boss_floor(0, 48) && !boss_floor(16, 48));
+        assert!(boss_floor(15, 48) && boss_floor(31, 48) && boss_floor(47, 48));
+        assert!(boss_floor(7, 8) && !boss_floor(0, 1));
+
+        //Every boss floor has a boss and its ceiling is closed
+        let templates = load_room_templates("assets/room_templates");
+        let size = TowerSize::new(48, 2).expect("invalid tower size");
+        let (level, enemies) = Level::generate_level(&templates, 3, size, Difficulty::Normal);
+        assert_eq!(level.locked_floors(), &[15, 31, 47]);
+        for floor in level.locked_floors() {
+            let bosses = enemies
+                .iter()
+                .filter(|enemy| enemy.is_boss() && floor_at(enemy.sprite.position.y) == *floor)
+                .count();
+            assert_eq!(bosses, 1, "floor {floor}");
+            for room_x in 0..size.rooms_per_floor {
+                assert!(ceiling_gap().all(|x| {
+                    let (x, y) = (x + room_x * (ROOM_SIZE + 1), (floor + 1) * (ROOM_SIZE + 1));
+                    level.get_tile(x, y) != Tile::Air
+                }));
+            }
+        }
+        assert_eq!(enemies.iter().filter(|enemy| enemy.is_boss()).count(), 3);
+    }
+
+    #[test]
+    fn test_tower_size() {
+        assert_eq!(TowerSize::parse("practice"), Ok(TowerSize::PRACTICE));
+        assert_eq!(TowerSize::parse("marathon"), Ok(TowerSize::MARATHON));
+        assert_eq!(TowerSize::parse("20x2"), TowerSize::new(20, 2));
+        assert_eq!(
+            TowerSize::parse(&TowerSize::STANDARD.to_text()),
+            Ok(TowerSize::STANDARD)
+        );
+        assert!(TowerSize::parse("0x1").is_err());
+        assert!(TowerSize::parse("10x9").is_err());
+        assert!(TowerSize::parse("tall").is_err());
+        //The default tower is a single room wide
+        assert_eq!(TowerSize::STANDARD.width(), 18);
+        assert_eq!(TowerSize::STANDARD.height(), 48 * (ROOM_SIZE + 1) + 1);
+    }
+
+    #[test]
+    fn test_impassable_rooms_are_replaced() {
+        //Returns true if there is a ladder at x in the bottom row of every room
+        fn ladder_in_every_room(level: &Level, x: u32) -> bool {
+            let floors = level.h() / (ROOM_SIZE + 1);
+            (0..floors)
+                .all(|room_y| level.get_tile(x, room_y * (ROOM_SIZE + 1) + 1) == Tile::Ladder)
+        }
+
+        //An empty room can not be passed so it is always rerolled
+        let ladder = vec!["........H......."; ROOM_SIZE as usize];
+        let ladder = format!("tiles\n{}", ladder.join("\n"));
+        let templates = [
+            template_with("weight 100"),
+            RoomTemplate::from_text(&ladder).expect("failed to parse template"),
+        ];
+        let (mut level, _) =
+            Level::generate_level(&templates, 0, TowerSize::STANDARD, Difficulty::Normal);
+        assert!(ladder_in_every_room(&level, 9));
+        assert!(tower_passable(&mut level, &mut HashMap::new()));
+
+        //Rooms with a ladder are placed if no template can be passed
+        let (mut level, _) =
+            Level::generate_level(&templates[0..1], 0, TowerSize::STANDARD, Difficulty::Normal);
+        assert!(ladder_in_every_room(&level, ceiling_gap().start));
+        assert!(tower_passable(&mut level, &mut HashMap::new()));
     }
 }
@@ -0,0 +1,466 @@
+use super::{
+    traversal::{is_ladder, on_solid_ground, passable, supported},
+    Level, ROOM_SIZE,
+};
+use crate::game::GRAVITY;
+use cgmath::Vector2;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::OnceLock,
+};
+
+/*
+ * Navigation graph that enemies use to find their way to the player.
+ * The nodes of the graph are the tiles an enemy can stay in: tiles with
+ * solid ground below them, ladders and the tops of ladders. Nodes are
+ * linked by walking to the next tile, climbing up and down ladders,
+ * jumping across gaps and onto ledges and dropping off of ledges.
+ *
+ * The links of the tiles on a floor are found the first time an enemy
+ * needs them and are found again when a tile on or next to the floor
+ * changes, every enemy in the level shares the same graph. Jumps are
+ * checked against how far each enemy can jump when searching for a path.
+ * */
+
+//Furthest an enemy jumps across, in tiles
+const MAX_JUMP_DISTANCE: i32 = 4;
+//Highest ledge that an enemy jumps onto
+const MAX_JUMP_HEIGHT: i32 = 2;
+//Furthest an enemy jumps down
+const MAX_JUMP_DROP: i32 = 3;
+//Furthest an enemy drops off of a ledge, links never go further
+//than the floor above or below so that changing a tile only
+//changes the links of the floors around it
+const MAX_DROP: i32 = ROOM_SIZE as i32 + 1;
+//Most nodes that are looked at when searching for a path
+const MAX_SEARCH_NODES: usize = 2048;
+//How much higher than a ledge an enemy has to jump to land on it
+const JUMP_MARGIN: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinkType {
+    Walk,
+    //Up or down a ladder
+    Climb,
+    //The feet of the enemy have to get `rise` tiles higher
+    //than where it jumped from to clear everything in the way
+    Jump { rise: i32 },
+    //Walk off of a ledge and fall
+    Drop,
+}
+
+//Link from a node to the node at (x, y)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NavLink {
+    pub x: i32,
+    pub y: i32,
+    pub link_type: LinkType,
+}
+
+impl NavLink {
+    //Cost of following the link from (x, y), this is never less than
+    //2 for each tile across and 1 for each tile up or down
+    fn cost(&self, x: i32, y: i32) -> u32 {
+        let (dx, dy) = ((self.x - x).unsigned_abs(), (self.y - y).unsigned_abs());
+        match self.link_type {
+            LinkType::Walk => 2,
+            LinkType::Climb => 3,
+            LinkType::Jump { rise } => 2 * dx + 2 * rise as u32 + 2,
+            LinkType::Drop => 2 + dy,
+        }
+    }
+}
+
+//How far an enemy can jump, enemies with a jump speed
+//of 0 can only walk, climb and drop
+#[derive(Clone, Copy, Debug)]
+pub struct JumpAbility {
+    pub speed: f32,
+    pub jump_speed: f32,
+}
+
+impl JumpAbility {
+    //Returns true if the enemy can jump `dx` tiles across to land `dy` tiles
+    //higher, clearing everything `rise` tiles above where it jumped from.
+    //Enemies jump from the edge of their tile and only have to reach the
+    //edge of the tile they land on.
+    fn can_jump(&self, dx: i32, dy: i32, rise: i32) -> bool {
+        let height = self.jump_speed * self.jump_speed / (2.0 * GRAVITY);
+        if height < rise as f32 + JUMP_MARGIN {
+            return false;
+        }
+
+        //Time to get to the top of the jump and to fall back down to the ledge
+        let air_time = self.jump_speed / GRAVITY + (2.0 * (height - dy as f32) / GRAVITY).sqrt();
+        self.speed * air_time >= dx.abs() as f32 - 1.0
+    }
+}
+
+//Node that a path came from and the link it followed
+type Step = ((i32, i32), NavLink);
+
+//Links of every tile on a floor, the links of the tile at
+//index i are links[first[i]..first[i + 1]]
+struct FloorLinks {
+    first: Vec<u32>,
+    links: Vec<NavLink>,
+}
+
+pub struct NavGraph {
+    floors: Vec<OnceLock<FloorLinks>>,
+    //Goes up every time the graph changes so that enemies
+    //know when the paths they found are out of date
+    version: u64,
+}
+
+impl NavGraph {
+    pub fn new(h: u32) -> Self {
+        Self {
+            floors: (0..h.div_ceil(ROOM_SIZE + 1))
+                .map(|_| OnceLock::new())
+                .collect(),
+            version: 0,
+        }
+    }
+
+    //Forgets the links of the floor that the row is on and of the
+    //floors next to it since links can go into the floor above or below
+    pub fn invalidate(&mut self, y: u32) {
+        self.version += 1;
+        let floor = (y / (ROOM_SIZE + 1)) as usize;
+        for floor in floor.saturating_sub(1)..=(floor + 1) {
+            if let Some(links) = self.floors.get_mut(floor) {
+                links.take();
+            }
+        }
+    }
+}
+
+//Returns the tile that a sprite of the given height is standing in
+pub fn standing_tile(position: Vector2<f32>, height: f32) -> (i32, i32) {
+    (
+        position.x.round() as i32,
+        (position.y - height / 2.0 + 0.5).round() as i32,
+    )
+}
+
+impl Level {
+    pub fn nav_version(&self) -> u64 {
+        self.navigation.version
+    }
+
+    //Returns true if an enemy can stay in the tile
+    pub fn is_nav_node(&self, (x, y): (i32, i32)) -> bool {
+        passable(self, x, y) && supported(self, x, y)
+    }
+
+    //Returns true if an enemy in the tile is held up by a ladder
+    //instead of standing on the ground, enemies do not fall there
+    pub fn is_ladder_node(&self, (x, y): (i32, i32)) -> bool {
+        self.is_nav_node((x, y)) && !on_solid_ground(self, x, y)
+    }
+
+    //Returns the first node at or below the tile, used to find where
+    //the player is going to land if they are in the air
+    pub fn nearest_nav_node(&self, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+        ((y - MAX_JUMP_HEIGHT - 2)..=y)
+            .rev()
+            .map(|y| (x, y))
+            .find(|&node| self.is_nav_node(node))
+    }
+
+    fn jump_links(&self, x: i32, y: i32, dir: i32, links: &mut Vec<NavLink>) {
+        //Returns true if every tile in a column between two rows is passable
+        let clear = |x: i32, bottom: i32, top: i32| (bottom..=top).all(|y| passable(self, x, y));
+
+        //How high the enemy has to jump to get over the tiles in between
+        let mut rise = 0;
+        for distance in 1..=MAX_JUMP_DISTANCE {
+            let jump_x = x + dir * distance;
+            for dy in -MAX_JUMP_DROP..=MAX_JUMP_HEIGHT {
+                //Walking and dropping get to the tiles right next to the enemy
+                if distance == 1 && dy <= 0 {
+                    continue;
+                }
+
+                let rise = rise.max(dy);
+                if rise > MAX_JUMP_HEIGHT
+                    || !self.is_nav_node((jump_x, y + dy))
+                    || !on_solid_ground(self, jump_x, y + dy)
+                {
+                    continue;
+                }
+
+                //There has to be room for the enemy over everything it jumps across
+                let top = y + rise + 1;
+                if clear(x, y, top)
+                    && (1..distance).all(|i| clear(x + dir * i, y + rise, top))
+                    && clear(jump_x, y + dy, top)
+                {
+                    links.push(NavLink {
+                        x: jump_x,
+                        y: y + dy,
+                        link_type: LinkType::Jump { rise },
+                    });
+                }
+            }
+
+            if let Some(block) = (y..=(y + MAX_JUMP_HEIGHT)).rfind(|&y| !passable(self, jump_x, y))
+            {
+                rise = rise.max(block - y + 1);
+            }
+        }
+    }
+
+    //Returns every link from a tile, tiles that are not nodes have no links
+    fn tile_links(&self, x: i32, y: i32) -> Vec<NavLink> {
+        let mut links = vec![];
+        if !self.is_nav_node((x, y)) {
+            return links;
+        }
+
+        let link = |x: i32, y: i32, link_type: LinkType| NavLink { x, y, link_type };
+        if is_ladder(self, x, y) && self.is_nav_node((x, y + 1)) {
+            links.push(link(x, y + 1, LinkType::Climb));
+        }
+        if (is_ladder(self, x, y) || is_ladder(self, x, y - 1)) && self.is_nav_node((x, y - 1)) {
+            links.push(link(x, y - 1, LinkType::Climb));
+        }
+
+        for dir in [-1, 1] {
+            if self.is_nav_node((x + dir, y)) {
+                links.push(link(x + dir, y, LinkType::Walk));
+            } else if passable(self, x + dir, y) {
+                //Fall until there is something to land on
+                let mut drop_y = y - 1;
+                while passable(self, x + dir, drop_y)
+                    && !supported(self, x + dir, drop_y)
+                    && y - drop_y < MAX_DROP
+                {
+                    drop_y -= 1;
+                }
+
+                if self.is_nav_node((x + dir, drop_y)) {
+                    links.push(link(x + dir, drop_y, LinkType::Drop));
+                }
+            }
+
+            //Enemies can only jump from solid ground
+            if on_solid_ground(self, x, y) && !is_ladder(self, x, y) {
+                self.jump_links(x, y, dir, &mut links);
+            }
+        }
+
+        links
+    }
+
+    fn floor_links(&self, floor: u32) -> FloorLinks {
+        let bottom = floor * (ROOM_SIZE + 1);
+        let top = (bottom + ROOM_SIZE + 1).min(self.h());
+        let mut floor_links = FloorLinks {
+            first: vec![0],
+            links: vec![],
+        };
+
+        for y in bottom..top {
+            for x in 0..self.w() {
+                floor_links
+                    .links
+                    .extend(self.tile_links(x as i32, y as i32));
+                floor_links.first.push(floor_links.links.len() as u32);
+            }
+        }
+
+        floor_links
+    }
+
+    pub fn nav_links(&self, x: i32, y: i32) -> &[NavLink] {
+        if self.out_of_bounds(x, y) {
+            return &[];
+        }
+
+        let floor = y as u32 / (ROOM_SIZE + 1);
+        let floor_links =
+            self.navigation.floors[floor as usize].get_or_init(|| self.floor_links(floor));
+        let index = ((y as u32 - floor * (ROOM_SIZE + 1)) * self.w() + x as u32) as usize;
+        let (first, last) = (floor_links.first[index], floor_links.first[index + 1]);
+        &floor_links.links[first as usize..last as usize]
+    }
+
+    //Finds the shortest path between two nodes with A* and returns the
+    //links to follow, jumps that the enemy can not make are skipped.
+    //Returns None if the nodes are the same or there is no path.
+    pub fn find_path(
+        &self,
+        start: (i32, i32),
+        goal: (i32, i32),
+        ability: &JumpAbility,
+    ) -> Option<Vec<NavLink>> {
+        if start == goal || !self.is_nav_node(start) || !self.is_nav_node(goal) {
+            return None;
+        }
+
+        let heuristic =
+            |(x, y): (i32, i32)| 2 * (goal.0 - x).unsigned_abs() + (goal.1 - y).unsigned_abs();
+        //Lowest cost to get to each node and the link it was reached by
+        let mut visited: HashMap<(i32, i32), (u32, Option<Step>)> = HashMap::new();
+        visited.insert(start, (0, None));
+        let mut open = BinaryHeap::new();
+        open.push(Reverse((heuristic(start), 0, start)));
+
+        let mut searched = 0;
+        while let Some(Reverse((_, cost, node))) = open.pop() {
+            if node == goal {
+                let mut path = vec![];
+                let mut current = goal;
+                while let Some(&(_, Some((previous, link)))) = visited.get(&current) {
+                    path.push(link);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            //Skip nodes that were found again with a lower cost
+            if visited.get(&node).is_some_and(|&(lowest, _)| cost > lowest) {
+                continue;
+            }
+
+            searched += 1;
+            if searched > MAX_SEARCH_NODES {
+                return None;
+            }
+
+            for link in self.nav_links(node.0, node.1) {
+                if let LinkType::Jump { rise } = link.link_type {
+                    if !ability.can_jump(link.x - node.0, link.y - node.1, rise) {
+                        continue;
+                    }
+                }
+
+                let next = (link.x, link.y);
+                let next_cost = cost + link.cost(node.0, node.1);
+                if visited
+                    .get(&next)
+                    .is_none_or(|&(lowest, _)| next_cost < lowest)
+                {
+                    visited.insert(next, (next_cost, Some((node, *link))));
+                    open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Tile;
+
+    const WALKER: JumpAbility = JumpAbility {
+        speed: 1.0,
+        jump_speed: 0.0,
+    };
+    const JUMPER: JumpAbility = JumpAbility {
+        speed: 2.0,
+        jump_speed: 8.0,
+    };
+
+    //Creates an empty room with walls on the sides and a floor at y = 0
+    fn empty_level() -> Level {
+        let mut level = Level::new(12, 12);
+        for x in 1..11 {
+            for y in 1..12 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+        level
+    }
+
+    fn link_types(path: &[NavLink]) -> Vec<LinkType> {
+        path.iter().map(|link| link.link_type).collect()
+    }
+
+    #[test]
+    fn test_climb_ladders() {
+        let mut level = empty_level();
+        //Platform at y = 5 with a ladder up to it
+        for x in 1..6 {
+            level.set_tile(x, 5, Tile::Brick);
+        }
+        for y in 1..=5 {
+            level.set_tile(6, y, Tile::Ladder);
+        }
+
+        let path = level.find_path((9, 1), (2, 6), &WALKER);
+        let path = path.expect("no path up the ladder");
+        assert_eq!(
+            path.iter()
+                .filter(|link| link.link_type == LinkType::Climb)
+                .count(),
+            5
+        );
+        assert_eq!(path.last().map(|link| (link.x, link.y)), Some((2, 6)));
+
+        //Dropping off the top of the ladder is faster than climbing back down
+        let path = level.find_path((2, 6), (9, 1), &WALKER);
+        assert!(path.is_some_and(|path| link_types(&path).contains(&LinkType::Drop)));
+    }
+
+    #[test]
+    fn test_jump_across_gaps() {
+        let mut level = empty_level();
+        //Pit of lava that is 2 tiles wide
+        for x in 5..7 {
+            level.set_tile(x, 0, Tile::Lava);
+        }
+
+        let path = level
+            .find_path((2, 1), (9, 1), &JUMPER)
+            .expect("no path over the gap");
+        assert!(link_types(&path).contains(&LinkType::Jump { rise: 0 }));
+        //Enemies that can not jump can not get across
+        assert_eq!(level.find_path((2, 1), (9, 1), &WALKER), None);
+
+        //Ledges that are too high can not be jumped onto
+        let mut level = empty_level();
+        for x in 6..11 {
+            level.set_tile(x, 1, Tile::Brick);
+        }
+        assert!(level.find_path((2, 1), (8, 2), &JUMPER).is_some());
+        let low_jump = JumpAbility {
+            speed: 2.0,
+            jump_speed: 4.0,
+        };
+        assert_eq!(level.find_path((2, 1), (8, 2), &low_jump), None);
+        for x in 6..11 {
+            level.set_tile(x, 2, Tile::Brick);
+            level.set_tile(x, 3, Tile::Brick);
+        }
+        assert_eq!(level.find_path((2, 1), (8, 4), &JUMPER), None);
+    }
+
+    #[test]
+    fn test_drop_off_ledges() {
+        let mut level = empty_level();
+        for x in 1..6 {
+            level.set_tile(x, 6, Tile::Brick);
+        }
+
+        let path = level
+            .find_path((2, 7), (8, 1), &WALKER)
+            .expect("no path off the ledge");
+        assert!(link_types(&path).contains(&LinkType::Drop));
+        //There is no way back up without a ladder
+        assert_eq!(level.find_path((8, 1), (2, 7), &JUMPER), None);
+
+        //The graph is updated when the level changes
+        for y in 1..=6 {
+            level.set_tile(6, y, Tile::Ladder);
+        }
+        assert!(level.find_path((8, 1), (2, 7), &WALKER).is_some());
+    }
+}
@@ -0,0 +1,93 @@
+use super::{transparent, Level};
+use cgmath::Vector2;
+
+//Returns the tile that a position is in, tiles are centered on integer coordinates
+fn tile_at(position: Vector2<f32>) -> (i32, i32) {
+    (
+        (position.x + 0.5).floor() as i32,
+        (position.y + 0.5).floor() as i32,
+    )
+}
+
+//Distance along a line (from 0 at the start to 1 at the end) to the first
+//tile boundary in one axis and the distance to cross a whole tile
+fn boundaries(start: f32, delta: f32, tile: i32) -> (f32, f32) {
+    if delta > 0.0 {
+        ((tile as f32 + 0.5 - start) / delta, 1.0 / delta)
+    } else if delta < 0.0 {
+        ((tile as f32 - 0.5 - start) / delta, -1.0 / delta)
+    } else {
+        (f32::INFINITY, f32::INFINITY)
+    }
+}
+
+impl Level {
+    //Walks the tiles along the line from start to end and returns the first
+    //one that is not transparent, returns None if nothing is in the way.
+    //Tiles outside of the level are treated as air.
+    pub fn raycast(&self, start: Vector2<f32>, end: Vector2<f32>) -> Option<(i32, i32)> {
+        let delta = end - start;
+        let (mut x, mut y) = tile_at(start);
+        let (step_x, step_y) = (delta.x.signum() as i32, delta.y.signum() as i32);
+        let (mut next_x, cross_x) = boundaries(start.x, delta.x, x);
+        let (mut next_y, cross_y) = boundaries(start.y, delta.y, y);
+
+        loop {
+            if !self.out_of_bounds(x, y) && !transparent(self.get_tile(x as u32, y as u32)) {
+                return Some((x, y));
+            }
+
+            if next_x.min(next_y) > 1.0 {
+                return None;
+            }
+
+            if next_x < next_y {
+                x += step_x;
+                next_x += cross_x;
+            } else {
+                y += step_y;
+                next_y += cross_y;
+            }
+        }
+    }
+
+    //Returns true if nothing blocks the line between the two positions
+    pub fn line_of_sight(&self, start: Vector2<f32>, end: Vector2<f32>) -> bool {
+        self.raycast(start, end).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Tile;
+    use cgmath::vec2;
+
+    #[test]
+    fn test_raycast() {
+        let mut level = Level::new(12, 12);
+        for x in 1..11 {
+            for y in 1..11 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+        level.set_tile(5, 3, Tile::Brick);
+        //Ladders, lava and spikes can be seen through
+        level.set_tile(5, 5, Tile::Ladder);
+        level.set_tile(5, 6, Tile::Lava);
+
+        assert_eq!(level.raycast(vec2(2.0, 3.0), vec2(8.0, 3.0)), Some((5, 3)));
+        assert_eq!(level.raycast(vec2(8.0, 3.0), vec2(2.0, 3.0)), Some((5, 3)));
+        assert_eq!(level.raycast(vec2(2.0, 5.0), vec2(8.0, 6.0)), None);
+        assert!(level.line_of_sight(vec2(5.0, 1.0), vec2(5.0, 2.4)));
+        assert!(!level.line_of_sight(vec2(5.0, 1.0), vec2(5.0, 4.0)));
+
+        //Diagonal lines stop at the first tile they touch
+        assert_eq!(level.raycast(vec2(3.0, 1.0), vec2(7.0, 5.0)), Some((5, 3)));
+        assert_eq!(level.raycast(vec2(2.0, 2.0), vec2(9.0, 9.0)), None);
+        assert_eq!(level.raycast(vec2(2.0, 2.0), vec2(-3.0, 2.0)), Some((0, 2)));
+
+        //The line ends at the end position
+        assert_eq!(level.raycast(vec2(2.0, 3.0), vec2(4.0, 3.0)), None);
+    }
+}
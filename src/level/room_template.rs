@@ -1,11 +1,14 @@
 use super::BackgroundTile;
 use super::Tile;
 use super::ROOM_SIZE;
-use crate::gfx::load_image_pixels;
+use crate::gfx::{load_image_pixels, save_image_pixels};
 use core::slice::Iter;
 use std::fs::File;
 use std::io::Read;
 
+mod text;
+pub mod validate;
+
 /*
  * There are 4 types of room templates:
  * - Starting room -> this is the room the player starts in
@@ -13,24 +16,43 @@ use std::io::Read;
  * - Vertical -> this is the room that will lead the player to the next floor
  * */
 
+#[derive(PartialEq, Eq)]
 pub enum SpawnType {
     MaybeTreasure,
     Treasure,
     MaybeEnemy,
     Enemy,
     Pickup,
+    Boss,
 }
 
+#[derive(PartialEq, Eq)]
 pub struct Spawn {
     pub spawn_type: SpawnType,
     pub tile_x: u32,
     pub tile_y: u32,
 }
 
+#[derive(PartialEq)]
 pub struct RoomTemplate {
     tiles: [Tile; (ROOM_SIZE * ROOM_SIZE) as usize],
     background_tiles: [BackgroundTile; (ROOM_SIZE * ROOM_SIZE) as usize],
     spawns: Vec<Spawn>,
+    //How likely the template is to be chosen compared to other templates
+    weight: u32,
+    //Range of floors that the template can appear on
+    min_floor: u32,
+    max_floor: Option<u32>,
+    //Labels describing the room, for example "treasure", "lava" or "rest"
+    tags: Vec<String>,
+}
+
+//A line in the template list, of the form:
+//path key=value key=value ...
+//where the keys are the same as the metadata of a text template
+pub struct TemplateListEntry {
+    pub path: String,
+    pub metadata: Vec<(String, String)>,
 }
 
 //Attempts to convert a pixel
@@ -57,10 +79,34 @@ fn pixel_to_spawn(pixel: u32) -> Option<SpawnType> {
         0xff008800 => Some(SpawnType::MaybeEnemy),
         0xff00ff00 => Some(SpawnType::Enemy),
         0xffff00ff => Some(SpawnType::Pickup),
+        0xff000088 => Some(SpawnType::Boss),
         _ => None,
     }
 }
 
+fn tile_to_pixel(tile: Tile) -> u32 {
+    match tile {
+        Tile::Air => 0x00000000,
+        Tile::Brick => 0xffaaaaaa,
+        Tile::Ladder => 0xff002848,
+        Tile::BrickTile => 0xff666666,
+        Tile::BrickTile2 => 0xff444444,
+        Tile::Lava => 0xff0000ff,
+        Tile::Spikes => 0xffffffff,
+    }
+}
+
+fn spawn_to_pixel(spawn_type: &SpawnType) -> u32 {
+    match spawn_type {
+        SpawnType::MaybeTreasure => 0xff008888,
+        SpawnType::Treasure => 0xff00ffff,
+        SpawnType::MaybeEnemy => 0xff008800,
+        SpawnType::Enemy => 0xff00ff00,
+        SpawnType::Pickup => 0xffff00ff,
+        SpawnType::Boss => 0xff000088,
+    }
+}
+
 fn pixel_to_background_tile(pixel: u32) -> Option<BackgroundTile> {
     match pixel {
         0xffff4848 => Some(BackgroundTile::BannerTop),
@@ -73,14 +119,121 @@ fn pixel_to_background_tile(pixel: u32) -> Option<BackgroundTile> {
     }
 }
 
+//Returns None if the background tile is not stored in a pixel,
+//the bottom halves of banners and big windows are filled in
+//automatically when the template is loaded
+fn background_tile_to_pixel(background_tile: BackgroundTile) -> Option<u32> {
+    match background_tile {
+        BackgroundTile::BannerTop => Some(0xffff4848),
+        BackgroundTile::SkullDecoration => Some(0xff222222),
+        BackgroundTile::Window => Some(0xffffff00),
+        BackgroundTile::BarredWindow => Some(0xff888800),
+        BackgroundTile::Painting1 | BackgroundTile::Painting2 => Some(0xffff8800),
+        BackgroundTile::BigWindowTop => Some(0xffffaa66),
+        BackgroundTile::Empty
+        | BackgroundTile::Wall
+        | BackgroundTile::BannerBottom
+        | BackgroundTile::BigWindowBottom => None,
+    }
+}
+
 impl RoomTemplate {
-    pub fn load_from_file(path: &str) -> Result<Self, String> {
-        let mut template = RoomTemplate {
+    fn empty() -> Self {
+        Self {
             tiles: [Tile::Air; (ROOM_SIZE * ROOM_SIZE) as usize],
             background_tiles: [BackgroundTile::Wall; (ROOM_SIZE * ROOM_SIZE) as usize],
             spawns: Vec::new(),
+            weight: 1,
+            min_floor: 0,
+            max_floor: None,
+            tags: Vec::new(),
+        }
+    }
+
+    //Sets a metadata value of the template, metadata can come from the
+    //header of a text template or from the template list
+    fn set_metadata(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parse_number = || {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("{key} expects a positive whole number"))
         };
 
+        match key {
+            "weight" => self.weight = parse_number()?,
+            "min_floor" => self.min_floor = parse_number()?,
+            "max_floor" => self.max_floor = Some(parse_number()?),
+            "tags" => {
+                self.tags = value
+                    .split(',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            _ => return Err(format!("unknown metadata: {key}")),
+        }
+
+        Ok(())
+    }
+
+    fn check_floor_range(&self) -> Result<(), String> {
+        match self.max_floor {
+            Some(max_floor) if self.min_floor > max_floor => Err(format!(
+                "min_floor ({}) is greater than max_floor ({max_floor})",
+                self.min_floor
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    //How likely the template is to be chosen on `floor`,
+    //0 if the template can not appear on that floor
+    pub fn weight_on_floor(&self, floor: u32) -> u32 {
+        if floor < self.min_floor || self.max_floor.is_some_and(|max_floor| floor > max_floor) {
+            return 0;
+        }
+
+        self.weight
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    //Loads a template from a PNG file or a text file ending in `.room`
+    pub fn load(path: &str) -> Result<Self, String> {
+        if path.ends_with(".room") {
+            Self::load_from_text_file(path)
+        } else {
+            Self::load_from_file(path)
+        }
+    }
+
+    //Saves a template as a PNG file or a text file ending in `.room`
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if path.ends_with(".room") {
+            self.save_text_file(path)
+        } else {
+            self.save_png(path)
+        }
+    }
+
+    //Fills in the bottom halves of banners and big windows
+    fn fill_background_bottoms(&mut self) {
+        for y in (0..ROOM_SIZE).rev() {
+            for x in 0..ROOM_SIZE {
+                if self.get_background_tile(x, y + 1) == BackgroundTile::BannerTop {
+                    self.set_background_tile(x, y, BackgroundTile::BannerBottom);
+                } else if self.get_background_tile(x, y + 1) == BackgroundTile::BigWindowTop {
+                    self.set_background_tile(x, y, BackgroundTile::BigWindowBottom);
+                }
+            }
+        }
+    }
+
+    pub fn load_from_file(path: &str) -> Result<Self, String> {
+        let mut template = RoomTemplate::empty();
+
         let (buf, _) = load_image_pixels(path)?;
 
         let mut x = 0;
@@ -94,12 +247,6 @@ impl RoomTemplate {
                 pixel_to_background_tile(*pixel).unwrap_or(BackgroundTile::Wall),
             );
 
-            if template.get_background_tile(x, y + 1) == BackgroundTile::BannerTop {
-                template.set_background_tile(x, y, BackgroundTile::BannerBottom);
-            } else if template.get_background_tile(x, y + 1) == BackgroundTile::BigWindowTop {
-                template.set_background_tile(x, y, BackgroundTile::BigWindowBottom);
-            }
-
             if let Some(t) = pixel_to_spawn(*pixel) {
                 template.spawns.push(Spawn {
                     spawn_type: t,
@@ -115,9 +262,44 @@ impl RoomTemplate {
             }
         });
 
+        template.fill_background_bottoms();
+
         Ok(template)
     }
 
+    //A pixel can only store a tile, a background tile or a spawn so
+    //templates that have more than one of those on a tile can not be
+    //saved as a PNG, the metadata is also not stored in the PNG
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let mut pixels = vec![0; (ROOM_SIZE * ROOM_SIZE) as usize];
+
+        for y in 0..ROOM_SIZE {
+            for x in 0..ROOM_SIZE {
+                let tile = self.get_tile(x, y);
+                let background_pixel = background_tile_to_pixel(self.get_background_tile(x, y));
+                let spawn = self
+                    .spawns
+                    .iter()
+                    .find(|spawn| spawn.tile_x == x && spawn.tile_y == y);
+
+                let pixel = match (tile, background_pixel, spawn) {
+                    (tile, None, None) => tile_to_pixel(tile),
+                    (Tile::Air, Some(pixel), None) => pixel,
+                    (Tile::Air, None, Some(spawn)) => spawn_to_pixel(&spawn.spawn_type),
+                    _ => {
+                        return Err(format!(
+                            "({x}, {y}) has more than one of a tile, background tile or spawn"
+                        ))
+                    }
+                };
+
+                pixels[((ROOM_SIZE - 1 - y) * ROOM_SIZE + x) as usize] = pixel;
+            }
+        }
+
+        save_image_pixels(path, &pixels, ROOM_SIZE, ROOM_SIZE)
+    }
+
     pub fn get_tile(&self, x: u32, y: u32) -> Tile {
         if x >= ROOM_SIZE || y >= ROOM_SIZE {
             return Tile::Air;
@@ -155,43 +337,138 @@ impl RoomTemplate {
     }
 }
 
-pub fn load_room_templates(path: &str) -> Vec<RoomTemplate> {
+//Parses a template list, blank lines and lines starting with `//` are ignored
+pub fn parse_template_list(text: &str) -> Result<Vec<TemplateListEntry>, String> {
+    let mut entries = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let mut split = line.split_whitespace();
+        let path = split.next().unwrap_or_default().to_string();
+        let metadata = split
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(format!("line {}: expected key=value: {pair}", i + 1)),
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+
+        entries.push(TemplateListEntry { path, metadata });
+    }
+
+    Ok(entries)
+}
+
+//Returns the entries in the template list of a directory,
+//the paths of the entries include the directory
+pub fn read_template_list(path: &str) -> Vec<TemplateListEntry> {
     let mut template_list_path = String::from(path);
     template_list_path.push_str("/template_list.txt");
 
-    let paths: Vec<String> = match File::open(&template_list_path) {
-        Ok(mut file) => {
-            let mut buf = String::new();
-            if let Err(msg) = file.read_to_string(&mut buf) {
-                eprintln!("{msg}");
-            }
+    let mut buf = String::new();
+    if let Err(msg) = File::open(&template_list_path).and_then(|mut f| f.read_to_string(&mut buf)) {
+        eprintln!("failed to open: {template_list_path}");
+        eprintln!("{msg}");
+        return Vec::new();
+    }
 
-            buf.lines()
-                .map(|line| {
-                    let mut path = String::from(path);
-                    path.push('/');
-                    path.push_str(line);
-                    path
-                })
-                .collect()
-        }
+    match parse_template_list(&buf) {
+        Ok(entries) => entries
+            .into_iter()
+            .map(|entry| TemplateListEntry {
+                path: format!("{path}/{}", entry.path),
+                metadata: entry.metadata,
+            })
+            .collect(),
         Err(msg) => {
-            eprintln!("failed to open: {template_list_path}");
-            eprintln!("{msg}");
-            return Vec::new(); //return empty room template list
+            eprintln!("{template_list_path}: {msg}");
+            Vec::new()
         }
-    };
+    }
+}
+
+//Loads a template and applies the metadata from the template list to it,
+//metadata in the template list overrides the metadata in the template
+fn load_template_list_entry(entry: &TemplateListEntry) -> Result<RoomTemplate, String> {
+    let mut template = RoomTemplate::load(&entry.path)?;
+    for (key, value) in &entry.metadata {
+        template.set_metadata(key, value)?;
+    }
+    template.check_floor_range()?;
+    Ok(template)
+}
 
-    paths
+pub fn load_room_templates(path: &str) -> Vec<RoomTemplate> {
+    read_template_list(path)
         .iter()
-        .map(|path| RoomTemplate::load_from_file(path))
-        .filter(|template_res| template_res.is_ok())
-        .map(|template_res| {
-            template_res.unwrap_or(RoomTemplate {
-                tiles: [Tile::Air; (ROOM_SIZE * ROOM_SIZE) as usize],
-                background_tiles: [BackgroundTile::Empty; (ROOM_SIZE * ROOM_SIZE) as usize],
-                spawns: Vec::new(),
-            })
+        .filter_map(|entry| match load_template_list_entry(entry) {
+            Ok(template) => Some(template),
+            Err(msg) => {
+                eprintln!("failed to load room template: {}", entry.path);
+                eprintln!("{msg}");
+                None
+            }
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template_list() {
+        let list = "//Comment\nroom1.png\n\nroom2.png weight=3 min_floor=8 tags=lava,rest\n";
+        let entries = parse_template_list(list).expect("failed to parse template list");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "room1.png");
+        assert!(entries[0].metadata.is_empty());
+        assert_eq!(entries[1].path, "room2.png");
+        assert_eq!(
+            entries[1].metadata,
+            vec![
+                ("weight".to_string(), "3".to_string()),
+                ("min_floor".to_string(), "8".to_string()),
+                ("tags".to_string(), "lava,rest".to_string()),
+            ]
+        );
+
+        assert!(parse_template_list("room1.png weight\n").is_err());
+    }
+
+    #[test]
+    fn test_template_list_metadata() {
+        let entry = TemplateListEntry {
+            path: "assets/room_templates/room1.png".to_string(),
+            metadata: vec![
+                ("weight".to_string(), "5".to_string()),
+                ("min_floor".to_string(), "4".to_string()),
+                ("max_floor".to_string(), "9".to_string()),
+                ("tags".to_string(), "treasure".to_string()),
+            ],
+        };
+        let template = load_template_list_entry(&entry).expect("failed to load template");
+        assert!(template.has_tag("treasure"));
+        assert_eq!(template.weight_on_floor(3), 0);
+        assert_eq!(template.weight_on_floor(4), 5);
+        assert_eq!(template.weight_on_floor(9), 5);
+        assert_eq!(template.weight_on_floor(10), 0);
+
+        let bad_entry = TemplateListEntry {
+            path: entry.path.clone(),
+            metadata: vec![("colour".to_string(), "red".to_string())],
+        };
+        assert!(load_template_list_entry(&bad_entry).is_err());
+
+        //Tags are free-form labels so any tag can be used
+        let tagged_entry = TemplateListEntry {
+            path: entry.path.clone(),
+            metadata: vec![("tags".to_string(), "boss,lava".to_string())],
+        };
+        let template = load_template_list_entry(&tagged_entry).expect("failed to load template");
+        assert!(template.has_tag("boss") && template.has_tag("lava"));
+    }
+}
@@ -0,0 +1,390 @@
+use super::{RoomTemplate, Spawn, SpawnType};
+use crate::level::{BackgroundTile, Tile, ROOM_SIZE};
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+
+/*
+ * Text room templates are made up of metadata followed by three
+ * layers that are each ROOM_SIZE characters wide and ROOM_SIZE rows tall,
+ * rows are listed from the top of the room to the bottom:
+ *
+ * //Comments start with two slashes
+ * weight 1
+ * min_floor 0
+ * max_floor 47
+ * tags lava,treasure
+ * tiles
+ * <ROOM_SIZE rows of tiles>
+ * background
+ * <ROOM_SIZE rows of background tiles>
+ * spawns
+ * <ROOM_SIZE rows of spawns>
+ *
+ * The tiles layer is required, the other layers and the metadata
+ * are optional. `.` is always the default value of a layer.
+ * */
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Layer {
+    Tiles,
+    Background,
+    Spawns,
+}
+
+impl Layer {
+    fn name(&self) -> &'static str {
+        match self {
+            Layer::Tiles => "tiles",
+            Layer::Background => "background",
+            Layer::Spawns => "spawns",
+        }
+    }
+}
+
+fn char_to_tile(ch: char) -> Option<Tile> {
+    match ch {
+        '.' => Some(Tile::Air),
+        '#' => Some(Tile::Brick),
+        'H' => Some(Tile::Ladder),
+        '=' => Some(Tile::BrickTile),
+        '%' => Some(Tile::BrickTile2),
+        '~' => Some(Tile::Lava),
+        '^' => Some(Tile::Spikes),
+        _ => None,
+    }
+}
+
+fn tile_to_char(tile: Tile) -> char {
+    match tile {
+        Tile::Air => '.',
+        Tile::Brick => '#',
+        Tile::Ladder => 'H',
+        Tile::BrickTile => '=',
+        Tile::BrickTile2 => '%',
+        Tile::Lava => '~',
+        Tile::Spikes => '^',
+    }
+}
+
+//The bottom halves of banners and big windows are filled in automatically
+//below the top halves but can also be written out explicitly
+fn char_to_background_tile(ch: char) -> Option<BackgroundTile> {
+    match ch {
+        '.' => Some(BackgroundTile::Wall),
+        'S' => Some(BackgroundTile::SkullDecoration),
+        'B' => Some(BackgroundTile::BannerTop),
+        'b' => Some(BackgroundTile::BannerBottom),
+        'W' => Some(BackgroundTile::Window),
+        'X' => Some(BackgroundTile::BarredWindow),
+        'P' => Some(BackgroundTile::Painting1),
+        'G' => Some(BackgroundTile::BigWindowTop),
+        'g' => Some(BackgroundTile::BigWindowBottom),
+        _ => None,
+    }
+}
+
+fn background_tile_to_char(background_tile: BackgroundTile) -> char {
+    match background_tile {
+        BackgroundTile::Empty | BackgroundTile::Wall => '.',
+        BackgroundTile::SkullDecoration => 'S',
+        BackgroundTile::BannerTop => 'B',
+        BackgroundTile::BannerBottom => 'b',
+        BackgroundTile::Window => 'W',
+        BackgroundTile::BarredWindow => 'X',
+        //Painting2 is only chosen randomly when the tower is generated
+        BackgroundTile::Painting1 | BackgroundTile::Painting2 => 'P',
+        BackgroundTile::BigWindowTop => 'G',
+        BackgroundTile::BigWindowBottom => 'g',
+    }
+}
+
+//Returns Ok(None) if there is no spawn
+fn char_to_spawn(ch: char) -> Result<Option<SpawnType>, ()> {
+    match ch {
+        '.' => Ok(None),
+        '?' => Ok(Some(SpawnType::MaybeTreasure)),
+        '$' => Ok(Some(SpawnType::Treasure)),
+        'e' => Ok(Some(SpawnType::MaybeEnemy)),
+        'E' => Ok(Some(SpawnType::Enemy)),
+        '+' => Ok(Some(SpawnType::Pickup)),
+        'B' => Ok(Some(SpawnType::Boss)),
+        _ => Err(()),
+    }
+}
+
+fn spawn_to_char(spawn_type: &SpawnType) -> char {
+    match spawn_type {
+        SpawnType::MaybeTreasure => '?',
+        SpawnType::Treasure => '$',
+        SpawnType::MaybeEnemy => 'e',
+        SpawnType::Enemy => 'E',
+        SpawnType::Pickup => '+',
+        SpawnType::Boss => 'B',
+    }
+}
+
+impl RoomTemplate {
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut template = RoomTemplate::empty();
+        //The layer currently being read and how many rows have been read
+        let mut current_layer: Option<(Layer, u32)> = None;
+        let mut layers_read = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            if let Some((layer, rows)) = current_layer {
+                if rows < ROOM_SIZE {
+                    if line.chars().count() != ROOM_SIZE as usize {
+                        return Err(format!(
+                            "line {line_number}: {} row must be {ROOM_SIZE} characters wide",
+                            layer.name()
+                        ));
+                    }
+
+                    let y = ROOM_SIZE - 1 - rows;
+                    for (x, ch) in line.chars().enumerate() {
+                        let x = x as u32;
+                        let invalid = || {
+                            format!(
+                                "line {line_number}: unknown {} character '{ch}' at ({x}, {y})",
+                                layer.name()
+                            )
+                        };
+
+                        match layer {
+                            Layer::Tiles => {
+                                template.set_tile(x, y, char_to_tile(ch).ok_or_else(invalid)?)
+                            }
+                            Layer::Background => template.set_background_tile(
+                                x,
+                                y,
+                                char_to_background_tile(ch).ok_or_else(invalid)?,
+                            ),
+                            Layer::Spawns => {
+                                if let Some(spawn_type) =
+                                    char_to_spawn(ch).map_err(|_| invalid())?
+                                {
+                                    template.spawns.push(Spawn {
+                                        spawn_type,
+                                        tile_x: x,
+                                        tile_y: y,
+                                    });
+                                }
+                            }
+                        }
+                    }
+
+                    current_layer = Some((layer, rows + 1));
+                    continue;
+                }
+            }
+
+            let mut split = line.split_whitespace();
+            let layer = match split.next() {
+                Some(key @ ("weight" | "min_floor" | "max_floor" | "tags")) => {
+                    let value = split
+                        .next()
+                        .ok_or(format!("line {line_number}: {key} expects a value"))?;
+                    template
+                        .set_metadata(key, value)
+                        .map_err(|msg| format!("line {line_number}: {msg}"))?;
+                    None
+                }
+                Some("tiles") => Some(Layer::Tiles),
+                Some("background") => Some(Layer::Background),
+                Some("spawns") => Some(Layer::Spawns),
+                _ => return Err(format!("line {line_number}: unexpected line: {line}")),
+            };
+
+            if split.next().is_some() {
+                return Err(format!("line {line_number}: unexpected value after {line}"));
+            }
+
+            if let Some(layer) = layer {
+                if layers_read.contains(&layer) {
+                    return Err(format!(
+                        "line {line_number}: {} layer is defined twice",
+                        layer.name()
+                    ));
+                }
+                layers_read.push(layer);
+                current_layer = Some((layer, 0));
+            } else {
+                current_layer = None;
+            }
+        }
+
+        if let Some((layer, rows)) = current_layer {
+            if rows < ROOM_SIZE {
+                return Err(format!(
+                    "{} layer has {rows} rows, expected {ROOM_SIZE}",
+                    layer.name()
+                ));
+            }
+        }
+
+        if !layers_read.contains(&Layer::Tiles) {
+            return Err("template is missing a tiles layer".to_string());
+        }
+
+        template.check_floor_range()?;
+
+        template.fill_background_bottoms();
+
+        Ok(template)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("weight {}\nmin_floor {}\n", self.weight, self.min_floor);
+        if let Some(max_floor) = self.max_floor {
+            text.push_str(format!("max_floor {max_floor}\n").as_str());
+        }
+        if !self.tags.is_empty() {
+            text.push_str(format!("tags {}\n", self.tags.join(",")).as_str());
+        }
+
+        text.push_str("tiles\n");
+        for y in (0..ROOM_SIZE).rev() {
+            text.extend((0..ROOM_SIZE).map(|x| tile_to_char(self.get_tile(x, y))));
+            text.push('\n');
+        }
+
+        text.push_str("background\n");
+        for y in (0..ROOM_SIZE).rev() {
+            text.extend(
+                (0..ROOM_SIZE).map(|x| background_tile_to_char(self.get_background_tile(x, y))),
+            );
+            text.push('\n');
+        }
+
+        text.push_str("spawns\n");
+        let mut spawns = vec!['.'; (ROOM_SIZE * ROOM_SIZE) as usize];
+        for spawn in &self.spawns {
+            spawns[(spawn.tile_y * ROOM_SIZE + spawn.tile_x) as usize] =
+                spawn_to_char(&spawn.spawn_type);
+        }
+        for y in (0..ROOM_SIZE).rev() {
+            text.extend((0..ROOM_SIZE).map(|x| spawns[(y * ROOM_SIZE + x) as usize]));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn load_from_text_file(path: &str) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf).map_err(|e| e.to_string())?;
+        Self::from_text(&buf).map_err(|msg| format!("{path}: {msg}"))
+    }
+
+    pub fn save_text_file(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        file.write_all(self.to_text().as_bytes())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::room_template::read_template_list;
+
+    const TEST_TEMPLATE: &str = "
+        //A small test room
+        weight 3
+        min_floor 2
+        max_floor 10
+        tags lava,rest
+        tiles
+        ################
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ........H.......
+        ~~......H.....^^
+        ################
+        spawns
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ................
+        ..E.....?.......
+        ................
+        ................
+    ";
+
+    #[test]
+    fn test_parse_text_template() {
+        let template = RoomTemplate::from_text(TEST_TEMPLATE).expect("failed to parse template");
+        assert_eq!(template.weight, 3);
+        assert_eq!(template.min_floor, 2);
+        assert_eq!(template.max_floor, Some(10));
+        assert!(template.has_tag("lava") && template.has_tag("rest"));
+        assert!(!template.has_tag("treasure"));
+        assert!(template.get_tile(0, 0) == Tile::Brick);
+        assert!(template.get_tile(0, 1) == Tile::Lava);
+        assert!(template.get_tile(15, 1) == Tile::Spikes);
+        assert!(template.get_tile(8, 2) == Tile::Ladder);
+        assert!(template.get_background_tile(3, 3) == BackgroundTile::Wall);
+        //A spawn can share a tile with a ladder
+        let spawns: Vec<(u32, u32)> = template
+            .get_spawns()
+            .map(|s| (s.tile_x, s.tile_y))
+            .collect();
+        assert_eq!(spawns, vec![(2, 2), (8, 2)]);
+    }
+
+    #[test]
+    fn test_invalid_text_template() {
+        //Missing tiles layer
+        assert!(RoomTemplate::from_text("weight 1\n").is_err());
+        //Unknown tile
+        let bad_tile = TEST_TEMPLATE.replacen("~~", "~z", 1);
+        let err = RoomTemplate::from_text(&bad_tile).err().unwrap_or_default();
+        assert!(err.contains("line 22"), "{err}");
+        assert!(err.contains("(1, 1)"), "{err}");
+        //Row too short
+        let short_row = TEST_TEMPLATE.replacen("........H.......", "........H", 1);
+        assert!(RoomTemplate::from_text(&short_row).is_err());
+        //min_floor is greater than max_floor
+        let bad_floors = TEST_TEMPLATE.replacen("min_floor 2", "min_floor 11", 1);
+        assert!(RoomTemplate::from_text(&bad_floors).is_err());
+    }
+
+    #[test]
+    fn test_png_templates_round_trip_through_text() {
+        for entry in read_template_list("assets/room_templates") {
+            let path = entry.path;
+            let template = RoomTemplate::load(&path).expect("failed to load template");
+            let converted = RoomTemplate::from_text(&template.to_text())
+                .unwrap_or_else(|msg| panic!("{path}: {msg}"));
+            assert!(template == converted, "{path} changed after conversion");
+        }
+    }
+}
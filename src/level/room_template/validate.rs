@@ -0,0 +1,149 @@
+use super::{
+    load_template_list_entry, pixel_to_background_tile, pixel_to_spawn, pixel_to_tile,
+    read_template_list, RoomTemplate,
+};
+use crate::{
+    gfx::load_image_pixels,
+    level::{generate_level::ceiling_gap, traversal::room_passable, Level, Tile, ROOM_SIZE},
+};
+
+//Returns the problems with the pixels of a PNG template
+fn validate_png(path: &str) -> Result<Vec<String>, String> {
+    let (pixels, info) = load_image_pixels(path)?;
+    let mut problems = vec![];
+
+    if info.width != ROOM_SIZE || info.height != ROOM_SIZE {
+        problems.push(format!(
+            "image is {}x{}, expected {ROOM_SIZE}x{ROOM_SIZE}",
+            info.width, info.height
+        ));
+    }
+
+    for (i, pixel) in pixels.iter().enumerate() {
+        if pixel_to_tile(*pixel).is_none()
+            && pixel_to_spawn(*pixel).is_none()
+            && pixel_to_background_tile(*pixel).is_none()
+        {
+            //Pixels are of the format alpha, b, g, r
+            let (r, g, b, a) = (
+                pixel & 0xff,
+                (pixel >> 8) & 0xff,
+                (pixel >> 16) & 0xff,
+                pixel >> 24,
+            );
+            problems.push(format!(
+                "unknown colour rgba({r}, {g}, {b}, {a}) at pixel ({}, {})",
+                i as u32 % info.width,
+                i as u32 / info.width
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+//Builds a tower with the template as the second room
+fn tower_level(template: &RoomTemplate) -> Level {
+    let mut level = Level::new(ROOM_SIZE + 2, 2 * (ROOM_SIZE + 1) + 1);
+    for x in 0..ROOM_SIZE {
+        for y in 0..ROOM_SIZE {
+            level.set_tile(x + 1, y + ROOM_SIZE + 2, template.get_tile(x, y));
+        }
+    }
+
+    for x in ceiling_gap() {
+        level.set_tile(x, ROOM_SIZE + 1, Tile::Air);
+        level.set_tile(x, 2 * (ROOM_SIZE + 1), Tile::Air);
+    }
+
+    level
+}
+
+//Returns true if the player can get from the gap in the floor of the
+//room to the gap in the ceiling
+pub fn ceiling_reachable(template: &RoomTemplate) -> bool {
+    room_passable(&tower_level(template), 0, 1)
+}
+
+//Returns the problems with a template, the template is valid if
+//the list is empty
+pub fn validate_template(path: &str) -> Vec<String> {
+    let mut problems = vec![];
+
+    if !path.ends_with(".room") {
+        match validate_png(path) {
+            Ok(png_problems) => problems.extend(png_problems),
+            Err(msg) => return vec![msg],
+        }
+    }
+
+    match RoomTemplate::load(path) {
+        Ok(template) => {
+            if !ceiling_reachable(&template) {
+                problems.push("the ceiling can not be reached from the floor".to_string());
+            }
+        }
+        Err(msg) => problems.push(msg),
+    }
+
+    problems
+}
+
+//Validates every template in the template list of a directory and
+//returns the path of each template that has problems with its problems
+pub fn validate_templates(path: &str) -> Vec<(String, Vec<String>)> {
+    read_template_list(path)
+        .iter()
+        .map(|entry| {
+            let mut problems = validate_template(&entry.path);
+            if let Err(msg) = load_template_list_entry(entry) {
+                if !problems.contains(&msg) {
+                    problems.push(msg);
+                }
+            }
+            (entry.path.clone(), problems)
+        })
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Creates a room from the bottom rows of tiles, the rows above are empty
+    fn room_with_rows(rows: &[&str]) -> RoomTemplate {
+        let mut text = String::from("tiles\n");
+        for _ in rows.len()..(ROOM_SIZE as usize) {
+            text.push_str("................\n");
+        }
+        for row in rows {
+            text.push_str(row);
+            text.push('\n');
+        }
+        RoomTemplate::from_text(&text).expect("failed to parse template")
+    }
+
+    #[test]
+    fn test_included_templates_are_valid() {
+        let problems = validate_templates("assets/room_templates");
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn test_ceiling_reachable() {
+        //An empty room can not be climbed
+        assert!(!ceiling_reachable(&room_with_rows(&[])));
+
+        //A ladder from the floor to the ceiling
+        let ladder = vec![".......H........"; ROOM_SIZE as usize];
+        assert!(ceiling_reachable(&room_with_rows(&ladder)));
+
+        //Lava at the bottom of the ladder
+        let mut blocked = ladder.clone();
+        blocked[ROOM_SIZE as usize - 1] = "......~~~~......";
+        blocked[ROOM_SIZE as usize - 2] = "......~~~~......";
+        blocked[ROOM_SIZE as usize - 3] = "......~~~~......";
+        assert!(!ceiling_reachable(&room_with_rows(&blocked)));
+    }
+}
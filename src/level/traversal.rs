@@ -0,0 +1,453 @@
+use super::{generate_level::ceiling_gap, transparent, Level, Tile, ROOM_SIZE};
+use crate::{
+    game::{
+        player::{PLAYER_CLIMB_SPEED, PLAYER_JUMP_SPEED, PLAYER_SPEED},
+        GRAVITY, PLAYER_HEIGHT, PLAYER_WIDTH, SIM_TIMESTEP,
+    },
+    sprite::Sprite,
+};
+use cgmath::{vec2, Vector2};
+
+/*
+ * Reachability analysis of a level based on how the player can move.
+ * Jumps and falls are simulated with the same physics as the player using
+ * a few different ways of holding left or right in the air, while walking
+ * and climbing ladders are done one tile at a time. The analysis is kept
+ * conservative so that anything marked as reachable can actually be
+ * reached in the game without touching lava or falling onto spikes.
+ * */
+
+//Number of simulation steps of the game that are simulated at once
+//in the air, the player moves along the same curve but the search is
+//a lot faster
+const TICKS_PER_STEP: u32 = 2;
+//Number of simulation steps between the different times the
+//player starts or stops holding left or right during a jump
+const INPUT_STEP: u32 = 16;
+//Latest time the player starts or stops holding left or right
+const MAX_INPUT_TIME: u32 = 64;
+//Longest a single jump or fall is simulated for
+const MAX_AIR_TIME: u32 = 600;
+//How far from the center of a tile the player can stand while still
+//being supported by it, used to jump from the edges of platforms
+const EDGE_OFFSET: f32 = 0.5 + PLAYER_WIDTH / 2.0 - 0.06;
+
+//Returns true if the player can be inside of the tile
+pub(super) fn passable(level: &Level, x: i32, y: i32) -> bool {
+    if level.out_of_bounds(x, y) {
+        return false;
+    }
+
+    let tile = level.get_tile(x as u32, y as u32);
+    transparent(tile) && tile != Tile::Lava && tile != Tile::Spikes
+}
+
+pub(super) fn is_ladder(level: &Level, x: i32, y: i32) -> bool {
+    !level.out_of_bounds(x, y) && level.get_tile(x as u32, y as u32) == Tile::Ladder
+}
+
+//Returns true if there is a solid tile below that the player can jump from
+pub(super) fn on_solid_ground(level: &Level, x: i32, y: i32) -> bool {
+    !level.out_of_bounds(x, y - 1) && !transparent(level.get_tile(x as u32, y as u32 - 1))
+}
+
+//Returns true if the player can stay in the tile without falling,
+//the tile above a ladder supports the player but they can not jump from it
+pub(super) fn supported(level: &Level, x: i32, y: i32) -> bool {
+    is_ladder(level, x, y) || on_solid_ground(level, x, y) || is_ladder(level, x, y - 1)
+}
+
+//Returns the tile of the ladder that the sprite is touching
+fn touching_ladder(sprite: &Sprite, level: &Level) -> Option<(i32, i32)> {
+    let (center_x, center_y) = (
+        sprite.position.x.round() as i32,
+        sprite.position.y.round() as i32,
+    );
+    for x in (center_x - 1)..=(center_x + 1) {
+        for y in (center_y - 1)..=(center_y + 1) {
+            let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
+            if is_ladder(level, x, y) && sprite.intersecting(&hitbox) {
+                return Some((x, y));
+            }
+        }
+    }
+    None
+}
+
+//Returns true if there is a ladder, lava or spikes in any of the tiles
+//around the tile the player is in, the player can only touch these tiles
+fn near_special_tile(level: &Level, x: i32, y: i32) -> bool {
+    ((x - 1)..=(x + 1)).any(|x| {
+        ((y - 1)..=(y + 1)).any(|y| {
+            !level.out_of_bounds(x, y)
+                && matches!(
+                    level.get_tile(x as u32, y as u32),
+                    Tile::Ladder | Tile::Lava | Tile::Spikes
+                )
+        })
+    })
+}
+
+//Position of the player when they are standing in a tile
+fn standing_position(x: i32, y: i32) -> Vector2<f32> {
+    vec2(x as f32, y as f32 - 0.5 + PLAYER_HEIGHT / 2.0)
+}
+
+pub struct Reachability {
+    width: i32,
+    height: i32,
+    reachable: Vec<bool>,
+}
+
+impl Reachability {
+    pub fn is_reachable(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return false;
+        }
+
+        self.reachable[(y * self.width + x) as usize]
+    }
+
+    fn mark(&mut self, x: i32, y: i32) {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.reachable[(y * self.width + x) as usize] = true;
+        }
+    }
+}
+
+//The direction the player holds while in the air and the
+//simulation steps between which it is held
+struct AirInput {
+    direction: f32,
+    start: u32,
+    end: u32,
+}
+
+//Every way of holding left or right that is tried in the air
+fn air_inputs() -> Vec<AirInput> {
+    let mut inputs = vec![AirInput {
+        direction: 0.0,
+        start: 0,
+        end: 0,
+    }];
+
+    for direction in [-1.0, 1.0] {
+        for time in (0..=MAX_INPUT_TIME).step_by(INPUT_STEP as usize) {
+            //Start holding the direction late
+            inputs.push(AirInput {
+                direction,
+                start: time,
+                end: u32::MAX,
+            });
+            //Let go of the direction early
+            if time > 0 {
+                inputs.push(AirInput {
+                    direction,
+                    start: 0,
+                    end: time,
+                });
+            }
+        }
+    }
+
+    inputs
+}
+
+//State of the search through the tiles the player can reach
+struct Search<'a> {
+    level: &'a Level,
+    reachability: Reachability,
+    //Tiles the player can stay in, these are the tiles that
+    //the player can start moving from
+    resting: Vec<bool>,
+    //Resting tiles that still need to be searched from and
+    //whether the player can always jump from them
+    stack: Vec<(i32, i32, bool)>,
+    air_inputs: Vec<AirInput>,
+    //Tiles that are next to a ladder, lava or spikes
+    near_special: Vec<bool>,
+}
+
+impl Search<'_> {
+    fn rest(&mut self, x: i32, y: i32, can_jump: bool) {
+        if self.level.out_of_bounds(x, y) {
+            return;
+        }
+
+        let index = (y * self.reachability.width + x) as usize;
+        self.reachability.mark(x, y);
+        if !self.resting[index] {
+            self.resting[index] = true;
+            self.stack.push((x, y, can_jump));
+        }
+    }
+
+    //Simulates the player moving through the air until they land,
+    //grab onto a ladder or touch something that hurts them and returns
+    //the number of simulation steps the player was in the air for
+    fn simulate_air(&mut self, position: Vector2<f32>, velocity_y: f32, input: usize) -> u32 {
+        let level = self.level;
+        let input = &self.air_inputs[input];
+        let (direction, start, end) = (input.direction, input.start, input.end);
+        let mut sprite = Sprite::new(position.x, position.y, PLAYER_WIDTH, PLAYER_HEIGHT);
+        sprite.velocity.y = velocity_y;
+        let start_ladder = touching_ladder(&sprite, level);
+
+        for tick in (0..MAX_AIR_TIME).step_by(TICKS_PER_STEP as usize) {
+            let dt = SIM_TIMESTEP * TICKS_PER_STEP as f32;
+            if tick >= start && tick < end {
+                sprite.sweep_x(direction * PLAYER_SPEED * dt, level);
+            }
+
+            let mut dy = sprite.velocity.y / 2.0 * dt;
+            sprite.velocity.y -= GRAVITY * dt;
+            dy += sprite.velocity.y / 2.0 * dt;
+            let collision = sprite.sweep_y(dy, level);
+
+            let (x, y) = (
+                sprite.position.x.round() as i32,
+                sprite.position.y.round() as i32,
+            );
+            if level.out_of_bounds(x, y) {
+                return tick;
+            }
+
+            //Spikes only hurt the player if they fall onto them
+            let near_special = self.near_special[(y * self.reachability.width + x) as usize];
+            if near_special
+                && (sprite.touching_tile(Tile::Lava, level)
+                    || (sprite.touching_tile(Tile::Spikes, level)
+                        && sprite.velocity.y <= -PLAYER_CLIMB_SPEED))
+            {
+                return tick;
+            }
+            self.reachability.mark(x, y);
+
+            let ladder = if near_special {
+                touching_ladder(&sprite, level)
+            } else {
+                None
+            };
+            if let Some((ladder_x, ladder_y)) =
+                ladder.filter(|&ladder| Some(ladder) != start_ladder)
+            {
+                self.rest(ladder_x, ladder_y, false);
+                return tick;
+            }
+
+            match collision {
+                Some(collision) if collision.normal.y > 0.0 => {
+                    self.rest(collision.tile_x, collision.tile_y + 1, false);
+                    return tick;
+                }
+                Some(_) => sprite.velocity.y = 0.0,
+                None => {}
+            }
+        }
+
+        MAX_AIR_TIME
+    }
+
+    //Simulates every air input that holds one of `directions`, inputs that
+    //start holding a direction late are only tried if `late_start` is true
+    fn simulate_inputs(
+        &mut self,
+        position: Vector2<f32>,
+        velocity_y: f32,
+        directions: &[f32],
+        late_start: bool,
+    ) {
+        let no_input_time = self.simulate_air(position, velocity_y, 0);
+        let mut held_time = 0;
+        for input in 1..self.air_inputs.len() {
+            let (direction, start, end) = {
+                let air_input = &self.air_inputs[input];
+                (air_input.direction, air_input.start, air_input.end)
+            };
+            //Inputs that only change after the player has landed are the
+            //same as holding the direction the whole time or not at all
+            if !directions.contains(&direction)
+                || (start > 0 && (!late_start || start >= no_input_time))
+                || (end != u32::MAX && end >= held_time)
+            {
+                continue;
+            }
+
+            let time = self.simulate_air(position, velocity_y, input);
+            if start == 0 && end == u32::MAX {
+                held_time = time;
+            }
+        }
+    }
+
+    fn jump(&mut self, x: i32, y: i32) {
+        let position = standing_position(x, y);
+        self.simulate_inputs(position, PLAYER_JUMP_SPEED, &[-1.0, 1.0], true);
+        //Also jump from the edges of the platform if there are any
+        for dx in [-1, 1] {
+            if passable(self.level, x + dx, y) && !supported(self.level, x + dx, y) {
+                let edge = position + vec2(dx as f32 * EDGE_OFFSET, 0.0);
+                self.simulate_inputs(edge, PLAYER_JUMP_SPEED, &[dx as f32], true);
+            }
+        }
+    }
+
+    //Climbs off of the top of a ladder, the player keeps moving up at the
+    //speed they were climbing at once they are no longer on the ladder
+    fn climb_off_top(&mut self, x: i32, y: i32) {
+        let position = standing_position(x, y + 1);
+        self.simulate_inputs(position, PLAYER_CLIMB_SPEED, &[-1.0, 1.0], true);
+    }
+
+    //Walks or climbs sideways into the next tile, if there is nothing
+    //there to stand on then the player falls
+    fn step_sideways(&mut self, x: i32, y: i32, dx: i32) {
+        if !passable(self.level, x + dx, y) {
+            return;
+        }
+
+        if supported(self.level, x + dx, y) {
+            self.rest(x + dx, y, false);
+            return;
+        }
+
+        let mut position = if is_ladder(self.level, x, y) {
+            vec2(x as f32, y as f32)
+        } else {
+            standing_position(x, y)
+        };
+        position.x += dx as f32 * (0.5 + PLAYER_WIDTH / 2.0 + 0.01);
+        self.simulate_inputs(position, 0.0, &[dx as f32], false);
+    }
+}
+
+//Returns every tile the player can be in when starting from any of the
+//tiles in `start`, the player can jump from the starting tiles
+pub fn reachable_tiles(level: &Level, start: &[(i32, i32)]) -> Reachability {
+    let mut search = Search {
+        level,
+        reachability: Reachability {
+            width: level.w() as i32,
+            height: level.h() as i32,
+            reachable: vec![false; (level.w() * level.h()) as usize],
+        },
+        resting: vec![false; (level.w() * level.h()) as usize],
+        stack: vec![],
+        air_inputs: air_inputs(),
+        near_special: (0..(level.w() * level.h()) as i32)
+            .map(|i| near_special_tile(level, i % level.w() as i32, i / level.w() as i32))
+            .collect(),
+    };
+
+    for &(x, y) in start {
+        search.rest(x, y, true);
+    }
+
+    while let Some((x, y, can_jump)) = search.stack.pop() {
+        search.step_sideways(x, y, -1);
+        search.step_sideways(x, y, 1);
+
+        //Climb up and down ladders
+        if is_ladder(level, x, y) && passable(level, x, y + 1) {
+            search.rest(x, y + 1, false);
+            if !is_ladder(level, x, y + 1) {
+                search.climb_off_top(x, y);
+            }
+        }
+        if (is_ladder(level, x, y) || is_ladder(level, x, y - 1)) && passable(level, x, y - 1) {
+            if supported(level, x, y - 1) {
+                search.rest(x, y - 1, false);
+            } else {
+                search.simulate_air(vec2(x as f32, y as f32 - 1.0), 0.0, 0);
+            }
+        }
+
+        if can_jump || (on_solid_ground(level, x, y) && !is_ladder(level, x, y)) {
+            search.jump(x, y);
+        }
+    }
+
+    search.reachability
+}
+
+//Returns true if the player can get from the entrance of a room in a tower
+//to the gap in its ceiling. The rooms on the bottom floor are entered at the
+//bottom left corner, which is where the player starts or the doorway from the
+//room on the left, and the other rooms are entered through the gap in their
+//floor. The player is assumed to be able to jump from the gap in the floor
+//since they are coming from the room below. Only the room itself is searched
+//so the rooms around it do not change the result.
+pub fn room_passable(level: &Level, room_x: u32, room_y: u32) -> bool {
+    let (left, bottom) = (room_x * (ROOM_SIZE + 1), room_y * (ROOM_SIZE + 1));
+    let mut room = Level::new(ROOM_SIZE + 2, ROOM_SIZE + 2);
+    for x in 1..=ROOM_SIZE {
+        for y in 0..(ROOM_SIZE + 2) {
+            room.set_tile(x, y, level.get_tile(left + x, bottom + y));
+        }
+    }
+
+    let entrance: Vec<(i32, i32)> = if room_y == 0 {
+        vec![(1, 1)]
+    } else {
+        ceiling_gap().map(|x| (x as i32, 0)).collect()
+    };
+    let start: Vec<(i32, i32)> = entrance
+        .into_iter()
+        .filter(|&(x, y)| passable(&room, x, y))
+        .collect();
+    let reachability = reachable_tiles(&room, &start);
+    ceiling_gap().any(|x| reachability.is_reachable(x as i32, ROOM_SIZE as i32 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Creates an empty room with walls on the sides and a floor at y = 0
+    fn empty_level() -> Level {
+        let mut level = Level::new(12, 12);
+        for x in 1..11 {
+            for y in 1..12 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+        level
+    }
+
+    #[test]
+    fn test_jump_onto_platforms() {
+        let mut level = empty_level();
+        //Platform 2 tiles above the floor can be reached
+        level.set_tile(3, 2, Tile::Brick);
+        //Platform 4 tiles above the floor is too high and too far
+        //away from the other platform to be reached
+        level.set_tile(9, 4, Tile::Brick);
+        let reachability = reachable_tiles(&level, &[(1, 1)]);
+        assert!(reachability.is_reachable(10, 1));
+        assert!(reachability.is_reachable(3, 3));
+        assert!(!reachability.is_reachable(9, 5));
+        //A step in between makes the higher platform reachable
+        level.set_tile(6, 3, Tile::Brick);
+        let reachability = reachable_tiles(&level, &[(1, 1)]);
+        assert!(reachability.is_reachable(9, 5));
+    }
+
+    #[test]
+    fn test_ladders_and_hazards() {
+        let mut level = empty_level();
+        for y in 1..10 {
+            level.set_tile(2, y, Tile::Ladder);
+        }
+        //Ledge at the top of the ladder
+        for x in 3..11 {
+            level.set_tile(x, 9, Tile::Brick);
+        }
+        //Lava blocks the floor
+        level.set_tile(6, 1, Tile::Lava);
+        let reachability = reachable_tiles(&level, &[(1, 1)]);
+        assert!(reachability.is_reachable(10, 10));
+        assert!(!reachability.is_reachable(6, 1));
+        //The player can jump over the lava
+        assert!(reachability.is_reachable(10, 1));
+    }
+}
@@ -4,7 +4,12 @@ use crate::game::Player;
 use crate::sprite::Sprite;
 
 impl Level {
-    pub fn update_interactive_tiles(&mut self, player: &mut Player, sfx_player: &SfxPlayer) {
+    //Returns the type of the tile that the player picked up, if any
+    pub fn update_interactive_tiles(
+        &mut self,
+        player: &mut Player,
+        sfx_player: &SfxPlayer,
+    ) -> Option<InteractiveTile> {
         let mut delete_index = None;
 
         for (i, tile) in self.interactive_tiles.iter().enumerate() {
@@ -57,8 +62,6 @@ impl Level {
             }
         }
 
-        if let Some(i) = delete_index {
-            self.interactive_tiles.remove(i);
-        }
+        delete_index.map(|i| self.interactive_tiles.remove(i).tile_type)
     }
 }
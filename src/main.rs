@@ -8,20 +8,92 @@ mod audio;
 mod game;
 mod gfx;
 mod level;
+mod parse;
 mod shader;
 mod sprite;
 mod ui;
 
 use crate::audio::sfx_ids;
 use audio::SfxPlayer;
-use cgmath::Matrix4;
-use game::{hiscore, GameScreen, State};
+use cgmath::{Matrix4, Vector2};
+use game::{
+    gamepad::{GamepadInput, GlfwGamepads},
+    hiscore::{self, HighScore},
+    input_config::{self, Action},
+    profile::{self, Profile},
+    replay::{Replay, ReplayPlayer},
+    settings::{self, Settings},
+    stats, GameScreen, State, SIM_TIMESTEP,
+};
 use glfw::Context;
-use level::room_template;
-use level::Level;
-use sprite::Sprite;
+use level::display_level::LevelRenderer;
+use level::room_template::{self, RoomTemplate};
+use level::{difficulty::Difficulty, generate_level::TowerSize, Level};
+use sprite::{enemy::definition, Sprite};
 use std::{sync::mpsc::Receiver, time::Instant};
 
+//Maximum number of simulation steps run in a single frame, if the game
+//falls further behind than this then it slows down instead of trying
+//to catch up (which would make the next frame take even longer)
+const MAX_STEPS_PER_FRAME: u32 = 8;
+//Longest amount of time (in seconds) a single frame can add to the simulation
+const MAX_FRAME_TIME: f32 = 0.25;
+//Distance (in tiles) the camera moves when the screen shakes
+const SCREEN_SHAKE_STRENGTH: f32 = 0.15;
+//Position of the window when it leaves fullscreen
+const WINDOWED_POSITION: (i32, i32) = (100, 100);
+
+//Every menu in the game
+struct Menus {
+    main: ui::Menu,
+    pause: ui::Menu,
+    gameover: ui::Menu,
+    hiscore: ui::Menu,
+    win: ui::Menu,
+    about: ui::Menu,
+    statistics: ui::Menu,
+    achievements: ui::Menu,
+    controls: ui::Menu,
+    settings: ui::Menu,
+    new_profile: ui::Menu,
+}
+
+impl Menus {
+    fn create() -> Self {
+        Self {
+            main: ui::Menu::create_main_menu(),
+            pause: ui::Menu::create_pause_menu(),
+            gameover: ui::Menu::create_gameover_menu(),
+            hiscore: ui::Menu::create_hiscore_menu(),
+            win: ui::Menu::create_win_screen(),
+            about: ui::Menu::create_about_screen(),
+            statistics: ui::Menu::create_statistics_screen(),
+            achievements: ui::Menu::create_achievements_screen(),
+            controls: ui::Menu::create_controls_screen(),
+            settings: ui::Menu::create_settings_screen(),
+            new_profile: ui::Menu::create_new_profile_screen(),
+        }
+    }
+
+    //Returns the menu that is shown on a screen
+    fn get(&mut self, screen: GameScreen) -> Option<&mut ui::Menu> {
+        match screen {
+            GameScreen::Game => None,
+            GameScreen::MainMenu => Some(&mut self.main),
+            GameScreen::Paused => Some(&mut self.pause),
+            GameScreen::GameOver => Some(&mut self.gameover),
+            GameScreen::HighScores => Some(&mut self.hiscore),
+            GameScreen::WinScreen => Some(&mut self.win),
+            GameScreen::AboutScreen => Some(&mut self.about),
+            GameScreen::StatisticsScreen => Some(&mut self.statistics),
+            GameScreen::AchievementsScreen => Some(&mut self.achievements),
+            GameScreen::ControlsScreen => Some(&mut self.controls),
+            GameScreen::Settings => Some(&mut self.settings),
+            GameScreen::NewProfile => Some(&mut self.new_profile),
+        }
+    }
+}
+
 fn get_glfw_window_info(window: &glfw::Window) -> ui::WindowInfo {
     let (mouse_x, mouse_y) = window.get_cursor_pos();
     let (win_w, win_h) = window.get_size();
@@ -33,16 +105,100 @@ fn get_glfw_window_info(window: &glfw::Window) -> ui::WindowInfo {
     }
 }
 
+//Returns the position of the camera, the camera follows the player but
+//stops at the sides of towers that are wider than the window
+fn camera_position(state: &State, alpha: f32, win_info: &ui::WindowInfo) -> Vector2<f32> {
+    let mut position = state.player.player_spr.interpolated_position(alpha);
+    //Half of the width of the level that can be seen, this
+    //depends on the vertical field of view of the perspective matrix
+    let half_fov = state.settings.fov as f32 / 2.0;
+    let half_width =
+        -level::LEVEL_Z * half_fov.to_radians().tan() * win_info.win_w / win_info.win_h;
+    let (min_x, max_x) = (half_width - 0.5, state.level.w() as f32 - 0.5 - half_width);
+    if min_x < max_x {
+        position.x = position.x.clamp(min_x, max_x);
+    }
+    position
+}
+
 //Handle window resizing
 fn handle_window_resize(w: i32, h: i32, state: &mut State) {
     unsafe {
         gl::Viewport(0, 0, w, h);
     }
     //Update the perspective matrix
-    state.perspective = cgmath::perspective(cgmath::Deg(75.0), w as f32 / h as f32, 0.1, 1000.0)
+    state.perspective = state.settings.perspective(w as f32 / h as f32)
+}
+
+//Offset of the camera when the screen shakes after the player is hit,
+//this only moves the camera so it does not change the simulation
+fn screen_shake(state: &State, animation_timer: f32) -> Vector2<f32> {
+    if !state.settings.screen_shake || state.player.damage_cooldown <= 0.0 {
+        return Vector2::new(0.0, 0.0);
+    }
+
+    let strength = SCREEN_SHAKE_STRENGTH * state.player.damage_cooldown / game::DAMAGE_COOLDOWN;
+    Vector2::new(
+        (animation_timer * 97.0).sin() * strength,
+        (animation_timer * 71.0).cos() * strength,
+    )
+}
+
+//Applies the settings of the state that changed since they were last applied
+//to the window and audio, `applied` is None the first time
+fn apply_settings(
+    applied: Option<&Settings>,
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    state: &mut State,
+    sfx_player: &mut SfxPlayer,
+) {
+    let settings = state.settings;
+    sfx_player.set_volume(settings.effects_volume());
+    if settings.vsync {
+        glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
+    } else {
+        glfw.set_swap_interval(glfw::SwapInterval::None);
+    }
+
+    let window_changed = applied.is_none_or(|applied| {
+        applied.fullscreen != settings.fullscreen || applied.resolution != settings.resolution
+    });
+    if window_changed && settings.fullscreen {
+        //Fullscreen uses the current video mode of the monitor
+        glfw.with_primary_monitor(|_, monitor| {
+            if let Some(monitor) = monitor {
+                if let Some(mode) = monitor.get_video_mode() {
+                    window.set_monitor(
+                        glfw::WindowMode::FullScreen(monitor),
+                        0,
+                        0,
+                        mode.width,
+                        mode.height,
+                        Some(mode.refresh_rate),
+                    );
+                }
+            }
+        });
+    } else if window_changed {
+        //Keep the window where it is unless it is leaving fullscreen
+        let (xpos, ypos) = window.with_window_mode(|mode| match mode {
+            glfw::WindowMode::Windowed => window.get_pos(),
+            glfw::WindowMode::FullScreen(_) => WINDOWED_POSITION,
+        });
+        let (width, height) = settings.resolution;
+        window.set_monitor(glfw::WindowMode::Windowed, xpos, ypos, width, height, None);
+    }
+
+    //The field of view may have changed
+    let (width, height) = window.get_framebuffer_size();
+    if width > 0 && height > 0 {
+        handle_window_resize(width, height, state);
+    }
 }
 
-//Handle key input
+//Handle key input, returns the action of a key that was pressed
+//so that it can be used to navigate the menu on the current screen
 fn handle_key_input(
     key: glfw::Key,
     _scancode: glfw::Scancode,
@@ -50,21 +206,49 @@ fn handle_key_input(
     _modifiers: glfw::Modifiers,
     state: &mut State,
     sfx_player: &SfxPlayer,
-) {
+) -> Option<Action> {
     let key_id = key as i32;
+    if key == glfw::Key::Backspace && action != glfw::Action::Release {
+        state.handle_backspace();
+    }
+
+    //Letters and digits are only used to type in the name of a new
+    //profile, even if they are bound to an action
+    let typing_key = (glfw::Key::A as i32..=glfw::Key::Z as i32).contains(&key_id)
+        || (glfw::Key::Num0 as i32..=glfw::Key::Num9 as i32).contains(&key_id)
+        || key == glfw::Key::Minus;
+    if state.game_screen == GameScreen::NewProfile && typing_key {
+        return None;
+    }
+
     if action == glfw::Action::Press {
-        state.handle_key_press(key_id, sfx_player);
+        //Keys pressed while waiting for a key to bind are not used in menus
+        let capturing = state.rebinding.is_some() && !state.controls_gamepad;
+        let bound_action = state.handle_key_press(key_id, sfx_player);
+        if capturing {
+            return None;
+        }
+        //Enter picks the focused button just like the Attack action
+        if key == glfw::Key::Enter {
+            return Some(Action::Attack);
+        }
+        return bound_action;
     } else if action == glfw::Action::Release {
-        state.handle_key_release(key_id);
+        state.handle_key_release(key_id, sfx_player);
     }
+
+    None
 }
 
+//Handles the events of the window and returns the actions that were
+//pressed along with the screen they were pressed on
 fn process_events(
     _window: &mut glfw::Window,
     events: &Receiver<(f64, glfw::WindowEvent)>,
     state: &mut State,
     sfx_player: &SfxPlayer,
-) {
+) -> Vec<(GameScreen, Action)> {
+    let mut menu_actions = vec![];
     for (_, event) in glfw::flush_messages(events) {
         match event {
             //Window resize
@@ -73,33 +257,194 @@ fn process_events(
             }
             //Key input
             glfw::WindowEvent::Key(key, scancode, action, modifiers) => {
-                handle_key_input(key, scancode, action, modifiers, state, sfx_player);
+                let screen = state.game_screen;
+                if let Some(action) =
+                    handle_key_input(key, scancode, action, modifiers, state, sfx_player)
+                {
+                    menu_actions.push((screen, action));
+                }
             }
+            //Text input
+            glfw::WindowEvent::Char(ch) => state.handle_text_input(ch),
             _ => {}
         }
     }
+    menu_actions
 }
 
-fn process_button_action(button_action: ui::ButtonAction, state: &mut State) {
+fn process_button_action(
+    button_action: ui::ButtonAction,
+    state: &mut State,
+    level_renderer: &mut LevelRenderer,
+) {
+    //Leaving a run from the pause menu saves it so that it can be continued
+    let leaves_run = matches!(
+        button_action,
+        ui::ButtonAction::QuitGame | ui::ButtonAction::GotoMainMenu
+    );
+    if state.game_screen == GameScreen::Paused && state.playback.is_none() && leaves_run {
+        if let Err(msg) = state.save_run(&state.profile.path(profile::SAVE_FILE)) {
+            eprintln!("failed to save game: {msg}");
+        }
+        state.save_stats();
+    }
+
+    //Clicking any button stops waiting for a key to bind
+    state.rebinding = None;
     match button_action {
         ui::ButtonAction::QuitGame => {
             std::process::exit(0);
         }
         ui::ButtonAction::GotoMainMenu => state.game_screen = GameScreen::MainMenu,
+        ui::ButtonAction::ContinueGame => continue_game(state, level_renderer),
         ui::ButtonAction::GotoHighScores => state.game_screen = GameScreen::HighScores,
+        ui::ButtonAction::SortHighScores => state.highscore_sort = state.highscore_sort.next(),
+        ui::ButtonAction::NextProfile => {
+            if let Some(name) = profile::next_profile_name(&profile::list(), &state.profile.name) {
+                switch_profile(state, &name);
+            }
+        }
+        ui::ButtonAction::GotoNewProfile => {
+            state.profile_name_input.clear();
+            state.profile_message.clear();
+            state.game_screen = GameScreen::NewProfile;
+        }
+        ui::ButtonAction::CreateProfile => create_profile(state),
         ui::ButtonAction::GotoAbout => state.game_screen = GameScreen::AboutScreen,
+        ui::ButtonAction::GotoStatistics => state.game_screen = GameScreen::StatisticsScreen,
+        ui::ButtonAction::GotoAchievements => state.game_screen = GameScreen::AchievementsScreen,
+        ui::ButtonAction::GotoControls => {
+            state.controls_message = String::new();
+            state.game_screen = GameScreen::ControlsScreen;
+        }
+        ui::ButtonAction::RebindAction(action_index) => state.rebinding = Some(action_index),
+        ui::ButtonAction::ClearBinding(action_index) => state.clear_binding(action_index),
+        ui::ButtonAction::SwitchControlsDevice => {
+            state.controls_message = String::new();
+            state.controls_gamepad = !state.controls_gamepad;
+        }
+        ui::ButtonAction::ResetControls => state.reset_controls(),
+        ui::ButtonAction::GotoSettings => state.game_screen = GameScreen::Settings,
+        ui::ButtonAction::ChangeSetting(setting_index, direction) => {
+            state.change_setting(setting_index, direction)
+        }
         ui::ButtonAction::StartGame => {
-            let persp_matrix = state.perspective;
-            *state = State::starting_state();
-            state.perspective = persp_matrix;
-            state.game_screen = GameScreen::Game;
-            //Attempt to load room templates
-            let room_templates = room_template::load_room_templates("assets/room_templates");
-            let (level, enemies) = Level::generate_level(&room_templates);
-            state.level = level;
-            state.enemies = enemies;
-            state.level.build_chunks();
+            let seed = state.choose_seed();
+            state.difficulty = state.settings.difficulty;
+            start_game(state, seed, level_renderer);
+        }
+    }
+}
+
+//Plays the select sound and does the action of a button that was
+//clicked or picked with a gamepad
+fn activate_button(
+    button_action: ui::ButtonAction,
+    state: &mut State,
+    level_renderer: &mut LevelRenderer,
+    highscores: &mut Vec<HighScore>,
+    sfx_player: &SfxPlayer,
+) {
+    sfx_player.play(sfx_ids::SELECT);
+    let profile = state.profile.clone();
+    process_button_action(button_action, state, level_renderer);
+    //Every profile has its own high scores
+    if state.profile != profile {
+        *highscores = hiscore::load_highscores(&state.profile.path(profile::HISCORES_FILE));
+    }
+}
+
+//Resets the state and generates a new tower from the seed
+fn start_game(state: &mut State, seed: u64, level_renderer: &mut LevelRenderer) {
+    let persp_matrix = state.perspective;
+    let seed_input = state.seed_input.clone();
+    let tower_size = state.tower_size;
+    let difficulty = state.difficulty;
+    let profile = state.profile.clone();
+    let player_name = state.player_name.clone();
+    let highscore_sort = state.highscore_sort;
+    let stats = state.stats.clone();
+    *state = State::starting_state();
+    state.perspective = persp_matrix;
+    state.seed_input = seed_input;
+    state.tower_size = tower_size;
+    state.difficulty = difficulty;
+    state.set_profile(profile);
+    state.stats = stats;
+    state.player_name = player_name;
+    state.highscore_sort = highscore_sort;
+    state.seed = seed;
+    state.replay = Replay::new(seed, tower_size, difficulty);
+    state.game_screen = GameScreen::Game;
+    //Attempt to load room templates
+    let room_templates = room_template::load_room_templates("assets/room_templates");
+    let (level, enemies) = Level::generate_level(&room_templates, seed, tower_size, difficulty);
+    state.level = level;
+    state.enemies = enemies;
+    *level_renderer = LevelRenderer::build(&state.level);
+}
+
+//Restores the saved run, the save is removed once the run is continued
+//and is saved again if the run is left from the pause menu again
+fn continue_game(state: &mut State, level_renderer: &mut LevelRenderer) {
+    let room_templates = room_template::load_room_templates("assets/room_templates");
+    let save_path = state.profile.path(profile::SAVE_FILE);
+    match state.load_run(&save_path, &room_templates) {
+        Ok(()) => {
+            *level_renderer = LevelRenderer::build(&state.level);
+            if let Err(msg) = std::fs::remove_file(&save_path) {
+                eprintln!("failed to remove saved game: {msg}");
+            }
+        }
+        Err(msg) => eprintln!("failed to load saved game: {msg}"),
+    }
+}
+
+//Switches to another profile and remembers it for the next time the game starts
+fn switch_profile(state: &mut State, name: &str) {
+    match Profile::open(name) {
+        Ok(profile) => {
+            state.save_stats();
+            if let Err(msg) = profile.set_last() {
+                eprintln!("failed to remember profile: {msg}");
+            }
+            state.set_profile(profile);
+        }
+        Err(msg) => eprintln!("failed to open profile {name}: {msg}"),
+    }
+}
+
+//Creates a profile with the name typed in on the new profile screen and
+//switches to it, the reason is shown if the profile can not be created
+fn create_profile(state: &mut State) {
+    let name = state.profile_name_input.clone();
+    if let Err(msg) = profile::validate_name(&name) {
+        state.profile_message = msg;
+        return;
+    }
+    if profile::list().contains(&name) {
+        state.profile_message = format!("the profile {name} already exists");
+        return;
+    }
+
+    switch_profile(state, &name);
+    if state.profile.name == name {
+        state.game_screen = GameScreen::MainMenu;
+    } else {
+        state.profile_message = format!("failed to create the profile {name}");
+    }
+}
+
+//Opens the profile passed on the command line with `--profile <name>`,
+//otherwise opens the last profile that was used
+fn profile_from_args() -> Result<Profile, String> {
+    match arg_value("--profile") {
+        Some(name) => {
+            let profile = Profile::open(&name)?;
+            profile.set_last()?;
+            Ok(profile)
         }
+        None => Profile::open_last(),
     }
 }
 
@@ -116,7 +461,127 @@ fn load_icon(window: &mut glfw::Window) {
     }
 }
 
+//Returns the value that follows `option` on the command line
+fn arg_value(option: &str) -> Option<String> {
+    arg_values(option, 1).map(|mut values| values.remove(0))
+}
+
+//Returns the `count` values that follow `option` on the command line
+fn arg_values(option: &str, count: usize) -> Option<Vec<String>> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == option)?;
+    args.get((index + 1)..(index + 1 + count))
+        .map(|values| values.to_vec())
+}
+
+//Converts a room template between the PNG and text formats with
+//`--convert-template <input> <output>`, the format is chosen
+//based on the file extension (`.png` or `.room`)
+fn convert_template(input: &str, output: &str) -> Result<(), String> {
+    let template = RoomTemplate::load(input).map_err(|msg| format!("{input}: {msg}"))?;
+    template
+        .save(output)
+        .map_err(|msg| format!("{output}: {msg}"))
+}
+
+//Returns the seed passed on the command line with `--seed <number>`
+fn seed_from_args() -> Option<String> {
+    match arg_value("--seed") {
+        Some(seed) if seed.parse::<u64>().is_ok() => Some(seed),
+        None => None,
+        _ => {
+            eprintln!("--seed expects a number between 0 and {}", u64::MAX);
+            None
+        }
+    }
+}
+
+//Returns the size of the tower passed on the command line with
+//`--tower <size>`, the size is either practice, standard, marathon
+//or <floors>x<rooms per floor>
+fn tower_size_from_args() -> Option<TowerSize> {
+    match TowerSize::parse(&arg_value("--tower")?) {
+        Ok(size) => Some(size),
+        Err(msg) => {
+            eprintln!("{msg}");
+            None
+        }
+    }
+}
+
+//Returns the difficulty passed on the command line with
+//`--difficulty <name>`, the name is easy, normal or hard
+fn difficulty_from_args() -> Option<Difficulty> {
+    let name = arg_value("--difficulty")?;
+    let difficulty = Difficulty::from_name(&name);
+    if difficulty.is_none() {
+        eprintln!("invalid difficulty: {name}");
+    }
+    difficulty
+}
+
+//Checks every template in a directory with `--validate-templates <dir>`
+fn validate_templates(path: &str) -> Result<(), String> {
+    let invalid = room_template::validate::validate_templates(path);
+    for (template_path, problems) in &invalid {
+        for problem in problems {
+            eprintln!("{template_path}: {problem}");
+        }
+    }
+
+    if invalid.is_empty() {
+        println!("all templates in {path} are valid");
+        Ok(())
+    } else {
+        Err(format!("{} invalid template(s)", invalid.len()))
+    }
+}
+
+//Checks a high score with `--verify-hiscore <rank>` by playing back
+//its replay, the rank is the position of the score in the table
+fn verify_hiscore(rank: &str) -> Result<(), String> {
+    let profile = profile_from_args()?;
+    let highscores = hiscore::load_highscores(&profile.path(profile::HISCORES_FILE));
+    let sorted = hiscore::sorted_highscores(&highscores, hiscore::HighScoreSort::Score);
+    let highscore = rank
+        .parse::<usize>()
+        .ok()
+        .and_then(|rank| sorted.get(rank.checked_sub(1)?))
+        .ok_or(format!("there is no high score with rank {rank}"))?;
+
+    let room_templates = room_template::load_room_templates("assets/room_templates");
+    let replay_dir = profile.path(profile::HISCORE_REPLAY_DIR);
+    hiscore::verify_highscore(highscore, &replay_dir, &room_templates).map_err(|msg| {
+        format!(
+            "{} ({}) failed verification: {msg}",
+            highscore.name, highscore.score
+        )
+    })?;
+    println!("{} ({}) is valid", highscore.name, highscore.score);
+    Ok(())
+}
+
 fn main() -> Result<(), String> {
+    if let Some(paths) = arg_values("--convert-template", 2) {
+        return convert_template(&paths[0], &paths[1]);
+    }
+
+    if let Some(path) = arg_value("--validate-templates") {
+        return validate_templates(&path);
+    }
+
+    //Enemies are defined in a data file, the game does not start if it is invalid
+    if let Err(msg) = definition::definitions() {
+        return Err(format!("failed to load enemy definitions: {msg}"));
+    }
+    if let Err(msg) = level::difficulty::load_profiles() {
+        return Err(format!("failed to load difficulty profiles: {msg}"));
+    }
+
+    if let Some(rank) = arg_value("--verify-hiscore") {
+        return verify_hiscore(&rank);
+    }
+
     //Attempt to initialize glfw
     let mut glfw = glfw::init_no_callbacks().map_err(|e| e.to_string())?;
     glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
@@ -131,6 +596,7 @@ fn main() -> Result<(), String> {
     glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
     window.set_framebuffer_size_polling(true);
     window.set_key_polling(true);
+    window.set_char_polling(true);
     //Attempt to load OpenGL functions
     gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
 
@@ -173,28 +639,112 @@ fn main() -> Result<(), String> {
     let icons = gfx::load_texture("assets/textures/icons.png");
     //Initialize the current state of the application
     let mut state = State::starting_state();
+    let mut level_renderer = LevelRenderer::new();
+    match profile_from_args() {
+        Ok(profile) => state.set_profile(profile),
+        Err(msg) => eprintln!("failed to open profile: {msg}"),
+    }
+    if let Some(seed) = seed_from_args() {
+        state.seed_input = seed;
+    }
+    if let Some(tower_size) = tower_size_from_args() {
+        state.tower_size = tower_size;
+    }
+    //The difficulty from the command line is only used until the settings are loaded again
+    if let Some(difficulty) = difficulty_from_args() {
+        state.settings.difficulty = difficulty;
+    }
+    //Name saved with high scores, passed with `--name <name>`
+    if let Some(name) = arg_value("--name") {
+        state.set_player_name(&name);
+    }
+
+    //Play back a replay passed on the command line with `--replay <path>`
+    if let Some(path) = arg_value("--replay") {
+        match Replay::load(&path) {
+            Ok(replay) => {
+                state.tower_size = replay.tower_size;
+                state.difficulty = replay.difficulty;
+                start_game(&mut state, replay.seed, &mut level_renderer);
+                state.playback = Some(ReplayPlayer::new(replay));
+            }
+            Err(msg) => eprintln!("failed to load replay {path}: {msg}"),
+        }
+    }
 
-    let pause_menu = ui::Menu::create_pause_menu();
-    let main_menu = ui::Menu::create_main_menu();
-    let gameover_menu = ui::Menu::create_gameover_menu();
-    let hiscore_menu = ui::Menu::create_hiscore_menu();
-    let win_screen = ui::Menu::create_win_screen();
-    let about_screen = ui::Menu::create_about_screen();
+    let mut menus = Menus::create();
 
     let mut dt = 0.0f32;
+    //Time that has passed that has not been simulated yet
+    let mut accumulator = 0.0f32;
     let mut animation_timer = 0.0f32;
-    let mut highscores = hiscore::load_highscores("hiscores");
+    let mut highscores = hiscore::load_highscores(&state.profile.path(profile::HISCORES_FILE));
 
-    let sfx_player = SfxPlayer::init();
+    let mut sfx_player = SfxPlayer::init();
+    //Settings that the window and audio were last changed to
+    let mut applied_settings: Option<Settings> = None;
+    let mut gamepads = GlfwGamepads::new(&glfw);
+    let mut gamepad_input = GamepadInput::default();
+    let mut last_screen = state.game_screen;
 
     while !window.should_close() {
         let start = Instant::now();
-        process_events(&mut window, &events, &mut state, &sfx_player);
+        //Settings are applied as soon as they change
+        if applied_settings != Some(state.settings) {
+            apply_settings(
+                applied_settings.as_ref(),
+                &mut glfw,
+                &mut window,
+                &mut state,
+                &mut sfx_player,
+            );
+            applied_settings = Some(state.settings);
+        }
+        let mut menu_actions = process_events(&mut window, &events, &mut state, &sfx_player);
+        //Gamepad buttons control the player
+        for (input, pressed) in gamepad_input.update(&mut gamepads) {
+            let screen = state.game_screen;
+            match state.handle_pad_input(input, pressed, &sfx_player) {
+                Some(action) if pressed => menu_actions.push((screen, action)),
+                _ => {}
+            }
+        }
+        //Menus start without a focused button every time they are shown so
+        //that a key held when the screen changes can not pick a button
+        if state.game_screen != last_screen {
+            if let Some(menu) = menus.get(state.game_screen) {
+                menu.clear_focus();
+            }
+            last_screen = state.game_screen;
+        }
+        //The keyboard and gamepad can also be used to pick buttons in menus,
+        //actions that changed the screen (like Escape) are not used
+        for (screen, action) in menu_actions {
+            if screen != state.game_screen {
+                continue;
+            }
+
+            let button_action = menus.get(screen).and_then(|menu| menu.navigate(action));
+            if let Some(button_action) = button_action {
+                activate_button(
+                    button_action,
+                    &mut state,
+                    &mut level_renderer,
+                    &mut highscores,
+                    &sfx_player,
+                );
+            }
+        }
         let win_info = get_glfw_window_info(&window);
 
+        //How far we are between the previous simulation step and the next one,
+        //this is used to smoothly draw sprites between simulation steps
+        let alpha = accumulator / SIM_TIMESTEP;
+        let camera_position =
+            camera_position(&state, alpha, &win_info) + screen_shake(&state, animation_timer);
         let view_matrix = Matrix4::from_translation(cgmath::vec3(
-            -state.player_position().x,
-            -state.player_position().y,
+            -camera_position.x,
+            -camera_position.y,
             level::LEVEL_Z,
         ));
 
@@ -215,7 +765,14 @@ fn main() -> Result<(), String> {
         sprite_shader.uniform_float("uTexScale", 1.0 / 8.0);
 
         match state.game_screen {
-            GameScreen::MainMenu | GameScreen::HighScores | GameScreen::AboutScreen => {
+            GameScreen::MainMenu
+            | GameScreen::HighScores
+            | GameScreen::AboutScreen
+            | GameScreen::StatisticsScreen
+            | GameScreen::AchievementsScreen
+            | GameScreen::ControlsScreen
+            | GameScreen::Settings
+            | GameScreen::NewProfile => {
                 tile_textures.bind();
                 background_shader.use_program();
                 background_shader.uniform_vec2f(
@@ -229,12 +786,14 @@ fn main() -> Result<(), String> {
                 //Display level
                 tile_textures.bind();
                 level_shader.use_program();
-                state.level.display(&state.player_position());
+                level_renderer.display(&state.player_position());
                 //Display player sprite
                 rect_vao.bind();
                 sprite_shader.use_program();
                 sprite_textures.bind();
-                state.player.display_player(&rect_vao, &sprite_shader);
+                state
+                    .player
+                    .display_player(&rect_vao, &sprite_shader, alpha);
                 //Display tiles that the player can interact with
                 cube_vao.bind();
                 sprite_shader.uniform_bool("uFlipped", false);
@@ -245,15 +804,17 @@ fn main() -> Result<(), String> {
                     animation_timer,
                 );
                 rect_vao.bind();
-                state.display_enemies(&rect_vao, &sprite_shader);
-                state.display_projectiles(&rect_vao, &sprite_shader);
-                state.display_particles(&rect_vao, &sprite_shader);
+                state.display_enemies(&rect_vao, &sprite_shader, alpha);
+                state.display_projectiles(&rect_vao, &sprite_shader, alpha);
+                if state.settings.particles {
+                    state.display_particles(&rect_vao, &sprite_shader, alpha);
+                }
             }
             GameScreen::GameOver => {
                 //Display level
                 tile_textures.bind();
                 level_shader.use_program();
-                state.level.display(&state.player_position());
+                level_renderer.display(&state.player_position());
                 //Display tiles that the player can interact with
                 sprite_shader.use_program();
                 sprite_textures.bind();
@@ -266,9 +827,11 @@ fn main() -> Result<(), String> {
                     animation_timer,
                 );
                 rect_vao.bind();
-                state.display_enemies(&rect_vao, &sprite_shader);
-                state.display_projectiles(&rect_vao, &sprite_shader);
-                state.display_particles(&rect_vao, &sprite_shader);
+                state.display_enemies(&rect_vao, &sprite_shader, alpha);
+                state.display_projectiles(&rect_vao, &sprite_shader, alpha);
+                if state.settings.particles {
+                    state.display_particles(&rect_vao, &sprite_shader, alpha);
+                }
             }
         }
 
@@ -286,10 +849,31 @@ fn main() -> Result<(), String> {
 
         match state.game_screen {
             GameScreen::MainMenu => {
-                main_menu.display(&rect_vao, &text_shader, &win_info);
+                menus.main.display(&rect_vao, &text_shader, &win_info);
+                let seed_text = if state.seed_input.is_empty() {
+                    "seed:random (type to enter)".to_string()
+                } else {
+                    format!("seed:{}", state.seed_input)
+                };
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    seed_text.as_bytes(),
+                    0.0,
+                    48.0,
+                    8.0,
+                );
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    format!("profile:{}", state.profile.name).as_bytes(),
+                    0.0,
+                    132.0,
+                    8.0,
+                );
             }
             GameScreen::AboutScreen => {
-                about_screen.display(&rect_vao, &text_shader, &win_info);
+                menus.about.display(&rect_vao, &text_shader, &win_info);
             }
             GameScreen::WinScreen => {
                 text_shader.use_program();
@@ -301,6 +885,14 @@ fn main() -> Result<(), String> {
                     96.0,
                     8.0,
                 );
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    format!("seed:{}", state.seed).as_bytes(),
+                    0.0,
+                    -48.0,
+                    8.0,
+                );
 
                 if state.new_highscore {
                     ui::display_ascii_text_centered(
@@ -313,16 +905,74 @@ fn main() -> Result<(), String> {
                     );
                 }
 
-                win_screen.display(&rect_vao, &text_shader, &win_info);
+                stats::display_new_achievements(
+                    &rect_vao,
+                    &text_shader,
+                    &state.new_achievements,
+                    -180.0,
+                );
+                menus.win.display(&rect_vao, &text_shader, &win_info);
             }
             GameScreen::HighScores => {
-                hiscore::display_hiscores(&rect_vao, &text_shader, &highscores);
-                hiscore_menu.display(&rect_vao, &text_shader, &win_info);
+                hiscore::display_hiscores(
+                    &rect_vao,
+                    &text_shader,
+                    &highscores,
+                    state.highscore_sort,
+                );
+                menus.hiscore.display(&rect_vao, &text_shader, &win_info);
+            }
+            GameScreen::StatisticsScreen => {
+                stats::display_statistics(&rect_vao, &text_shader, &state.stats);
+                menus.statistics.display(&rect_vao, &text_shader, &win_info);
+            }
+            GameScreen::AchievementsScreen => {
+                stats::display_achievements(&rect_vao, &text_shader, &state.stats);
+                menus
+                    .achievements
+                    .display(&rect_vao, &text_shader, &win_info);
+            }
+            GameScreen::Settings => {
+                settings::display_settings(&rect_vao, &text_shader, &state.settings);
+                menus.settings.display(&rect_vao, &text_shader, &win_info);
+            }
+            GameScreen::NewProfile => {
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    format!("name:{}_", state.profile_name_input).as_bytes(),
+                    0.0,
+                    40.0,
+                    8.0,
+                );
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    state.profile_message.as_bytes(),
+                    0.0,
+                    0.0,
+                    8.0,
+                );
+                menus
+                    .new_profile
+                    .display(&rect_vao, &text_shader, &win_info);
+            }
+            GameScreen::ControlsScreen => {
+                input_config::display_controls(
+                    &rect_vao,
+                    &text_shader,
+                    &state.input,
+                    state.controls_gamepad,
+                    state.rebinding,
+                    &state.controls_message,
+                );
+                menus.controls.display(&rect_vao, &text_shader, &win_info);
             }
             GameScreen::Game => {
                 state
                     .player
                     .display_player_stats(&rect_vao, &text_shader, &window);
+                state.display_boss_health(&rect_vao, &text_shader, &window);
                 //Make the screen flash red if the player takes damage
                 if state.player.damage_cooldown > 0.0 {
                     rect_shader.use_program();
@@ -340,11 +990,12 @@ fn main() -> Result<(), String> {
                 state
                     .player
                     .display_player_stats(&rect_vao, &text_shader, &window);
+                state.display_boss_health(&rect_vao, &text_shader, &window);
                 rect_shader.use_program();
                 rect_shader.uniform_vec4f("uColor", 0.6, 0.6, 0.6, 0.4);
                 rect_vao.draw_arrays();
                 text_shader.use_program();
-                pause_menu.display(&rect_vao, &text_shader, &win_info);
+                menus.pause.display(&rect_vao, &text_shader, &win_info);
             }
             GameScreen::GameOver => {
                 rect_shader.use_program();
@@ -360,6 +1011,14 @@ fn main() -> Result<(), String> {
                     48.0,
                     8.0,
                 );
+                ui::display_ascii_text_centered(
+                    &rect_vao,
+                    &text_shader,
+                    format!("seed:{}", state.seed).as_bytes(),
+                    0.0,
+                    0.0,
+                    8.0,
+                );
 
                 if state.new_highscore {
                     ui::display_ascii_text_centered(
@@ -372,7 +1031,13 @@ fn main() -> Result<(), String> {
                     );
                 }
 
-                gameover_menu.display(&rect_vao, &text_shader, &win_info);
+                stats::display_new_achievements(
+                    &rect_vao,
+                    &text_shader,
+                    &state.new_achievements,
+                    -120.0,
+                );
+                menus.gameover.display(&rect_vao, &text_shader, &win_info);
             }
         }
 
@@ -383,31 +1048,74 @@ fn main() -> Result<(), String> {
         //Handle interaction with menu
         let left_mouse_held = window.get_mouse_button(glfw::MouseButtonLeft) == glfw::Action::Press;
         if left_mouse_held && !state.left_mouse_held {
-            let button_action = match state.game_screen {
-                GameScreen::Game => None,
-                GameScreen::AboutScreen => about_screen.get_clicked_button_action(&win_info),
-                GameScreen::WinScreen => win_screen.get_clicked_button_action(&win_info),
-                GameScreen::HighScores => hiscore_menu.get_clicked_button_action(&win_info),
-                GameScreen::Paused => pause_menu.get_clicked_button_action(&win_info),
-                GameScreen::GameOver => gameover_menu.get_clicked_button_action(&win_info),
-                GameScreen::MainMenu => main_menu.get_clicked_button_action(&win_info),
-            };
-
+            let button_action = menus
+                .get(state.game_screen)
+                .and_then(|menu| menu.get_clicked_button_action(&win_info));
             if let Some(action) = button_action {
-                sfx_player.play(sfx_ids::SELECT);
-                process_button_action(action, &mut state);
+                activate_button(
+                    action,
+                    &mut state,
+                    &mut level_renderer,
+                    &mut highscores,
+                    &sfx_player,
+                );
             }
         }
 
         state.left_mouse_held = left_mouse_held;
 
-        if state.game_screen == GameScreen::Game {
-            state.update_game_screen(dt, &sfx_player);
-            state.check_gameover(&mut highscores, &sfx_player);
-        } else if state.game_screen == GameScreen::GameOver {
-            state.update_enemies(dt, &sfx_player);
-            state.update_projectiles(dt);
-            state.update_particles(dt);
+        //Simulate the game in fixed steps, the time a frame takes is capped
+        //so that a long pause (like dragging the window) isn't simulated
+        if state.game_screen == GameScreen::Game || state.game_screen == GameScreen::GameOver {
+            accumulator += dt.min(MAX_FRAME_TIME);
+        }
+
+        let locked_floors = state.level.locked_floors().len();
+        let mut steps = 0;
+        while accumulator >= SIM_TIMESTEP && steps < MAX_STEPS_PER_FRAME {
+            accumulator -= SIM_TIMESTEP;
+            steps += 1;
+
+            if state.game_screen == GameScreen::Game {
+                state.update_fixed_step(&sfx_player);
+                if state.check_gameover(&mut highscores, &sfx_player) {
+                    if state.tracking_stats() {
+                        state.save_stats();
+                    }
+                    if state.new_highscore && state.playback.is_none() {
+                        hiscore::write_highscores(
+                            &state.profile.path(profile::HISCORES_FILE),
+                            &highscores,
+                        );
+                        let replay_dir = state.profile.path(profile::HISCORE_REPLAY_DIR);
+                        if let Err(msg) = hiscore::save_highscore_replay(&state.replay, &replay_dir)
+                        {
+                            eprintln!("failed to save high score replay: {msg}");
+                        }
+                    }
+
+                    if state.playback.is_none() {
+                        let path = state.profile.path(profile::LAST_REPLAY_FILE);
+                        if let Err(msg) = state.replay.save(&path) {
+                            eprintln!("failed to save replay: {msg}");
+                        }
+                    }
+                }
+            } else if state.game_screen == GameScreen::GameOver {
+                state.update_enemies(SIM_TIMESTEP, &sfx_player);
+                state.update_projectiles(SIM_TIMESTEP);
+                state.update_particles(SIM_TIMESTEP);
+            }
+        }
+
+        //Drop any time we could not catch up on
+        if accumulator >= SIM_TIMESTEP {
+            accumulator %= SIM_TIMESTEP;
+        }
+
+        //The ceiling of a boss floor opens once the boss is defeated
+        if state.level.locked_floors().len() != locked_floors {
+            level_renderer = LevelRenderer::build(&state.level);
         }
 
         if state.game_screen == GameScreen::Game {
@@ -427,7 +1135,10 @@ fn main() -> Result<(), String> {
         dt = end.duration_since(start).as_secs_f32();
     }
 
-    hiscore::write_highscores("hiscores", &highscores);
+    hiscore::write_highscores(&state.profile.path(profile::HISCORES_FILE), &highscores);
+    if state.tracking_stats() {
+        state.save_stats();
+    }
 
     Ok(())
 }
@@ -0,0 +1,7 @@
+use std::str::FromStr;
+
+//Parses a single value of one of the text files of the game,
+//`name` is used in the error message
+pub fn parse_value<T: FromStr>(text: &str, name: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("invalid {name}: {text}"))
+}
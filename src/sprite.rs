@@ -1,6 +1,8 @@
 use crate::level::{Level, Tile};
 use cgmath::{vec2, Vector2};
 
+pub mod collision;
+mod display;
 pub mod enemy;
 pub mod particle;
 
@@ -10,6 +12,9 @@ pub struct Sprite {
     pub dimensions: Vector2<f32>,
     pub velocity: Vector2<f32>,
     pub flipped: bool,
+    //Position at the start of the current simulation step,
+    //used to smoothly draw the sprite between steps
+    prev_position: Vector2<f32>,
 
     //In seconds
     animation_timer: f32,
@@ -28,6 +33,7 @@ impl Sprite {
             dimensions: vec2(w, h),
             velocity: vec2(0.0, 0.0),
             flipped: false,
+            prev_position: vec2(x, y),
 
             animation_timer: 0.0,
             animation_duration: 0.0,
@@ -36,6 +42,18 @@ impl Sprite {
         }
     }
 
+    //Saves the current position, this should be called at the
+    //start of every simulation step
+    pub fn save_position(&mut self) {
+        self.prev_position = self.position;
+    }
+
+    //Returns the position of the sprite between the previous simulation step
+    //and the current simulation step, alpha is in the range 0.0 to 1.0
+    pub fn interpolated_position(&self, alpha: f32) -> Vector2<f32> {
+        self.prev_position + (self.position - self.prev_position) * alpha
+    }
+
     //Collision detection
     pub fn intersecting(&self, sprite: &Sprite) -> bool {
         self.position.x - self.dimensions.x / 2.0 < sprite.position.x + sprite.dimensions.x / 2.0
@@ -120,17 +138,4 @@ impl Sprite {
             }
         }
     }
-
-    //Uncollide the sprite with another sprite in the y axis
-    pub fn uncollide_y(&mut self, sprite: &Sprite) {
-        if self.intersecting(sprite) {
-            if self.position.y > sprite.position.y {
-                self.position.y =
-                    sprite.position.y + sprite.dimensions.y / 2.0 + self.dimensions.y / 2.0;
-            } else if self.position.y < sprite.position.y {
-                self.position.y =
-                    sprite.position.y - sprite.dimensions.y / 2.0 - self.dimensions.y / 2.0;
-            }
-        }
-    }
 }
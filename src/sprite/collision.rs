@@ -0,0 +1,208 @@
+use super::Sprite;
+use crate::level::{transparent, Level, Tile};
+use cgmath::{vec2, Vector2};
+
+//Tolerance used when checking if a sprite is touching a tile so that
+//a sprite resting exactly on a tile still counts as touching it
+const EPSILON: f32 = 0.0001;
+
+//Information about a tile that a sprite ran into
+#[derive(Copy, Clone, Debug)]
+pub struct TileCollision {
+    //Direction pointing away from the face of the tile that was hit
+    pub normal: Vector2<f32>,
+    //Tile that was hit
+    #[allow(dead_code)]
+    pub tile: Tile,
+    pub tile_x: i32,
+    pub tile_y: i32,
+}
+
+//Returns the tile if it is solid, tiles outside of the level are not solid
+fn solid_tile(level: &Level, x: i32, y: i32) -> Option<Tile> {
+    if level.out_of_bounds(x, y) {
+        return None;
+    }
+
+    let tile = level.get_tile(x as u32, y as u32);
+    if transparent(tile) {
+        return None;
+    }
+
+    Some(tile)
+}
+
+//Returns the range of tile coordinates that overlap the interval (min, max),
+//tiles are 1 unit wide and centered on integer coordinates
+fn overlapping_tiles(min: f32, max: f32) -> (i32, i32) {
+    (
+        (min - 0.5 + EPSILON).floor() as i32 + 1,
+        (max + 0.5 - EPSILON).ceil() as i32 - 1,
+    )
+}
+
+impl Sprite {
+    //Moves the sprite along the x axis, if there is a solid tile in the
+    //way then the sprite stops at the first tile it hits no matter how
+    //far it is moving
+    pub fn sweep_x(&mut self, dx: f32, level: &Level) -> Option<TileCollision> {
+        let half = self.dimensions / 2.0;
+        let (min_y, max_y) = overlapping_tiles(self.position.y - half.y, self.position.y + half.y);
+
+        if dx > 0.0 {
+            let edge = self.position.x + half.x;
+            let start = (edge + 0.5 - EPSILON).ceil() as i32;
+            let end = (edge + dx + 0.5).ceil() as i32 - 1;
+            for x in start..=end {
+                for y in min_y..=max_y {
+                    if let Some(tile) = solid_tile(level, x, y) {
+                        self.position.x = x as f32 - 0.5 - half.x;
+                        return Some(TileCollision {
+                            normal: vec2(-1.0, 0.0),
+                            tile,
+                            tile_x: x,
+                            tile_y: y,
+                        });
+                    }
+                }
+            }
+        } else if dx < 0.0 {
+            let edge = self.position.x - half.x;
+            let start = (edge - 0.5 + EPSILON).floor() as i32;
+            let end = (edge + dx - 0.5).floor() as i32 + 1;
+            for x in (end..=start).rev() {
+                for y in min_y..=max_y {
+                    if let Some(tile) = solid_tile(level, x, y) {
+                        self.position.x = x as f32 + 0.5 + half.x;
+                        return Some(TileCollision {
+                            normal: vec2(1.0, 0.0),
+                            tile,
+                            tile_x: x,
+                            tile_y: y,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.position.x += dx;
+        None
+    }
+
+    //Moves the sprite along the y axis, if there is a solid tile in the
+    //way then the sprite stops at the first tile it hits no matter how
+    //far it is moving
+    pub fn sweep_y(&mut self, dy: f32, level: &Level) -> Option<TileCollision> {
+        let half = self.dimensions / 2.0;
+        let (min_x, max_x) = overlapping_tiles(self.position.x - half.x, self.position.x + half.x);
+
+        if dy > 0.0 {
+            let edge = self.position.y + half.y;
+            let start = (edge + 0.5 - EPSILON).ceil() as i32;
+            let end = (edge + dy + 0.5).ceil() as i32 - 1;
+            for y in start..=end {
+                for x in min_x..=max_x {
+                    if let Some(tile) = solid_tile(level, x, y) {
+                        self.position.y = y as f32 - 0.5 - half.y;
+                        return Some(TileCollision {
+                            normal: vec2(0.0, -1.0),
+                            tile,
+                            tile_x: x,
+                            tile_y: y,
+                        });
+                    }
+                }
+            }
+        } else if dy < 0.0 {
+            let edge = self.position.y - half.y;
+            let start = (edge - 0.5 + EPSILON).floor() as i32;
+            let end = (edge + dy - 0.5).floor() as i32 + 1;
+            for y in (end..=start).rev() {
+                for x in min_x..=max_x {
+                    if let Some(tile) = solid_tile(level, x, y) {
+                        self.position.y = y as f32 + 0.5 + half.y;
+                        return Some(TileCollision {
+                            normal: vec2(0.0, 1.0),
+                            tile,
+                            tile_x: x,
+                            tile_y: y,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.position.y += dy;
+        None
+    }
+
+    //Moves the sprite along the x axis and then the y axis and returns
+    //all of the tiles that the sprite ran into
+    pub fn move_and_collide(
+        &mut self,
+        displacement: Vector2<f32>,
+        level: &Level,
+    ) -> Vec<TileCollision> {
+        self.sweep_x(displacement.x, level)
+            .into_iter()
+            .chain(self.sweep_y(displacement.y, level))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Creates a level that is empty except for a floor
+    //of bricks at y = 0 and a wall at x = 9
+    fn test_level() -> Level {
+        let mut level = Level::new(10, 200);
+        for x in 0..10 {
+            for y in 1..200 {
+                if x != 9 {
+                    level.set_tile(x, y, Tile::Air);
+                }
+            }
+        }
+        level
+    }
+
+    #[test]
+    fn test_fast_fall_lands_on_floor() {
+        let level = test_level();
+        let mut sprite = Sprite::new(4.0, 150.0, 0.625, 0.8125);
+        //Falling 120 tiles in a single step should still hit the floor
+        let collision = sprite.sweep_y(-120.0, &level);
+        assert!(collision.is_none());
+        let collision = sprite
+            .sweep_y(-120.0, &level)
+            .expect("sprite fell through floor");
+        assert_eq!(collision.normal, vec2(0.0, 1.0));
+        assert_eq!((collision.tile_x, collision.tile_y), (4, 0));
+        assert!(collision.tile == Tile::Brick);
+        assert_eq!(sprite.position.y, 0.5 + 0.8125 / 2.0);
+    }
+
+    #[test]
+    fn test_resting_sprite_touches_floor() {
+        let level = test_level();
+        let mut sprite = Sprite::new(4.0, 0.5 + 0.5, 1.0, 1.0);
+        assert!(sprite.sweep_y(-0.0001, &level).is_some());
+        assert_eq!(sprite.position.y, 1.0);
+        //Moving along the floor should not collide with it
+        assert!(sprite.sweep_x(1.5, &level).is_none());
+        assert_eq!(sprite.position.x, 5.5);
+    }
+
+    #[test]
+    fn test_stops_at_wall() {
+        let level = test_level();
+        let mut sprite = Sprite::new(2.0, 5.0, 0.5, 0.5);
+        let collisions = sprite.move_and_collide(vec2(50.0, 0.0), &level);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].normal, vec2(-1.0, 0.0));
+        assert_eq!(collisions[0].tile_x, 9);
+        assert_eq!(sprite.position.x, 8.25);
+    }
+}
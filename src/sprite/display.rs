@@ -0,0 +1,63 @@
+use super::{
+    enemy::Enemy,
+    particle::{Particle, ParticleType},
+};
+use crate::{gfx::VertexArrayObject, shader::ShaderProgram};
+use cgmath::{vec3, Matrix4};
+
+impl Enemy {
+    pub fn display(
+        &self,
+        rect_vao: &VertexArrayObject,
+        shader_program: &ShaderProgram,
+        alpha: f32,
+    ) {
+        shader_program.uniform_bool("uFlipped", self.sprite.flipped);
+
+        //Frames are drawn as a square as tall as the hitbox so
+        //that enemies taller than a tile (bosses) are drawn larger
+        let position = self.sprite.interpolated_position(alpha);
+        let transform_matrix = Matrix4::from_translation(cgmath::vec3(position.x, position.y, 0.0))
+            * Matrix4::from_scale(0.5 * self.sprite.dimensions.y);
+        shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+
+        //Apply texture
+        let (column, row) = self.enemy_type.def().sprite;
+        shader_program.uniform_vec2f(
+            "uTexOffset",
+            1.0 / 8.0 * (self.sprite.current_frame() + column) as f32,
+            1.0 / 8.0 * row as f32,
+        );
+
+        rect_vao.draw_arrays();
+    }
+}
+
+impl Particle {
+    pub fn display(
+        &self,
+        rect_vao: &VertexArrayObject,
+        shader_program: &ShaderProgram,
+        alpha: f32,
+    ) {
+        let position = self.sprite.interpolated_position(alpha);
+        let transform_matrix = Matrix4::from_translation(vec3(position.x, position.y, 0.0))
+            * Matrix4::from_nonuniform_scale(
+                0.5 * self.sprite.dimensions.x,
+                0.5 * self.sprite.dimensions.y,
+                0.5,
+            );
+        shader_program.uniform_matrix4f("uTransform", &transform_matrix);
+
+        match self.particle_type {
+            ParticleType::Blood => {
+                shader_program.uniform_vec2f("uTexOffset", 0.0, 4.0 / 8.0);
+            }
+            ParticleType::Fire => {
+                shader_program.uniform_vec2f("uTexOffset", 1.0 / 8.0, 4.0 / 8.0);
+            }
+        }
+
+        rect_vao.draw_arrays();
+    }
+}
@@ -1,32 +1,65 @@
-use super::Sprite;
+use super::{collision::TileCollision, Sprite};
 use crate::{
-    game::Projectile, game::DAMAGE_COOLDOWN, game::GRAVITY, gfx::VertexArrayObject,
-    level::transparent, level::Level, shader::ShaderProgram,
+    game::Projectile, game::DAMAGE_COOLDOWN, game::GRAVITY, level::Level, parse::parse_value,
 };
-use cgmath::{vec2, Matrix4, Vector2};
+use cgmath::{vec2, Vector2};
+use definition::{definitions, Animation, Behaviour, EnemyDef};
 
-mod chicken;
-mod demon;
-mod eyeball;
-mod skeleton;
-mod slime;
+mod boss;
+mod charge;
+mod chase;
+pub mod definition;
+mod patrol;
+mod shoot;
+mod stalk;
+mod vision;
 
 const ENEMY_ATTACK_COOLDOWN: f32 = 1.0;
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum EnemyState {
     Idle,
     Wander,
     Chase,
+    //Going to where the player was last seen
+    Search,
 }
 
-#[derive(Copy, Clone)]
-pub enum EnemyType {
-    Slime,
-    Eyeball,
-    Chicken,
-    Skeleton,
-    Demon,
+//One of the enemies in the enemy definitions file
+#[derive(Copy, Clone, Debug)]
+pub struct EnemyType(&'static EnemyDef);
+
+impl PartialEq for EnemyType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for EnemyType {}
+
+impl EnemyType {
+    //Every enemy in the order they are defined
+    pub fn all() -> Vec<Self> {
+        definitions()
+            .map(|defs| defs.iter().map(EnemyType).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        &self.0.name
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        definitions()
+            .ok()?
+            .iter()
+            .find(|def| def.name == name)
+            .map(EnemyType)
+    }
+
+    pub fn def(&self) -> &'static EnemyDef {
+        self.0
+    }
 }
 
 pub struct Enemy {
@@ -38,37 +71,24 @@ pub struct Enemy {
     attack_cooldown: f32,
     idle_cooldown: f32,
     state: EnemyState,
+    //Tile the enemy is heading for when chasing the player
+    path_target: Option<(i32, i32)>,
+    //Last path that was found so it is only searched for again when
+    //the enemy gets to a new tile, the goal moves or the level changes
+    path: Option<chase::PathStep>,
+    //Tile the player was last seen in and how much longer
+    //the enemy looks for them after losing sight of them
+    last_seen: Option<(i32, i32)>,
+    search_cooldown: f32,
 }
 
 impl Enemy {
     //Create a new enemy
     pub fn new(x: f32, y: f32, enemy: EnemyType, flipped: bool) -> Self {
-        let mut spr = Sprite::new(x, y, 0.9, 1.0);
-
-        match enemy {
-            EnemyType::Slime => spr.set_animation(0.5, 0, 1),
-            EnemyType::Eyeball => spr.set_animation(1.0, 0, 1),
-            EnemyType::Chicken => spr.set_animation(1.0, 2, 3),
-            EnemyType::Skeleton => spr.set_animation(1.0, 2, 3),
-            EnemyType::Demon => spr.set_animation(1.0, 2, 3),
-        }
-
-        match enemy {
-            EnemyType::Slime => spr.velocity.x = 0.5,
-            EnemyType::Eyeball => spr.velocity.x = 1.0,
-            EnemyType::Chicken => spr.velocity.x = 1.5,
-            EnemyType::Skeleton => spr.velocity.x = 0.7,
-            EnemyType::Demon => spr.velocity.x = 1.1,
-        }
-
-        let enemy_hp = match enemy {
-            EnemyType::Slime => 1,
-            EnemyType::Eyeball => 2,
-            EnemyType::Chicken => 3,
-            EnemyType::Skeleton => 4,
-            EnemyType::Demon => 5,
-        };
-
+        let def = enemy.def();
+        let mut spr = Sprite::new(x, y, def.hitbox.0, def.hitbox.1);
+        spr.set_animation(def.walk.duration, def.walk.first_frame, def.walk.last_frame);
+        spr.velocity.x = def.speed;
         spr.flipped = flipped;
         if spr.flipped {
             spr.velocity.x *= -1.0;
@@ -77,81 +97,129 @@ impl Enemy {
         Self {
             sprite: spr,
             enemy_type: enemy,
-            health: enemy_hp,
+            health: def.health,
             falling: false,
             damage_cooldown: 0.0,
             attack_cooldown: 0.0,
             idle_cooldown: 0.0,
             state: EnemyState::Wander,
+            path_target: None,
+            path: None,
+            last_seen: None,
+            search_cooldown: 0.0,
         }
     }
 
-    pub fn display(&self, rect_vao: &VertexArrayObject, shader_program: &ShaderProgram) {
-        shader_program.uniform_bool("uFlipped", self.sprite.flipped);
-
-        let transform_matrix = Matrix4::from_translation(cgmath::vec3(
+    //Enemies are saved as text of the form:
+    //type x y velocity_x velocity_y flipped health falling
+    //damage_cooldown attack_cooldown idle_cooldown state
+    //followed by `target x y` if the enemy is following a path
+    //and `seen x y search_cooldown` if it has seen the player
+    pub fn to_text(&self) -> String {
+        let enemy_type = self.enemy_type.name();
+        let state = match self.state {
+            EnemyState::Idle => "idle",
+            EnemyState::Wander => "wander",
+            EnemyState::Chase => "chase",
+            EnemyState::Search => "search",
+        };
+        let target = self
+            .path_target
+            .map(|(x, y)| format!(" target {x} {y}"))
+            .unwrap_or_default();
+        let seen = self
+            .last_seen
+            .map(|(x, y)| format!(" seen {x} {y} {}", self.search_cooldown))
+            .unwrap_or_default();
+        format!(
+            "{enemy_type} {} {} {} {} {} {} {} {} {} {} {state}{target}{seen}",
             self.sprite.position.x,
             self.sprite.position.y,
-            0.0,
-        )) * Matrix4::from_scale(0.5);
-        shader_program.uniform_matrix4f("uTransform", &transform_matrix);
-
-        //Apply texture
-        match self.enemy_type {
-            EnemyType::Slime => {
-                shader_program.uniform_vec2f(
-                    "uTexOffset",
-                    1.0 / 8.0 * self.sprite.current_frame() as f32,
-                    1.0 / 8.0,
-                );
-            }
-            EnemyType::Chicken => {
-                shader_program.uniform_vec2f(
-                    "uTexOffset",
-                    1.0 / 8.0 * self.sprite.current_frame() as f32 + 2.0 / 8.0,
-                    1.0 / 8.0,
-                );
-            }
-            EnemyType::Eyeball => {
-                shader_program.uniform_vec2f(
-                    "uTexOffset",
-                    1.0 / 8.0 * self.sprite.current_frame() as f32 + 6.0 / 8.0,
-                    1.0 / 8.0,
-                );
-            }
-            EnemyType::Skeleton => {
-                shader_program.uniform_vec2f(
-                    "uTexOffset",
-                    1.0 / 8.0 * self.sprite.current_frame() as f32 + 4.0 / 8.0,
-                    2.0 / 8.0,
-                );
-            }
-            EnemyType::Demon => {
-                shader_program.uniform_vec2f(
-                    "uTexOffset",
-                    1.0 / 8.0 * self.sprite.current_frame() as f32 + 4.0 / 8.0,
-                    3.0 / 8.0,
-                );
+            self.sprite.velocity.x,
+            self.sprite.velocity.y,
+            self.sprite.flipped,
+            self.health,
+            self.falling,
+            self.damage_cooldown,
+            self.attack_cooldown,
+            self.idle_cooldown,
+        )
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let split: Vec<&str> = text.split_whitespace().collect();
+        if split.len() < 12 {
+            return Err("expected: type x y velocity_x velocity_y flipped health falling damage_cooldown attack_cooldown idle_cooldown state".to_string());
+        }
+
+        let enemy_type =
+            EnemyType::from_name(split[0]).ok_or(format!("invalid enemy: {}", split[0]))?;
+        let state = match split[11] {
+            "idle" => EnemyState::Idle,
+            "wander" => EnemyState::Wander,
+            "chase" => EnemyState::Chase,
+            "search" => EnemyState::Search,
+            other => return Err(format!("invalid enemy state: {other}")),
+        };
+
+        let x = parse_value(split[1], "x")?;
+        let y = parse_value(split[2], "y")?;
+        let flipped = parse_value(split[5], "flipped")?;
+        let mut enemy = Self::new(x, y, enemy_type, flipped);
+        enemy.sprite.velocity = vec2(
+            parse_value(split[3], "velocity_x")?,
+            parse_value(split[4], "velocity_y")?,
+        );
+        enemy.health = parse_value(split[6], "health")?;
+        enemy.falling = parse_value(split[7], "falling")?;
+        enemy.damage_cooldown = parse_value(split[8], "damage_cooldown")?;
+        enemy.attack_cooldown = parse_value(split[9], "attack_cooldown")?;
+        enemy.idle_cooldown = parse_value(split[10], "idle_cooldown")?;
+        enemy.state = state;
+
+        let mut rest = &split[12..];
+        while !rest.is_empty() {
+            match rest {
+                ["target", x, y, ..] => {
+                    enemy.path_target =
+                        Some((parse_value(x, "target_x")?, parse_value(y, "target_y")?));
+                    rest = &rest[3..];
+                }
+                ["seen", x, y, cooldown, ..] => {
+                    enemy.last_seen = Some((parse_value(x, "seen_x")?, parse_value(y, "seen_y")?));
+                    enemy.search_cooldown = parse_value(cooldown, "search_cooldown")?;
+                    rest = &rest[4..];
+                }
+                _ => return Err(format!("invalid enemy field: {}", rest.join(" "))),
             }
         }
+        Ok(enemy)
+    }
 
-        rect_vao.draw_arrays();
+    fn def(&self) -> &'static EnemyDef {
+        self.enemy_type.def()
+    }
+
+    //Sets the animation of the sprite to one of the animations of the enemy
+    fn set_animation(&mut self, animation: Animation) {
+        self.sprite.set_animation(
+            animation.duration,
+            animation.first_frame,
+            animation.last_frame,
+        );
     }
 
     //Handle collision in the y axis
-    fn handle_collision_y(&mut self, sprite: &Sprite) {
-        if self.sprite.intersecting(sprite) {
-            if self.sprite.position.y > sprite.position.y {
-                //If we are supported by a tile then stop falling
-                self.falling = false;
-                self.sprite.velocity.y = -0.01;
-            } else if self.sprite.position.y < sprite.position.y {
-                //Set y velocity to 0 so we don't "stick" to the tile if the
-                //player decides to hold down the jump key
-                self.sprite.velocity.y = 0.0;
-                //We hit the bottom of a tile, start falling again
-                self.falling = true;
-            }
+    fn handle_collision_y(&mut self, collision: &TileCollision) {
+        if collision.normal.y > 0.0 {
+            //If we are supported by a tile then stop falling
+            self.falling = false;
+            self.sprite.velocity.y = -0.01;
+        } else if collision.normal.y < 0.0 {
+            //Set y velocity to 0 so we don't "stick" to the tile
+            self.sprite.velocity.y = 0.0;
+            //We hit the bottom of a tile, start falling again
+            self.falling = true;
         }
     }
 
@@ -178,32 +246,20 @@ impl Enemy {
     }
 
     //Accelerates sprite due to gravity but also check if the sprite
-    //collided with a tile and if the sprite did collide with a tile,
+    //ran into a tile and if the sprite did run into a tile,
     //then check if the sprite is now being supported by that tile and
     //then check if the sprite should keep falling
     fn fall(&mut self, level: &Level, dt: f32) {
-        self.sprite.position.y += self.sprite.velocity.y * dt * 0.5;
+        let mut dy = self.sprite.velocity.y * dt * 0.5;
         if self.falling {
             self.sprite.velocity.y -= GRAVITY * dt;
         }
-        self.sprite.position.y += self.sprite.velocity.y * dt * 0.5;
-
-        let (top_left_x, top_left_y, bot_right_x, bot_right_y) = self.tile_bounding_box();
+        dy += self.sprite.velocity.y * dt * 0.5;
 
         self.falling = true;
-        //Uncollide from any tiles and also determine if the sprite is falling
-        for x in top_left_x..bot_right_x {
-            for y in top_left_y..bot_right_y {
-                if level.out_of_bounds(x, y) {
-                    continue;
-                }
-
-                if !transparent(level.get_tile(x as u32, y as u32)) {
-                    let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
-                    self.handle_collision_y(&hitbox);
-                    self.sprite.uncollide_y(&hitbox);
-                }
-            }
+        //Stop at any tiles in the way and also determine if the sprite is falling
+        if let Some(collision) = self.sprite.sweep_y(dy, level) {
+            self.handle_collision_y(&collision);
         }
     }
 
@@ -216,12 +272,12 @@ impl Enemy {
     ) {
         self.sprite.flipped = self.sprite.velocity.x < 0.0;
 
-        match self.enemy_type {
-            EnemyType::Slime => self.update_slime(dt, level, player_pos),
-            EnemyType::Eyeball => self.update_eyeball(dt, level, player_pos),
-            EnemyType::Chicken => self.update_chicken(dt, level, player_pos),
-            EnemyType::Skeleton => self.update_skeleton(dt, level, player_pos),
-            EnemyType::Demon => self.update_demon(dt, level, player_pos, projectiles),
+        match self.def().behaviour {
+            Behaviour::Patrol => self.update_patrol(dt, level, player_pos),
+            Behaviour::Stalk => self.update_stalk(dt, level, player_pos),
+            Behaviour::Charge => self.update_charge(dt, level, player_pos),
+            Behaviour::Shoot => self.update_shoot(dt, level, player_pos, projectiles),
+            Behaviour::Boss => self.update_boss(dt, level, player_pos, projectiles),
         }
 
         self.damage_cooldown -= dt;
@@ -233,10 +289,7 @@ impl Enemy {
             return 0;
         }
 
-        match self.enemy_type {
-            EnemyType::Slime | EnemyType::Eyeball | EnemyType::Chicken => 1,
-            EnemyType::Skeleton | EnemyType::Demon => 2,
-        }
+        self.def().damage
     }
 
     pub fn apply_damage(&mut self, amount: i32) -> bool {
@@ -249,14 +302,12 @@ impl Enemy {
         false
     }
 
+    pub fn is_boss(&self) -> bool {
+        self.def().behaviour == Behaviour::Boss
+    }
+
     pub fn score(&self) -> u32 {
-        match self.enemy_type {
-            EnemyType::Slime => 10,
-            EnemyType::Eyeball => 20,
-            EnemyType::Chicken => 30,
-            EnemyType::Skeleton => 40,
-            EnemyType::Demon => 60,
-        }
+        self.def().score
     }
 
     pub fn reset_attack_cooldown(&mut self) {
@@ -0,0 +1,190 @@
+use super::{Enemy, EnemyState};
+use crate::{game::Projectile, level::Level, Sprite};
+use cgmath::{vec2, InnerSpace, Vector2};
+use std::f32::consts::PI;
+
+const FIREBALL_SPEED: f32 = 4.0;
+const FIREBALL_SIZE: f32 = 0.3;
+//Number of fireballs in the ring that is shot in the last phase
+const RING_FIREBALLS: u32 = 10;
+//Angle between the fireballs of the spread that is shot in the second phase
+const SPREAD_ANGLE: f32 = PI / 12.0;
+//The boss bobs up and down by HOVER_HEIGHT tiles as it moves
+//and goes up and down once every HOVER_LENGTH tiles
+const HOVER_HEIGHT: f32 = 0.25;
+const HOVER_LENGTH: f32 = 4.0;
+//How long the cast animation is played after a volley of fireballs
+const CAST_TIME: f32 = 0.4;
+
+//The attack phases of a boss, the boss moves on to the
+//next phase each time it loses a third of its health
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BossPhase {
+    //Shoots a single fireball at the player
+    Aimed,
+    //Shoots a fan of fireballs at the player and moves faster
+    Spread,
+    //Shoots a ring of fireballs in every direction and moves even faster
+    Ring,
+}
+
+impl BossPhase {
+    //Time in seconds between each volley of fireballs
+    fn cooldown(self) -> f32 {
+        match self {
+            BossPhase::Aimed => 1.5,
+            BossPhase::Spread => 2.0,
+            BossPhase::Ring => 1.6,
+        }
+    }
+
+    fn speed_multiplier(self) -> f32 {
+        match self {
+            BossPhase::Aimed => 1.0,
+            BossPhase::Spread => 1.5,
+            BossPhase::Ring => 2.0,
+        }
+    }
+
+    //Angles of the fireballs in a volley, measured from the direction of the player
+    fn pattern(self) -> Vec<f32> {
+        match self {
+            BossPhase::Aimed => vec![0.0],
+            BossPhase::Spread => (-2..=2).map(|i| i as f32 * SPREAD_ANGLE).collect(),
+            BossPhase::Ring => (0..RING_FIREBALLS)
+                .map(|i| i as f32 * 2.0 * PI / RING_FIREBALLS as f32)
+                .collect(),
+        }
+    }
+}
+
+impl Enemy {
+    pub fn boss_phase(&self) -> BossPhase {
+        let max_health = self.def().health;
+        if self.health * 3 > max_health * 2 {
+            BossPhase::Aimed
+        } else if self.health * 3 > max_health {
+            BossPhase::Spread
+        } else {
+            BossPhase::Ring
+        }
+    }
+
+    //Returns the fireballs of the next volley, they are shot from the
+    //center of the boss so that they can be fired in any direction
+    fn boss_volley(&self, player_pos: &Vector2<f32>) -> Vec<(Projectile, Sprite)> {
+        let to_player = player_pos - self.sprite.position;
+        let aim = if to_player.magnitude2() > 0.0 {
+            to_player.y.atan2(to_player.x)
+        } else {
+            0.0
+        };
+
+        self.boss_phase()
+            .pattern()
+            .into_iter()
+            .map(|angle| {
+                let angle = aim + angle;
+                let mut sprite = Sprite::new(
+                    self.sprite.position.x,
+                    self.sprite.position.y,
+                    FIREBALL_SIZE,
+                    FIREBALL_SIZE,
+                );
+                sprite.velocity = vec2(angle.cos(), angle.sin()) * FIREBALL_SPEED;
+                (Projectile::Fireball, sprite)
+            })
+            .collect()
+    }
+
+    //Hovers back and forth across the room without falling and shoots
+    //volleys of fireballs at the player once they are seen,
+    //idle_cooldown is the time until the next volley
+    pub fn update_boss(
+        &mut self,
+        dt: f32,
+        level: &Level,
+        player_pos: &Vector2<f32>,
+        projectiles: &mut Vec<(Projectile, Sprite)>,
+    ) {
+        let phase = self.boss_phase();
+        self.sprite.velocity.x =
+            self.def().speed * phase.speed_multiplier() * self.sprite.velocity.x.signum();
+        if self
+            .sprite
+            .sweep_x(self.sprite.velocity.x * dt, level)
+            .is_some()
+        {
+            self.sprite.velocity.x *= -1.0;
+        }
+
+        //Bob up and down based on the position in the room so that
+        //the boss always stays at the same height in the same place
+        let k = 2.0 * PI / HOVER_LENGTH;
+        self.sprite.velocity.y =
+            HOVER_HEIGHT * k * (k * self.sprite.position.x).cos() * self.sprite.velocity.x;
+        self.sprite.sweep_y(self.sprite.velocity.y * dt, level);
+
+        if self.state != EnemyState::Chase && self.sees_player(level, player_pos) {
+            self.state = EnemyState::Chase;
+            self.idle_cooldown = phase.cooldown();
+        }
+
+        if self.state == EnemyState::Chase {
+            self.idle_cooldown -= dt;
+            if self.idle_cooldown < 0.0 {
+                projectiles.extend(self.boss_volley(player_pos));
+                self.idle_cooldown = phase.cooldown();
+            }
+        }
+
+        if self.state == EnemyState::Chase && self.idle_cooldown > phase.cooldown() - CAST_TIME {
+            self.set_animation(self.def().chase);
+        } else {
+            self.set_animation(self.def().walk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite::enemy::EnemyType;
+
+    fn boss() -> Enemy {
+        let warlock = EnemyType::from_name("warlock").expect("missing warlock definition");
+        Enemy::new(8.0, 3.0, warlock, false)
+    }
+
+    #[test]
+    fn test_boss_phases() {
+        let mut boss = boss();
+        let max_health = boss.health;
+        assert_eq!(boss.boss_phase(), BossPhase::Aimed);
+        boss.health = max_health * 2 / 3;
+        assert_eq!(boss.boss_phase(), BossPhase::Spread);
+        boss.health = max_health / 3;
+        assert_eq!(boss.boss_phase(), BossPhase::Ring);
+    }
+
+    #[test]
+    fn test_boss_volleys() {
+        let mut boss = boss();
+        let player_pos = vec2(4.0, 3.0);
+        let max_health = boss.health;
+        for (health, count) in [(max_health, 1), (max_health / 2, 5), (1, 10)] {
+            boss.health = health;
+            let volley = boss.boss_volley(&player_pos);
+            assert_eq!(volley.len(), count);
+            assert!(volley
+                .iter()
+                .all(|(projectile, _)| *projectile == Projectile::Fireball));
+        }
+
+        //The aimed fireball flies straight at the player
+        boss.health = max_health;
+        let volley = boss.boss_volley(&player_pos);
+        let velocity = volley[0].1.velocity;
+        assert!((velocity - vec2(-FIREBALL_SPEED, 0.0)).magnitude() < 0.001);
+    }
+}
@@ -5,23 +5,28 @@ use crate::{
     level::{Level, Tile},
     Sprite,
 };
-use cgmath::{InnerSpace, Vector2};
+use cgmath::Vector2;
 
 impl Enemy {
-    pub fn update_chicken(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) {
+    //Wanders around and rests at walls and ledges, once the player is
+    //seen it runs after them and jumps over anything in the way
+    pub fn update_charge(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) {
+        let following = self.pursue(dt, level, player_pos);
+        let mut collided = false;
         if ((player_pos.x - self.sprite.position.x).abs() > 0.7
             || (player_pos.y - self.sprite.position.y).abs() > 0.2)
-            && (self.state == EnemyState::Wander || self.state == EnemyState::Chase)
+            && self.state != EnemyState::Idle
+            && !following
         {
-            self.sprite.position.x += self.sprite.velocity.x * dt;
+            collided = self
+                .sprite
+                .sweep_x(self.sprite.velocity.x * dt, level)
+                .is_some();
         }
 
-        //Handle collision
+        //Scan the level for ledges that the sprite might have walked off of
         let (top_left_x, top_left_y, bot_right_x, bot_right_y) = self.tile_bounding_box();
 
-        //Scan the level for tiles the sprite might have collided with
-        //and then uncollide the sprite from the tiles
-        let mut collided = false;
         let mut at_edge = false;
         for x in top_left_x..bot_right_x {
             for y in top_left_y..bot_right_y {
@@ -29,13 +34,8 @@ impl Enemy {
                     continue;
                 }
 
-                if !transparent(level.get_tile(x as u32, y as u32)) {
-                    let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
-                    if self.sprite.intersecting(&hitbox) {
-                        collided = true;
-                    }
-                    self.sprite.uncollide_x(&hitbox);
-                } else if !level.out_of_bounds(x, y - 1)
+                if transparent(level.get_tile(x as u32, y as u32))
+                    && !level.out_of_bounds(x, y - 1)
                     && transparent(level.get_tile(x as u32, y as u32 - 1))
                     && self.state == EnemyState::Wander
                 {
@@ -54,16 +54,14 @@ impl Enemy {
             self.health = 0;
         }
 
-        if (self.sprite.position - player_pos).magnitude() < 8.0
-            && (self.sprite.position.y - player_pos.y).abs() < 2.0
-        {
+        if self.state != EnemyState::Chase && self.sees_player(level, player_pos) {
             self.state = EnemyState::Chase;
-            self.sprite.velocity.x = 2.0 * self.sprite.velocity.x.signum();
+            self.sprite.velocity.x = self.def().chase_speed * self.sprite.velocity.x.signum();
         }
 
         match self.state {
             EnemyState::Wander => {
-                self.sprite.set_animation(1.0, 2, 3);
+                self.set_animation(self.def().walk);
                 if collided || at_edge {
                     self.state = EnemyState::Idle;
                 }
@@ -75,28 +73,27 @@ impl Enemy {
             }
             EnemyState::Chase => {
                 self.idle_cooldown = 0.0;
-                self.sprite.set_animation(0.4, 2, 3);
-
-                if self.sprite.position.x < player_pos.x - 0.5 {
-                    self.sprite.velocity.x = 2.0;
-                } else if self.sprite.position.x > player_pos.x + 0.5 {
-                    self.sprite.velocity.x = -2.0;
-                }
+                self.set_animation(self.def().chase);
 
-                if (self.sprite.position - player_pos).magnitude() > 8.0
-                    && self.sprite.velocity.y <= 0.0
-                {
-                    self.state = EnemyState::Wander;
-                    self.sprite.velocity.x = 1.5 * self.sprite.velocity.x.signum();
+                if !following {
+                    if self.sprite.position.x < player_pos.x - 0.5 {
+                        self.sprite.velocity.x = self.def().chase_speed;
+                    } else if self.sprite.position.x > player_pos.x + 0.5 {
+                        self.sprite.velocity.x = -self.def().chase_speed;
+                    }
                 }
 
                 if collided && !self.falling && self.state == EnemyState::Chase {
                     //Attempt to jump over the obstacle
-                    self.sprite.velocity.y = 8.0;
+                    self.sprite.velocity.y = self.def().jump_speed;
                 }
             }
+            EnemyState::Search => {
+                self.idle_cooldown = 0.0;
+                self.set_animation(self.def().chase);
+            }
             EnemyState::Idle => {
-                self.sprite.set_animation(1.0, 0, 1);
+                self.set_animation(self.def().idle);
                 if self.idle_cooldown < -5.0 {
                     self.idle_cooldown = 5.0;
                     self.state = EnemyState::Wander;
@@ -106,6 +103,8 @@ impl Enemy {
         }
 
         self.idle_cooldown -= dt;
-        self.fall(level, dt);
+        if !following {
+            self.fall(level, dt);
+        }
     }
 }
@@ -0,0 +1,202 @@
+use super::Enemy;
+use crate::level::{
+    navigation::{standing_tile, JumpAbility, LinkType, NavLink},
+    Level,
+};
+
+//How fast enemies go up and down ladders
+const CLIMB_SPEED: f32 = 2.0;
+//How far past the center of its tile an enemy runs before jumping
+const JUMP_TAKEOFF: f32 = 0.3;
+
+//First link of the path between two nodes with the version
+//of the navigation graph that the path was found in
+pub(super) struct PathStep {
+    start: (i32, i32),
+    goal: (i32, i32),
+    nav_version: u64,
+    link: Option<NavLink>,
+}
+
+impl Enemy {
+    fn jump_ability(&self) -> JumpAbility {
+        JumpAbility {
+            speed: self.def().chase_speed,
+            jump_speed: self.def().jump_speed,
+        }
+    }
+
+    //Returns the y position of the enemy when it is standing in a tile on row y
+    fn standing_y(&self, y: i32) -> f32 {
+        y as f32 - 0.5 + self.sprite.dimensions.y / 2.0
+    }
+
+    //Moves toward x at the chase speed without going past it,
+    //returns true if the enemy ran into a tile
+    fn steer_to(&mut self, x: f32, dt: f32, level: &Level) -> bool {
+        let dx = x - self.sprite.position.x;
+        if dx.abs() > 0.01 {
+            self.sprite.velocity.x = self.def().chase_speed * dx.signum();
+        }
+        let step = (self.sprite.velocity.x * dt).clamp(-dx.abs(), dx.abs());
+        self.sprite.sweep_x(step, level).is_some()
+    }
+
+    //Moves toward y at the climb speed without going past it,
+    //returns true once the enemy is there
+    fn climb_to(&mut self, y: f32, dt: f32, level: &Level) -> bool {
+        let dy = y - self.sprite.position.y;
+        let step = dy.clamp(-CLIMB_SPEED * dt, CLIMB_SPEED * dt);
+        self.sprite.sweep_y(step, level);
+        self.sprite.velocity.y = 0.0;
+        self.falling = false;
+        dy.abs() <= CLIMB_SPEED * dt
+    }
+
+    //Returns the first link of the path from start to goal, the last
+    //path is used if the nodes and the level are the same as before
+    fn next_link(&mut self, start: (i32, i32), goal: (i32, i32), level: &Level) -> Option<NavLink> {
+        let nav_version = level.nav_version();
+        match &self.path {
+            Some(path)
+                if path.start == start && path.goal == goal && path.nav_version == nav_version =>
+            {
+                path.link
+            }
+            _ => {
+                let link = level
+                    .find_path(start, goal, &self.jump_ability())
+                    .and_then(|path| path.first().copied());
+                self.path = Some(PathStep {
+                    start,
+                    goal,
+                    nav_version,
+                    link,
+                });
+                link
+            }
+        }
+    }
+
+    //Follows the navigation graph of the level toward the goal node,
+    //the path is found again every time the enemy is in a new tile.
+    //Returns false if there is no path and the enemy did not move.
+    pub fn follow_path(&mut self, dt: f32, level: &Level, goal: (i32, i32)) -> bool {
+        let start = standing_tile(self.sprite.position, self.sprite.dimensions.y);
+
+        //In the middle of a jump or a drop, keep heading for where it lands
+        if self.falling || !level.is_nav_node(start) {
+            let Some((x, _)) = self.path_target else {
+                return false;
+            };
+            self.steer_to(x as f32, dt, level);
+            self.fall(level, dt);
+            return true;
+        }
+
+        let Some(link) = self.next_link(start, goal, level) else {
+            self.path_target = None;
+            return false;
+        };
+        self.path_target = Some((link.x, link.y));
+
+        //Enemies on a ladder have to be level with the tile before moving on
+        let on_ladder = level.is_ladder_node(start);
+        if link.link_type == LinkType::Climb {
+            self.steer_to(link.x as f32, dt, level);
+            self.climb_to(self.standing_y(link.y), dt, level);
+            return true;
+        } else if on_ladder && !self.climb_to(self.standing_y(start.1), dt, level) {
+            return true;
+        }
+
+        let collided = self.steer_to(link.x as f32, dt, level);
+        let dir = (link.x - start.0).signum() as f32;
+        if let LinkType::Jump { .. } = link.link_type {
+            if collided || (self.sprite.position.x - start.0 as f32) * dir >= JUMP_TAKEOFF {
+                self.sprite.velocity.y = self.def().jump_speed;
+                self.falling = true;
+            }
+        }
+
+        if !on_ladder || self.falling {
+            self.fall(level, dt);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{PLAYER_HEIGHT, SIM_TIMESTEP},
+        level::{Level, Tile},
+        sprite::enemy::{EnemyState, EnemyType},
+    };
+    use cgmath::vec2;
+
+    #[test]
+    fn test_chase_up_ladder() {
+        //Room with a platform at y = 3 and a ladder up to it
+        let mut level = Level::new(12, 12);
+        for x in 1..11 {
+            for y in 1..12 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+        for x in 1..6 {
+            level.set_tile(x, 3, Tile::Brick);
+        }
+        for y in 1..=3 {
+            level.set_tile(6, y, Tile::Ladder);
+        }
+
+        let skeleton = EnemyType::from_name("skeleton").expect("missing skeleton definition");
+        let mut enemy = Enemy::new(8.0, 1.0, skeleton, true);
+        //The platform is in the way so the enemy goes to where it last saw the player
+        let player_pos = vec2(3.0, 3.5 + PLAYER_HEIGHT / 2.0);
+        assert!(!enemy.sees_player(&level, &player_pos));
+        enemy.state = EnemyState::Chase;
+        enemy.last_seen = Some((3, 4));
+        enemy.search_cooldown = 10.0;
+        let mut projectiles = vec![];
+        for _ in 0..(10.0 / SIM_TIMESTEP) as u32 {
+            enemy.update(SIM_TIMESTEP, &level, &player_pos, &mut projectiles);
+        }
+
+        let (x, y) = standing_tile(enemy.sprite.position, enemy.sprite.dimensions.y);
+        assert_eq!((x, y), (3, 4));
+    }
+
+    #[test]
+    fn test_path_is_reused() {
+        let mut level = Level::new(12, 6);
+        for x in 1..11 {
+            for y in 1..6 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+
+        let skeleton = EnemyType::from_name("skeleton").expect("missing skeleton definition");
+        let mut enemy = Enemy::new(8.0, 1.0, skeleton, true);
+        let start = standing_tile(enemy.sprite.position, enemy.sprite.dimensions.y);
+        let link = enemy.next_link(start, (3, 1), &level);
+        assert_eq!(link.map(|link| (link.x, link.y)), Some((7, 1)));
+
+        //The path is not searched for again while nothing changes
+        let cached = NavLink {
+            x: 9,
+            y: 1,
+            link_type: LinkType::Walk,
+        };
+        if let Some(path) = &mut enemy.path {
+            path.link = Some(cached);
+        }
+        assert_eq!(enemy.next_link(start, (3, 1), &level), Some(cached));
+
+        //Changing the level finds the path again
+        level.set_tile(1, 5, Tile::Brick);
+        assert_eq!(enemy.next_link(start, (3, 1), &level), link);
+    }
+}
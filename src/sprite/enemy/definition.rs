@@ -0,0 +1,412 @@
+use crate::{game::hiscore::OTHER_RUN_ENDS, parse::parse_value};
+use std::{fs::File, io::Read, sync::OnceLock};
+
+/*
+ * Enemies are defined in a text file, each definition starts with
+ * `enemy <name>` and is followed by one property on each line:
+ *
+ * //Comments start with two slashes
+ * enemy skeleton
+ * hitbox 0.9 1.0          (width and height in tiles)
+ * health 4
+ * speed 0.7               (walking speed in tiles per second)
+ * damage 2                (damage done to the player on contact)
+ * score 40
+ * sprite 4 2              (column and row of the first frame in the sprite sheet)
+ * walk 2 3 1.0            (first frame, last frame and length of an animation)
+ * chase 2 3 0.4           (optional, defaults to the walk animation)
+ * idle 0 1 1.0            (optional, defaults to the walk animation)
+ * behaviour charge        (patrol, stalk, charge, shoot or boss)
+ * sight 6.0 3.0           (optional, distance and height the player is seen from)
+ * chase_speed 1.4         (optional, defaults to the speed)
+ * jump 6.0                (optional, jump speed when chasing the player)
+ *
+ * How often each enemy is spawned is set by the difficulty profiles
+ * */
+
+pub const ENEMY_DEFINITIONS_PATH: &str = "assets/enemies.txt";
+//The sprite sheet is a grid of SPRITE_SHEET_SIZE x SPRITE_SHEET_SIZE frames
+const SPRITE_SHEET_SIZE: u8 = 8;
+
+static DEFINITIONS: OnceLock<Result<Vec<EnemyDef>, String>> = OnceLock::new();
+
+//How an enemy moves and attacks, each behaviour is
+//implemented in the module with the same name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Behaviour {
+    //Walks back and forth and turns around at walls and ledges
+    Patrol,
+    //Patrols until it sees the player and then follows them
+    Stalk,
+    //Runs after the player and jumps over obstacles, stops to rest
+    //at walls and ledges
+    Charge,
+    //Runs after the player and stops to shoot fireballs
+    Shoot,
+    //Hovers across a boss room and shoots patterns of fireballs
+    //that change as it loses health, only spawned in boss rooms
+    Boss,
+}
+
+impl Behaviour {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Behaviour::Patrol => "patrol",
+            Behaviour::Stalk => "stalk",
+            Behaviour::Charge => "charge",
+            Behaviour::Shoot => "shoot",
+            Behaviour::Boss => "boss",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Behaviour::Patrol,
+            Behaviour::Stalk,
+            Behaviour::Charge,
+            Behaviour::Shoot,
+            Behaviour::Boss,
+        ]
+        .into_iter()
+        .find(|behaviour| behaviour.name() == name)
+    }
+}
+
+//Range of frames that is looped through, frames are counted
+//from the first frame of the enemy in the sprite sheet
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Animation {
+    pub first_frame: u8,
+    pub last_frame: u8,
+    //Time in seconds to go through every frame once
+    pub duration: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EnemyDef {
+    pub name: String,
+    pub hitbox: (f32, f32),
+    pub health: i32,
+    pub speed: f32,
+    pub damage: i32,
+    pub score: u32,
+    pub sprite: (u8, u8),
+    pub walk: Animation,
+    pub chase: Animation,
+    pub idle: Animation,
+    pub behaviour: Behaviour,
+    //The player is seen if they are closer than the distance and
+    //the difference in height is less than the height
+    pub sight_distance: f32,
+    pub sight_height: f32,
+    pub chase_speed: f32,
+    pub jump_speed: f32,
+}
+
+//Properties of a definition that has not been completely read yet
+#[derive(Default)]
+struct PartialDef {
+    name: String,
+    //Line that the definition starts on
+    line: usize,
+    hitbox: Option<(f32, f32)>,
+    health: Option<i32>,
+    speed: Option<f32>,
+    damage: Option<i32>,
+    score: Option<u32>,
+    sprite: Option<(u8, u8)>,
+    walk: Option<Animation>,
+    chase: Option<Animation>,
+    idle: Option<Animation>,
+    behaviour: Option<Behaviour>,
+    sight: Option<(f32, f32)>,
+    chase_speed: Option<f32>,
+    jump_speed: Option<f32>,
+}
+
+fn parse_pair<T: std::str::FromStr>(
+    values: &[&str],
+    names: (&str, &str),
+) -> Result<(T, T), String> {
+    match values {
+        [first, second] => Ok((parse_value(first, names.0)?, parse_value(second, names.1)?)),
+        _ => Err(format!("expected: {} {}", names.0, names.1)),
+    }
+}
+
+fn parse_single<T: std::str::FromStr>(values: &[&str], name: &str) -> Result<T, String> {
+    match values {
+        [value] => parse_value(value, name),
+        _ => Err(format!("expected: {name}")),
+    }
+}
+
+fn parse_animation(values: &[&str]) -> Result<Animation, String> {
+    match values {
+        [first, last, duration] => Ok(Animation {
+            first_frame: parse_value(first, "first frame")?,
+            last_frame: parse_value(last, "last frame")?,
+            duration: parse_value(duration, "duration")?,
+        }),
+        _ => Err("expected: first_frame last_frame duration".to_string()),
+    }
+}
+
+impl PartialDef {
+    fn set_property(&mut self, property: &str, values: &[&str]) -> Result<(), String> {
+        match property {
+            "hitbox" => self.hitbox = Some(parse_pair(values, ("width", "height"))?),
+            "health" => self.health = Some(parse_single(values, "health")?),
+            "speed" => self.speed = Some(parse_single(values, "speed")?),
+            "damage" => self.damage = Some(parse_single(values, "damage")?),
+            "score" => self.score = Some(parse_single(values, "score")?),
+            "sprite" => self.sprite = Some(parse_pair(values, ("column", "row"))?),
+            "walk" => self.walk = Some(parse_animation(values)?),
+            "chase" => self.chase = Some(parse_animation(values)?),
+            "idle" => self.idle = Some(parse_animation(values)?),
+            "behaviour" => {
+                let name: String = parse_single(values, "behaviour")?;
+                let behaviour =
+                    Behaviour::from_name(&name).ok_or(format!("unknown behaviour: {name}"))?;
+                self.behaviour = Some(behaviour);
+            }
+            "sight" => {
+                //The height is optional and defaults to the distance
+                self.sight = Some(match values {
+                    [distance] => {
+                        let distance = parse_value(distance, "distance")?;
+                        (distance, distance)
+                    }
+                    _ => parse_pair(values, ("distance", "height"))?,
+                })
+            }
+            "chase_speed" => self.chase_speed = Some(parse_single(values, "chase_speed")?),
+            "jump" => self.jump_speed = Some(parse_single(values, "jump")?),
+            _ => return Err(format!("unknown property: {property}")),
+        }
+        Ok(())
+    }
+
+    //Checks that every required property was given and that the
+    //values make sense, returns every problem that was found
+    fn finish(self) -> Result<EnemyDef, Vec<String>> {
+        let mut problems = vec![];
+        let mut require = |value: bool, problem: &str| {
+            if !value {
+                problems.push(format!("enemy {}: {problem}", self.name));
+            }
+        };
+
+        require(self.hitbox.is_some(), "missing hitbox");
+        require(self.health.is_some(), "missing health");
+        require(self.speed.is_some(), "missing speed");
+        require(self.damage.is_some(), "missing damage");
+        require(self.score.is_some(), "missing score");
+        require(self.sprite.is_some(), "missing sprite");
+        require(self.walk.is_some(), "missing walk animation");
+        require(self.behaviour.is_some(), "missing behaviour");
+        if let Some((w, h)) = self.hitbox {
+            require(w > 0.0 && h > 0.0, "hitbox must be larger than 0");
+        }
+        require(
+            self.health.is_none_or(|hp| hp > 0),
+            "health must be at least 1",
+        );
+        require(
+            self.damage.is_none_or(|dmg| dmg >= 0),
+            "damage can not be negative",
+        );
+        for speed in [self.speed, self.chase_speed, self.jump_speed]
+            .into_iter()
+            .flatten()
+        {
+            require(speed >= 0.0, "speeds can not be negative");
+        }
+        if let Some((distance, height)) = self.sight {
+            require(
+                distance >= 0.0 && height >= 0.0,
+                "sight can not be negative",
+            );
+        }
+        require(
+            !OTHER_RUN_ENDS.iter().any(|end| end.to_text() == self.name),
+            "name is already used by something else",
+        );
+
+        //Every frame of every animation has to be in the sprite sheet
+        if let Some((column, row)) = self.sprite {
+            require(
+                row < SPRITE_SHEET_SIZE,
+                "sprite is outside of the sprite sheet",
+            );
+            for animation in [self.walk, self.chase, self.idle].into_iter().flatten() {
+                require(
+                    animation.first_frame <= animation.last_frame,
+                    "animation ends before it starts",
+                );
+                require(
+                    column as u32 + (animation.last_frame as u32) < SPRITE_SHEET_SIZE as u32,
+                    "animation is outside of the sprite sheet",
+                );
+                require(
+                    animation.duration > 0.0,
+                    "animation length must be larger than 0",
+                );
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        //Every required property was checked above
+        let walk = self.walk.unwrap_or(Animation {
+            first_frame: 0,
+            last_frame: 0,
+            duration: 1.0,
+        });
+        let speed = self.speed.unwrap_or_default();
+        let (sight_distance, sight_height) = self.sight.unwrap_or((5.0, 5.0));
+        Ok(EnemyDef {
+            name: self.name,
+            hitbox: self.hitbox.unwrap_or_default(),
+            health: self.health.unwrap_or_default(),
+            speed,
+            damage: self.damage.unwrap_or_default(),
+            score: self.score.unwrap_or_default(),
+            sprite: self.sprite.unwrap_or_default(),
+            walk,
+            chase: self.chase.unwrap_or(walk),
+            idle: self.idle.unwrap_or(walk),
+            behaviour: self.behaviour.unwrap_or(Behaviour::Patrol),
+            sight_distance,
+            sight_height,
+            chase_speed: self.chase_speed.unwrap_or(speed),
+            jump_speed: self.jump_speed.unwrap_or_default(),
+        })
+    }
+}
+
+//Reads every definition in the text, all problems that are
+//found are returned in the error, one on each line
+pub fn parse_definitions(text: &str) -> Result<Vec<EnemyDef>, String> {
+    let mut problems = vec![];
+    let mut partial_defs: Vec<PartialDef> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match (&split[..], partial_defs.last_mut()) {
+            (["enemy", name], _) => {
+                if partial_defs.iter().any(|def| def.name == *name) {
+                    problems.push(format!("line {}: enemy {name} is defined twice", i + 1));
+                }
+                partial_defs.push(PartialDef {
+                    name: name.to_string(),
+                    line: i + 1,
+                    ..Default::default()
+                });
+            }
+            (["enemy", ..], _) => problems.push(format!("line {}: expected: enemy name", i + 1)),
+            ([property, values @ ..], Some(def)) => {
+                if let Err(msg) = def.set_property(property, values) {
+                    problems.push(format!("line {}: {msg}", i + 1));
+                }
+            }
+            _ => problems.push(format!("line {}: expected: enemy name", i + 1)),
+        }
+    }
+
+    let mut defs = vec![];
+    for partial_def in partial_defs {
+        let line = partial_def.line;
+        match partial_def.finish() {
+            Ok(def) => defs.push(def),
+            Err(def_problems) => problems.extend(
+                def_problems
+                    .into_iter()
+                    .map(|problem| format!("line {line}: {problem}")),
+            ),
+        }
+    }
+    if defs.is_empty() && problems.is_empty() {
+        problems.push("no enemies are defined".to_string());
+    }
+
+    if problems.is_empty() {
+        Ok(defs)
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+pub fn load_definitions(path: &str) -> Result<Vec<EnemyDef>, String> {
+    let mut buf = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut buf))
+        .map_err(|e| format!("{path}: {e}"))?;
+    parse_definitions(&buf).map_err(|msg| format!("{path}:\n{msg}"))
+}
+
+//Returns the enemy definitions, they are loaded from
+//`ENEMY_DEFINITIONS_PATH` the first time this is called
+pub fn definitions() -> Result<&'static [EnemyDef], String> {
+    DEFINITIONS
+        .get_or_init(|| load_definitions(ENEMY_DEFINITIONS_PATH))
+        .as_ref()
+        .map(|defs| defs.as_slice())
+        .map_err(|msg| msg.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAT_PROPERTIES: &str = "hitbox 0.5 0.5\nhealth 1\nspeed 2.0\ndamage 1\nscore 5\nsprite 0 6\nwalk 0 3 0.5\nbehaviour stalk\nsight 4.0\n";
+
+    #[test]
+    fn test_parse_definitions() {
+        let text = format!(
+            "//Comment\nenemy bat\n{BAT_PROPERTIES}jump 3.0\n\nenemy rat\n{BAT_PROPERTIES}"
+        );
+        let defs = parse_definitions(&text).expect("failed to parse definitions");
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs[0].name, "bat");
+        assert_eq!(defs[0].hitbox, (0.5, 0.5));
+        assert_eq!(defs[0].behaviour, Behaviour::Stalk);
+        //Optional properties use the other properties as defaults
+        assert_eq!(defs[0].idle, defs[0].walk);
+        assert_eq!(defs[0].chase_speed, 2.0);
+        assert_eq!((defs[0].sight_distance, defs[0].sight_height), (4.0, 4.0));
+        assert_eq!(defs[0].jump_speed, 3.0);
+        assert_eq!(defs[1].name, "rat");
+        assert_eq!(defs[1].jump_speed, 0.0);
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        //Every problem is reported
+        let text =
+            format!("enemy bat\n{BAT_PROPERTIES}health 0\nwalk 4 9 1.0\nteeth 32\n\nenemy lava\n");
+        let msg = parse_definitions(&text).expect_err("definitions should be invalid");
+        assert!(msg.contains("line 13: unknown property: teeth"));
+        assert!(msg.contains("line 1: enemy bat: health must be at least 1"));
+        assert!(msg.contains("enemy bat: animation is outside of the sprite sheet"));
+        assert!(msg.contains("line 15: enemy lava: missing health"));
+        assert!(msg.contains("enemy lava: name is already used by something else"));
+
+        assert!(parse_definitions("health 3\n").is_err());
+        assert!(parse_definitions("enemy bat\nenemy bat\n").is_err());
+    }
+
+    #[test]
+    fn test_enemy_definitions_file() {
+        let defs = definitions().expect("failed to load enemy definitions");
+        assert_eq!(defs[0].name, "slime");
+        assert_eq!(defs.len(), 6);
+        assert_eq!(defs[5].behaviour, Behaviour::Boss);
+    }
+}
@@ -1,54 +1,64 @@
 use super::{Enemy, EnemyState, ENEMY_ATTACK_COOLDOWN};
 use crate::{game::Projectile, level::transparent, level::Level, Sprite};
-use cgmath::{InnerSpace, Vector2};
+use cgmath::Vector2;
+
+const FIREBALL_SPEED: f32 = 4.0;
 
 impl Enemy {
-    pub fn update_demon(
+    //Wanders around and runs after the player once they are seen,
+    //every so often it stops to shoot a fireball
+    pub fn update_shoot(
         &mut self,
         dt: f32,
         level: &Level,
         player_pos: &Vector2<f32>,
         projectiles: &mut Vec<(Projectile, Sprite)>,
     ) {
+        let following = self.pursue(dt, level, player_pos);
+        let mut collided = false;
         if ((player_pos.x - self.sprite.position.x).abs() > 0.7
             || (player_pos.y - self.sprite.position.y).abs() > 0.2)
             && self.state != EnemyState::Idle
+            && !following
         {
-            self.sprite.position.x += self.sprite.velocity.x * dt;
+            collided = self
+                .sprite
+                .sweep_x(self.sprite.velocity.x * dt, level)
+                .is_some();
         }
 
-        //Handle collision
+        //Scan the level for ledges that the sprite might have walked off of
         let (top_left_x, top_left_y, bot_right_x, bot_right_y) = self.tile_bounding_box();
 
-        //Scan the level for tiles the sprite might have collided with
-        //and then uncollide the sprite from the tiles
-        let mut collided = false;
         for x in top_left_x..bot_right_x {
             for y in top_left_y..bot_right_y {
                 if level.out_of_bounds(x, y) {
                     continue;
                 }
 
-                if !transparent(level.get_tile(x as u32, y as u32))
-                    || (!level.out_of_bounds(x, y - 1)
-                        && transparent(level.get_tile(x as u32, y as u32 - 1)))
+                if transparent(level.get_tile(x as u32, y as u32))
+                    && !level.out_of_bounds(x, y - 1)
+                    && transparent(level.get_tile(x as u32, y as u32 - 1))
                 {
                     let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
                     if self.sprite.intersecting(&hitbox) {
                         collided = true;
                     }
-                    self.sprite.uncollide_x(&hitbox);
+                    //Enemies following a path jump and drop off of ledges
+                    if !following {
+                        self.sprite.uncollide_x(&hitbox);
+                    }
                 }
             }
         }
 
         match self.state {
             EnemyState::Wander => {
-                if (self.sprite.position - player_pos).magnitude() < 5.0 {
+                if self.sees_player(level, player_pos) {
                     self.state = EnemyState::Chase;
                 }
 
-                self.sprite.set_animation(1.0, 2, 3);
+                self.set_animation(self.def().walk);
                 if collided {
                     self.state = EnemyState::Idle;
                     self.idle_cooldown = 2.0;
@@ -61,17 +71,14 @@ impl Enemy {
                 }
             }
             EnemyState::Chase => {
-                self.sprite.set_animation(0.4, 2, 3);
+                self.set_animation(self.def().chase);
 
-                if self.sprite.position.x < player_pos.x - 0.5 {
-                    self.sprite.velocity.x = 1.4;
-                } else if self.sprite.position.x > player_pos.x + 0.5 {
-                    self.sprite.velocity.x = -1.4;
-                }
-
-                if (self.sprite.position - player_pos).magnitude() > 5.0 {
-                    self.state = EnemyState::Wander;
-                    self.sprite.velocity.x = 1.1 * self.sprite.velocity.x.signum();
+                if !following {
+                    if self.sprite.position.x < player_pos.x - 0.5 {
+                        self.sprite.velocity.x = self.def().chase_speed;
+                    } else if self.sprite.position.x > player_pos.x + 0.5 {
+                        self.sprite.velocity.x = -self.def().chase_speed;
+                    }
                 }
 
                 if self.idle_cooldown < -1.0 {
@@ -79,12 +86,15 @@ impl Enemy {
                     self.idle_cooldown = 2.0;
                 }
             }
+            EnemyState::Search => self.set_animation(self.def().chase),
             EnemyState::Idle => {
-                self.sprite.set_animation(1.0, 0, 1);
+                self.set_animation(self.def().idle);
                 if self.idle_cooldown < -2.0 {
                     self.idle_cooldown = 2.0;
-                    if (self.sprite.position - player_pos).magnitude() < 5.0 {
+                    if self.sees_player(level, player_pos) {
                         self.state = EnemyState::Chase;
+                    } else if self.last_seen.is_some() {
+                        self.state = EnemyState::Search;
                     } else {
                         self.state = EnemyState::Wander;
                     }
@@ -94,12 +104,14 @@ impl Enemy {
                 if self.attack_cooldown < 0.0 && self.idle_cooldown > 0.0 {
                     self.sprite.animation_timer = 0.75;
                     let mut sprite = Sprite::new(
-                        self.sprite.position.x + self.sprite.velocity.x.signum() * (0.45 + 0.2),
+                        self.sprite.position.x
+                            + self.sprite.velocity.x.signum()
+                                * (self.sprite.dimensions.x / 2.0 + 0.2),
                         self.sprite.position.y - 0.1,
                         0.3,
                         0.3,
                     );
-                    sprite.velocity.x = 4.0 * self.sprite.velocity.x.signum();
+                    sprite.velocity.x = FIREBALL_SPEED * self.sprite.velocity.x.signum();
 
                     projectiles.push((Projectile::Fireball, sprite));
                     self.attack_cooldown = ENEMY_ATTACK_COOLDOWN;
@@ -108,6 +120,8 @@ impl Enemy {
         }
 
         self.idle_cooldown -= dt;
-        self.fall(level, dt);
+        if !following {
+            self.fall(level, dt);
+        }
     }
 }
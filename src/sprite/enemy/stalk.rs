@@ -1,36 +1,43 @@
 use super::{Enemy, EnemyState};
 use crate::{level::transparent, level::Level, Sprite};
-use cgmath::{InnerSpace, Vector2};
+use cgmath::Vector2;
 
 impl Enemy {
-    pub fn update_eyeball(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) {
-        if (player_pos.x - self.sprite.position.x).abs() > 0.7
-            || (player_pos.y - self.sprite.position.y).abs() > 0.2
+    //Patrols until the player is seen and then follows them
+    pub fn update_stalk(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) {
+        let following = self.pursue(dt, level, player_pos);
+        let mut collided = false;
+        if !following
+            && ((player_pos.x - self.sprite.position.x).abs() > 0.7
+                || (player_pos.y - self.sprite.position.y).abs() > 0.2)
         {
-            self.sprite.position.x += self.sprite.velocity.x * dt;
+            collided = self
+                .sprite
+                .sweep_x(self.sprite.velocity.x * dt, level)
+                .is_some();
         }
 
-        //Handle collision
+        //Scan the level for ledges that the sprite might have walked off of
         let (top_left_x, top_left_y, bot_right_x, bot_right_y) = self.tile_bounding_box();
 
-        //Scan the level for tiles the sprite might have collided with
-        //and then uncollide the sprite from the tiles
-        let mut collided = false;
         for x in top_left_x..bot_right_x {
             for y in top_left_y..bot_right_y {
                 if level.out_of_bounds(x, y) {
                     continue;
                 }
 
-                if !transparent(level.get_tile(x as u32, y as u32))
-                    || (!level.out_of_bounds(x, y - 1)
-                        && transparent(level.get_tile(x as u32, y as u32 - 1)))
+                if transparent(level.get_tile(x as u32, y as u32))
+                    && !level.out_of_bounds(x, y - 1)
+                    && transparent(level.get_tile(x as u32, y as u32 - 1))
                 {
                     let hitbox = Sprite::new(x as f32, y as f32, 1.0, 1.0);
                     if self.sprite.intersecting(&hitbox) {
                         collided = true;
                     }
-                    self.sprite.uncollide_x(&hitbox);
+                    //Enemies following a path jump and drop off of ledges
+                    if !following {
+                        self.sprite.uncollide_x(&hitbox);
+                    }
                 }
             }
         }
@@ -39,29 +46,25 @@ impl Enemy {
             EnemyState::Wander => {
                 if collided {
                     self.sprite.velocity.x *= -1.0;
-                    self.sprite.position.x += self.sprite.velocity.x * dt;
+                    self.sprite.sweep_x(self.sprite.velocity.x * dt, level);
                 }
 
-                if (self.sprite.position - player_pos).magnitude() < 5.0
-                    && (self.sprite.position.y - player_pos.y).abs() < 1.0
-                {
+                if self.sees_player(level, player_pos) {
                     self.state = EnemyState::Chase;
                 }
             }
-            EnemyState::Chase => {
+            EnemyState::Chase if !following => {
                 if self.sprite.position.x < player_pos.x - 0.5 {
                     self.sprite.velocity.x = self.sprite.velocity.x.abs();
                 } else if self.sprite.position.x > player_pos.x + 0.5 {
                     self.sprite.velocity.x = -self.sprite.velocity.x.abs();
                 }
-
-                if (self.sprite.position - player_pos).magnitude() > 5.0 {
-                    self.state = EnemyState::Wander;
-                }
             }
             _ => {}
         }
 
-        self.fall(level, dt);
+        if !following {
+            self.fall(level, dt);
+        }
     }
 }
@@ -0,0 +1,222 @@
+use super::{Enemy, EnemyState};
+use crate::{
+    game::PLAYER_HEIGHT,
+    level::{navigation::standing_tile, Level},
+};
+use cgmath::{vec2, InnerSpace, Vector2};
+use std::f32::consts::PI;
+
+//Enemies see the player if they are at most this angle
+//above or below the direction the enemy is facing
+const VISION_ANGLE: f32 = PI / 3.0;
+//Enemies notice the player this close to them even if they are facing away
+const NOTICE_DISTANCE: f32 = 1.5;
+//How far below the top of the enemy its eyes are
+const EYE_DEPTH: f32 = 0.2;
+//How long an enemy looks for the player after losing sight of them
+const SEARCH_TIME: f32 = 5.0;
+//How long an enemy looks each way once it gets to where the player was last seen
+const LOOK_TIME: f32 = 1.0;
+
+impl Enemy {
+    fn eye_position(&self) -> Vector2<f32> {
+        self.sprite.position + vec2(0.0, self.sprite.dimensions.y / 2.0 - EYE_DEPTH)
+    }
+
+    //Returns true if nothing is in the way of the enemy seeing the player,
+    //the enemy only needs to see the center or the top of the player
+    fn clear_view(&self, level: &Level, player_pos: &Vector2<f32>) -> bool {
+        let eye = self.eye_position();
+        let player_top = player_pos + vec2(0.0, PLAYER_HEIGHT / 2.0 - 0.05);
+        (self.sprite.position - player_pos).magnitude() < self.def().sight_distance
+            && (self.sprite.position.y - player_pos.y).abs() < self.def().sight_height
+            && (level.line_of_sight(eye, *player_pos) || level.line_of_sight(eye, player_top))
+    }
+
+    //Returns true if the enemy can see the player, the player has to be in
+    //front of the enemy unless they are right next to it
+    pub(super) fn sees_player(&self, level: &Level, player_pos: &Vector2<f32>) -> bool {
+        let to_player = player_pos - self.sprite.position;
+        let facing = if self.sprite.flipped { -1.0 } else { 1.0 };
+        let in_view = to_player.magnitude() < NOTICE_DISTANCE
+            || to_player.x * facing >= to_player.magnitude() * VISION_ANGLE.cos();
+        in_view && self.clear_view(level, player_pos)
+    }
+
+    //Stops searching for the player and goes back to wandering around
+    fn stop_search(&mut self) {
+        self.state = EnemyState::Wander;
+        self.last_seen = None;
+        self.sprite.velocity.x = self.def().speed * self.sprite.velocity.x.signum();
+    }
+
+    //Keeps track of where the player was last seen while chasing them,
+    //enemies that lose sight of the player search for them there.
+    //A chasing enemy keeps track of the player in every direction.
+    pub(super) fn update_awareness(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) {
+        match self.state {
+            EnemyState::Chase => {
+                if self.clear_view(level, player_pos) {
+                    let tile = standing_tile(*player_pos, PLAYER_HEIGHT);
+                    if let Some(node) = level.nearest_nav_node(tile) {
+                        self.last_seen = Some(node);
+                    }
+                    self.search_cooldown = SEARCH_TIME;
+                } else if self.last_seen.is_some() {
+                    self.state = EnemyState::Search;
+                } else {
+                    self.stop_search();
+                }
+            }
+            EnemyState::Search => {
+                self.search_cooldown -= dt;
+                if self.sees_player(level, player_pos) {
+                    self.state = EnemyState::Chase;
+                } else if self.search_cooldown < 0.0 || self.last_seen.is_none() {
+                    self.stop_search();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //Turns back and forth where the player was last seen,
+    //the search ends once the search cooldown runs out
+    fn look_around(&mut self, dt: f32, level: &Level) {
+        let facing = if (self.search_cooldown / LOOK_TIME) as i32 % 2 == 0 {
+            1.0
+        } else {
+            -1.0
+        };
+        //The enemy faces the way it is moving
+        self.sprite.velocity.x = self.def().speed * facing;
+        self.fall(level, dt);
+    }
+
+    //Chases the player or searches for them, returns true if the enemy
+    //followed a path or looked around and false if the behaviour should
+    //move the enemy
+    pub(super) fn pursue(&mut self, dt: f32, level: &Level, player_pos: &Vector2<f32>) -> bool {
+        self.update_awareness(dt, level, player_pos);
+        match (self.state, self.last_seen) {
+            (EnemyState::Chase, Some(goal)) => self.follow_path(dt, level, goal),
+            (EnemyState::Search, Some(goal)) => {
+                if self.follow_path(dt, level, goal) {
+                    true
+                } else if standing_tile(self.sprite.position, self.sprite.dimensions.y) == goal {
+                    self.look_around(dt, level);
+                    true
+                } else {
+                    //Give up if the enemy can not get to where the player was
+                    self.stop_search();
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::SIM_TIMESTEP, level::Tile, sprite::enemy::EnemyType};
+
+    fn test_level() -> Level {
+        let mut level = Level::new(16, 8);
+        for x in 1..15 {
+            for y in 1..7 {
+                level.set_tile(x, y, Tile::Air);
+            }
+        }
+        level
+    }
+
+    fn demon(x: f32, flipped: bool) -> Enemy {
+        let demon = EnemyType::from_name("demon").expect("missing demon definition");
+        Enemy::new(x, 1.0, demon, flipped)
+    }
+
+    #[test]
+    fn test_vision() {
+        let mut level = test_level();
+        let player_pos = vec2(4.0, 1.0 - (1.0 - PLAYER_HEIGHT) / 2.0);
+
+        //The player has to be in front of the enemy
+        assert!(demon(7.0, true).sees_player(&level, &player_pos));
+        assert!(!demon(7.0, false).sees_player(&level, &player_pos));
+        assert!(demon(5.0, false).sees_player(&level, &player_pos));
+        //Too far away
+        assert!(!demon(12.0, true).sees_player(&level, &player_pos));
+
+        //Walls block the view but the player can be seen under a ledge
+        level.set_tile(6, 1, Tile::Brick);
+        level.set_tile(6, 2, Tile::Brick);
+        assert!(!demon(7.0, true).sees_player(&level, &player_pos));
+        level.set_tile(6, 1, Tile::Air);
+        assert!(demon(7.0, true).sees_player(&level, &player_pos));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut level = test_level();
+        let mut enemy = demon(8.0, true);
+        enemy.state = EnemyState::Chase;
+        let player_pos = vec2(4.0, 1.0 - (1.0 - PLAYER_HEIGHT) / 2.0);
+        enemy.update_awareness(0.1, &level, &player_pos);
+        assert_eq!(enemy.last_seen, Some((4, 1)));
+
+        //The player hides behind a wall and the enemy goes to look for them
+        for y in 1..7 {
+            level.set_tile(6, y, Tile::Brick);
+        }
+        enemy.update_awareness(0.1, &level, &player_pos);
+        assert_eq!(enemy.state, EnemyState::Search);
+        assert_eq!(enemy.last_seen, Some((4, 1)));
+
+        //The enemy gives up after a while
+        for _ in 0..(SEARCH_TIME / 0.1) as u32 + 1 {
+            enemy.update_awareness(0.1, &level, &player_pos);
+        }
+        assert_eq!(enemy.state, EnemyState::Wander);
+        assert_eq!(enemy.last_seen, None);
+    }
+
+    #[test]
+    fn test_search_last_seen() {
+        let mut level = test_level();
+        //The player is behind a wall on the other side of the enemy
+        for y in 1..7 {
+            level.set_tile(11, y, Tile::Brick);
+        }
+        let player_pos = vec2(13.0, 1.0 - (1.0 - PLAYER_HEIGHT) / 2.0);
+        let mut enemy = demon(8.0, true);
+        enemy.state = EnemyState::Search;
+        enemy.last_seen = Some((3, 1));
+        enemy.search_cooldown = SEARCH_TIME;
+
+        //The enemy walks over to where the player was last seen
+        let mut projectiles = vec![];
+        let mut ticks = 0;
+        while standing_tile(enemy.sprite.position, enemy.sprite.dimensions.y) != (3, 1) {
+            enemy.update(SIM_TIMESTEP, &level, &player_pos, &mut projectiles);
+            ticks += 1;
+            assert!(
+                ticks as f32 * SIM_TIMESTEP < SEARCH_TIME,
+                "enemy did not get there"
+            );
+        }
+        assert_eq!(enemy.state, EnemyState::Search);
+
+        //It looks both ways without leaving the tile until the search runs out
+        let mut facing = vec![];
+        while enemy.state == EnemyState::Search {
+            let tile = standing_tile(enemy.sprite.position, enemy.sprite.dimensions.y);
+            assert_eq!(tile, (3, 1));
+            enemy.update(SIM_TIMESTEP, &level, &player_pos, &mut projectiles);
+            facing.push(enemy.sprite.flipped);
+        }
+        assert!(facing.contains(&true) && facing.contains(&false));
+        assert_eq!(enemy.last_seen, None);
+    }
+}
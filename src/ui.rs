@@ -1,9 +1,20 @@
 use std::io::Read;
 
+use crate::game::input_config::{Action, ACTIONS};
+use crate::game::settings::SETTINGS;
 use crate::gfx::VertexArrayObject;
 use crate::shader::ShaderProgram;
 
 pub const ICONS_TEXTURE_SCALE: f32 = 16.0;
+//Position of the list of keys bound to each action on the controls screen
+pub const CONTROLS_KEYS_X: f32 = -180.0;
+//Position of the value of each setting on the settings screen
+pub const SETTINGS_VALUE_X: f32 = 160.0;
+
+//Returns the height of the row of an action on the controls screen
+pub fn controls_row_y(index: usize) -> f32 {
+    160.0 - index as f32 * 36.0
+}
 
 //Displays a string of text on the screen
 //text is an array of bytes representing an ascii string
@@ -106,8 +117,28 @@ pub enum ButtonAction {
     QuitGame,
     GotoMainMenu,
     StartGame,
+    ContinueGame,
     GotoHighScores,
+    SortHighScores,
+    NextProfile,
+    GotoNewProfile,
+    //Creates a profile with the name that was typed in and switches to it
+    CreateProfile,
     GotoAbout,
+    GotoStatistics,
+    GotoAchievements,
+    GotoControls,
+    //Waits for a key to bind to the action at this index in `ACTIONS`
+    RebindAction(usize),
+    //Removes every key bound to the action at this index in `ACTIONS`
+    ClearBinding(usize),
+    ResetControls,
+    //Switches the controls screen between keys and gamepad inputs
+    SwitchControlsDevice,
+    GotoSettings,
+    //Changes the setting at this index in `settings::SETTINGS`
+    //by one step up (1) or down (-1)
+    ChangeSetting(usize, i32),
 }
 
 pub struct MenuElement {
@@ -149,14 +180,17 @@ impl MenuElement {
 
     //Displays the button to the screen, if the mouse is hovering over
     //the button than the button will be darkened to indicate the mouse
-    //is hovering over it
+    //is hovering over it, the focused button is highlighted
     pub fn display_button(
         &self,
         rect_vao: &VertexArrayObject,
         shader_program: &ShaderProgram,
         win_info: &WindowInfo,
+        focused: bool,
     ) {
-        if self.mouse_hovering(win_info) {
+        if focused {
+            shader_program.uniform_vec4f("uColor", 1.0, 0.8, 0.2, 1.0);
+        } else if self.mouse_hovering(win_info) {
             shader_program.uniform_vec4f("uColor", 0.5, 0.5, 0.5, 1.0);
         } else {
             shader_program.uniform_vec4f("uColor", 1.0, 1.0, 1.0, 1.0);
@@ -202,6 +236,8 @@ impl MenuElement {
 pub struct Menu {
     text: Vec<MenuElement>,
     buttons: Vec<MenuElement>,
+    //Index of the button selected with a gamepad
+    focused: Option<usize>,
 }
 
 impl Menu {
@@ -210,33 +246,226 @@ impl Menu {
             buttons: vec![
                 //Start game
                 MenuElement::button(b"Start!", 0.0, -0.0, 16.0, ButtonAction::StartGame),
+                //Switch to the next profile
+                MenuElement::button(
+                    b"Change Profile",
+                    -90.0,
+                    108.0,
+                    8.0,
+                    ButtonAction::NextProfile,
+                ),
+                //Create a new profile
+                MenuElement::button(
+                    b"New Profile",
+                    90.0,
+                    108.0,
+                    8.0,
+                    ButtonAction::GotoNewProfile,
+                ),
+                //Continue a saved game
+                MenuElement::button(b"Continue", 0.0, -40.0, 16.0, ButtonAction::ContinueGame),
                 //Go to highscores
                 MenuElement::button(
                     b"High Scores",
                     0.0,
-                    -60.0,
+                    -80.0,
                     16.0,
                     ButtonAction::GotoHighScores,
                 ),
+                //Go to statistics
+                MenuElement::button(
+                    b"Statistics",
+                    0.0,
+                    -120.0,
+                    16.0,
+                    ButtonAction::GotoStatistics,
+                ),
+                //Go to settings
+                MenuElement::button(b"Settings", 0.0, -160.0, 16.0, ButtonAction::GotoSettings),
                 //Go to about page
-                MenuElement::button(b"About", 0.0, -120.0, 16.0, ButtonAction::GotoAbout),
+                MenuElement::button(b"About", 0.0, -200.0, 16.0, ButtonAction::GotoAbout),
                 //Quit game
-                MenuElement::button(b"Quit", 0.0, -180.0, 16.0, ButtonAction::QuitGame),
+                MenuElement::button(b"Quit", 0.0, -240.0, 16.0, ButtonAction::QuitGame),
             ],
             text: vec![
                 MenuElement::text(b"Scale the Tower", 0.0, 180.0, 22.0),
                 MenuElement::text(b"Created for the 2023 Game Off Jam", 0.0, 80.0, 8.0),
             ],
+            focused: None,
         }
     }
 
     pub fn create_hiscore_menu() -> Self {
         Self {
             buttons: vec![
+                //Change the field the high scores are sorted by
+                MenuElement::button(
+                    b"Change Sort",
+                    0.0,
+                    -150.0,
+                    8.0,
+                    ButtonAction::SortHighScores,
+                ),
                 //Go to main menu
                 MenuElement::button(b"Main Menu", 0.0, -192.0, 16.0, ButtonAction::GotoMainMenu),
             ],
             text: vec![MenuElement::text(b"High Scores", 0.0, 192.0, 16.0)],
+            focused: None,
+        }
+    }
+
+    pub fn create_statistics_screen() -> Self {
+        Self {
+            buttons: vec![
+                //Go to achievements
+                MenuElement::button(
+                    b"Achievements",
+                    0.0,
+                    -200.0,
+                    8.0,
+                    ButtonAction::GotoAchievements,
+                ),
+                //Go to main menu
+                MenuElement::button(b"Main Menu", 0.0, -250.0, 16.0, ButtonAction::GotoMainMenu),
+            ],
+            text: vec![MenuElement::text(b"Statistics", 0.0, 220.0, 16.0)],
+            focused: None,
+        }
+    }
+
+    pub fn create_achievements_screen() -> Self {
+        Self {
+            buttons: vec![
+                //Go to statistics
+                MenuElement::button(
+                    b"Statistics",
+                    0.0,
+                    -200.0,
+                    8.0,
+                    ButtonAction::GotoStatistics,
+                ),
+                //Go to main menu
+                MenuElement::button(b"Main Menu", 0.0, -250.0, 16.0, ButtonAction::GotoMainMenu),
+            ],
+            text: vec![MenuElement::text(b"Achievements", 0.0, 220.0, 16.0)],
+            focused: None,
+        }
+    }
+
+    pub fn create_controls_screen() -> Self {
+        let mut buttons = vec![];
+        for (i, action) in ACTIONS.iter().enumerate() {
+            //Click on an action to bind another key to it
+            buttons.push(MenuElement::button(
+                action.name().as_bytes(),
+                -270.0,
+                controls_row_y(i),
+                8.0,
+                ButtonAction::RebindAction(i),
+            ));
+            buttons.push(MenuElement::button(
+                b"Clear",
+                320.0,
+                controls_row_y(i),
+                8.0,
+                ButtonAction::ClearBinding(i),
+            ));
+        }
+        //Show the keys or the gamepad inputs
+        buttons.push(MenuElement::button(
+            b"Keyboard / Gamepad",
+            -170.0,
+            -200.0,
+            8.0,
+            ButtonAction::SwitchControlsDevice,
+        ));
+        //Go back to the default controls
+        buttons.push(MenuElement::button(
+            b"Reset to Defaults",
+            170.0,
+            -200.0,
+            8.0,
+            ButtonAction::ResetControls,
+        ));
+        //Go to main menu
+        buttons.push(MenuElement::button(
+            b"Main Menu",
+            0.0,
+            -250.0,
+            16.0,
+            ButtonAction::GotoMainMenu,
+        ));
+
+        Self {
+            buttons,
+            text: vec![MenuElement::text(b"Controls", 0.0, 230.0, 16.0)],
+            focused: None,
+        }
+    }
+
+    pub fn create_settings_screen() -> Self {
+        let mut buttons = vec![];
+        let mut text = vec![MenuElement::text(b"Settings", 0.0, 230.0, 16.0)];
+        for (i, setting) in SETTINGS.iter().enumerate() {
+            text.push(MenuElement::text(
+                setting.name().as_bytes(),
+                -160.0,
+                controls_row_y(i),
+                8.0,
+            ));
+            //The value of the setting is shown between these buttons
+            buttons.push(MenuElement::button(
+                b"<",
+                SETTINGS_VALUE_X - 100.0,
+                controls_row_y(i),
+                8.0,
+                ButtonAction::ChangeSetting(i, -1),
+            ));
+            buttons.push(MenuElement::button(
+                b">",
+                SETTINGS_VALUE_X + 100.0,
+                controls_row_y(i),
+                8.0,
+                ButtonAction::ChangeSetting(i, 1),
+            ));
+        }
+        //Go to controls
+        buttons.push(MenuElement::button(
+            b"Controls",
+            0.0,
+            -160.0,
+            8.0,
+            ButtonAction::GotoControls,
+        ));
+        //Go to main menu
+        buttons.push(MenuElement::button(
+            b"Main Menu",
+            0.0,
+            -250.0,
+            16.0,
+            ButtonAction::GotoMainMenu,
+        ));
+
+        Self {
+            buttons,
+            text,
+            focused: None,
+        }
+    }
+
+    pub fn create_new_profile_screen() -> Self {
+        Self {
+            buttons: vec![
+                //Create the profile
+                MenuElement::button(b"Create", 0.0, -40.0, 16.0, ButtonAction::CreateProfile),
+                //Go to main menu
+                MenuElement::button(b"Main Menu", 0.0, -250.0, 16.0, ButtonAction::GotoMainMenu),
+            ],
+            text: vec![
+                MenuElement::text(b"New Profile", 0.0, 180.0, 16.0),
+                MenuElement::text(b"Type the name of the profile", 0.0, 100.0, 8.0),
+            ],
+            focused: None,
         }
     }
 
@@ -252,6 +481,7 @@ impl Menu {
                 MenuElement::text(b"Paused", 0.0, 128.0, 32.0),
                 MenuElement::text(b"Press Escape to Unpause", 0.0, 48.0, 8.0),
             ],
+            focused: None,
         }
     }
 
@@ -264,6 +494,7 @@ impl Menu {
                 MenuElement::button(b"Quit", 0.0, -72.0, 16.0, ButtonAction::QuitGame),
             ],
             text: vec![MenuElement::text(b"Game Over!", 0.0, 96.0, 24.0)],
+            focused: None,
         }
     }
 
@@ -279,6 +510,7 @@ impl Menu {
                 MenuElement::text(b"You Did It!", 0.0, 180.0, 24.0),
                 MenuElement::text(b"You scaled the tower!", 0.0, 126.0, 8.0),
             ],
+            focused: None,
         }
     }
 
@@ -314,6 +546,7 @@ impl Menu {
                 ButtonAction::GotoMainMenu,
             )],
             text: about_text,
+            focused: None,
         }
     }
 
@@ -327,11 +560,56 @@ impl Menu {
             text.display_text(rect_vao, text_shader)
         }
 
-        for button in &self.buttons {
-            button.display_button(rect_vao, text_shader, win_info)
+        for (i, button) in self.buttons.iter().enumerate() {
+            button.display_button(rect_vao, text_shader, win_info, self.focused == Some(i))
         }
     }
 
+    //Indices of the buttons in the order the focus moves through
+    //them, from the top of the screen to the bottom and left to right
+    fn focus_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.buttons.len()).collect();
+        order.sort_by(|a, b| {
+            let (a, b) = (&self.buttons[*a], &self.buttons[*b]);
+            b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x))
+        });
+        order
+    }
+
+    //Moves the focus `offset` buttons forward in the focus order,
+    //if no button is focused then the first button gets the focus
+    fn move_focus(&mut self, offset: isize) {
+        let order = self.focus_order();
+        if order.is_empty() {
+            return;
+        }
+
+        let next = match self
+            .focused
+            .and_then(|i| order.iter().position(|b| *b == i))
+        {
+            Some(position) => (position as isize + offset).rem_euclid(order.len() as isize),
+            None => 0,
+        };
+        self.focused = Some(order[next as usize]);
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.focused = None;
+    }
+
+    //Moves the focus with the Up and Down actions and returns the
+    //action of the focused button when Attack is pressed
+    pub fn navigate(&mut self, action: Action) -> Option<ButtonAction> {
+        match action {
+            Action::Up => self.move_focus(-1),
+            Action::Down => self.move_focus(1),
+            Action::Attack => return self.focused.and_then(|i| self.buttons[i].click_action),
+            _ => {}
+        }
+        None
+    }
+
     pub fn get_clicked_button_action(&self, win_info: &WindowInfo) -> Option<ButtonAction> {
         for button in &self.buttons {
             if button.mouse_hovering(win_info) {
@@ -341,3 +619,60 @@ impl Menu {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_menu_navigation() {
+        let mut menu = Menu::create_pause_menu();
+        //Nothing is picked until a button has the focus
+        assert!(menu.navigate(Action::Attack).is_none());
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::GotoMainMenu)
+        ));
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::QuitGame)
+        ));
+        //The focus wraps around
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::GotoMainMenu)
+        ));
+        menu.navigate(Action::Up);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::QuitGame)
+        ));
+        menu.clear_focus();
+        assert!(menu.navigate(Action::Attack).is_none());
+    }
+
+    #[test]
+    fn test_menu_focus_order() {
+        //Buttons are focused from the top of the screen to the bottom
+        //even if they are not in that order in the menu
+        let mut menu = Menu::create_main_menu();
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::NextProfile)
+        ));
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::GotoNewProfile)
+        ));
+        menu.navigate(Action::Down);
+        assert!(matches!(
+            menu.navigate(Action::Attack),
+            Some(ButtonAction::StartGame)
+        ));
+    }
+}